@@ -0,0 +1,716 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::lexer::{LiteralType, Token, TokenType};
+use crate::parser::{Expr, FunctionTypes, Pattern, Stmt, StmtVisitor, TypeAnnotation, Visitor};
+use crate::raki_log::RakiError;
+
+use super::{Signature, Type};
+
+struct ClassType {
+  init: Option<Rc<Signature>>,
+  superclass: Option<String>,
+}
+
+// Gradual type checker run before the program. Annotated names are checked
+// against their annotation, unannotated ones get the type of their
+// initializer unless they are reassigned somewhere, and anything unknown is
+// `Any` and never reported.
+pub struct Checker {
+  scopes: RefCell<Vec<HashMap<String, Type>>>,
+  classes: RefCell<HashMap<String, ClassType>>,
+  // Enums and traits, whose values are checked as `Any`
+  opaque: RefCell<HashSet<String>>,
+  results: RefCell<Vec<Type>>,
+  reassigned: RefCell<HashSet<String>>,
+  collecting: Cell<bool>,
+  errors: RefCell<Vec<RakiError>>,
+}
+
+impl Default for Checker {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Checker {
+  pub fn new() -> Checker {
+    Checker {
+      scopes: RefCell::new(vec![HashMap::new()]),
+      classes: RefCell::new(HashMap::new()),
+      opaque: RefCell::new(HashSet::new()),
+      results: RefCell::new(Vec::new()),
+      reassigned: RefCell::new(HashSet::new()),
+      collecting: Cell::new(false),
+      errors: RefCell::new(Vec::new()),
+    }
+  }
+
+  pub fn check(&self, statements: &[Stmt]) {
+    // A silent first pass finds declared classes and reassigned variables,
+    // so later declarations and assignments are known up front
+    self.collecting.set(true);
+    self.check_statements(statements);
+    self.collecting.set(false);
+
+    self.scopes.replace(vec![HashMap::new()]);
+    self.check_statements(statements);
+  }
+
+  pub fn errors(&self) -> Vec<RakiError> {
+    self.errors.borrow().clone()
+  }
+
+  fn error(&self, line: u32, message: &str) {
+    if !self.collecting.get() {
+      self.errors.borrow_mut().push(RakiError::Type { line, message: message.to_string() });
+    }
+  }
+
+  fn check_statements(&self, statements: &[Stmt]) {
+    for stmt in statements {
+      self.visit_stmt(stmt);
+    }
+  }
+
+  fn scoped<T>(&self, f: impl FnOnce() -> T) -> T {
+    self.scopes.borrow_mut().push(HashMap::new());
+    let result = f();
+    self.scopes.borrow_mut().pop();
+    result
+  }
+
+  fn declare(&self, name: &str, t: Type) {
+    let mut scopes = self.scopes.borrow_mut();
+    scopes.last_mut().expect("globals scope").insert(name.to_string(), t);
+  }
+
+  // The type of an unannotated declaration only holds while the name isn't
+  // reassigned, after that it can hold anything
+  fn declare_inferred(&self, name: &str, t: Type) {
+    match self.reassigned.borrow().contains(name) {
+      true => self.declare(name, Type::Any),
+      false => self.declare(name, t),
+    }
+  }
+
+  fn lookup(&self, name: &str) -> Option<Type> {
+    self.scopes.borrow().iter().rev().find_map(|scope| scope.get(name)).cloned()
+  }
+
+  fn annotated(&self, annotation: &TypeAnnotation) -> Type {
+    let name = annotation.name.lexeme.as_str();
+    let t = match name {
+      "Any" => Type::Any,
+      "Int" => Type::Int,
+      "Num" => Type::Num,
+      "String" => Type::String,
+      "Bool" => Type::Bool,
+      "Nil" => Type::Nil,
+      "List" => Type::List,
+      "Map" => Type::Map,
+      "Tuple" => Type::Tuple,
+      "Range" => Type::Range,
+      "Function" => Type::Function(None),
+      name if self.classes.borrow().contains_key(name) => Type::Instance(name.to_string()),
+      name if self.opaque.borrow().contains(name) => Type::Any,
+      name => {
+        self.error(annotation.name.line, &format!("Unknown type '{}'.", name));
+        Type::Any
+      }
+    };
+
+    match annotation.nullable {
+      true => t.nullable(),
+      false => t,
+    }
+  }
+
+  fn annotated_or_any(&self, annotation: &Option<TypeAnnotation>) -> Type {
+    match annotation {
+      Some(annotation) => self.annotated(annotation),
+      None => Type::Any,
+    }
+  }
+
+  fn signature(&self, name: &str, types: &FunctionTypes) -> Rc<Signature> {
+    let params = types.params.iter().map(|param| self.annotated_or_any(param)).collect();
+    Rc::new(Signature { name: name.to_string(), params, result: self.annotated_or_any(&types.result) })
+  }
+
+  // Like `Type::accepts`, but instances of subclasses are accepted where their
+  // superclass is expected
+  fn accepts(&self, target: &Type, value: &Type) -> bool {
+    if target.accepts(value) {
+      return true;
+    }
+
+    let class_of = |t: &Type| match t {
+      Type::Instance(name) => Some(name.clone()),
+      Type::Nullable(t) => match t.as_ref() {
+        Type::Instance(name) => Some(name.clone()),
+        _ => None,
+      },
+      _ => None,
+    };
+    match (class_of(target), class_of(value)) {
+      (Some(ancestor), Some(class)) if self.inherits(&class, &ancestor) => {
+        let upcast = match value {
+          Type::Nullable(_) => Type::Instance(ancestor).nullable(),
+          _ => Type::Instance(ancestor),
+        };
+        target.accepts(&upcast)
+      }
+      _ => false,
+    }
+  }
+
+  fn inherits(&self, class: &str, ancestor: &str) -> bool {
+    let classes = self.classes.borrow();
+    let mut current = classes.get(class).and_then(|class| class.superclass.clone());
+    while let Some(name) = current {
+      if name == ancestor {
+        return true;
+      }
+      current = classes.get(&name).and_then(|class| class.superclass.clone());
+    }
+    false
+  }
+
+  // Classes without an initializer inherit their superclass's
+  fn init_signature(&self, class: &str) -> Option<Rc<Signature>> {
+    let classes = self.classes.borrow();
+    let mut current = classes.get(class);
+    while let Some(class) = current {
+      if class.init.is_some() {
+        return class.init.clone();
+      }
+      current = class.superclass.as_ref().and_then(|superclass| classes.get(superclass));
+    }
+    None
+  }
+
+  // Checks the methods of a class or trait, class fields are checked in their
+  // own scope since they don't declare variables
+  fn check_members(&self, members: &[Stmt]) {
+    for member in members {
+      match member {
+        Stmt::Function { name, params, body, types } => self.check_function(&self.signature(&name.lexeme, types), params, body, name.line),
+        field => self.scoped(|| self.visit_stmt(field)),
+      }
+    }
+  }
+
+  fn check_function(&self, signature: &Signature, params: &[Token], body: &[Stmt], line: u32) {
+    self.scoped(|| {
+      for (param, t) in params.iter().zip(&signature.params) {
+        self.declare(&param.lexeme, t.clone());
+      }
+      self.results.borrow_mut().push(signature.result.clone());
+      self.check_statements(body);
+      self.results.borrow_mut().pop();
+    });
+
+    // Falling off the end returns nil
+    let result = &signature.result;
+    if *result != Type::Any && !result.may_be_nil() && !always_exits(body) {
+      self.error(line, &format!("'{}' can end without returning a value of type {}.", signature.name, result));
+    }
+  }
+
+  fn check_condition(&self, condition: &Expr, keyword: &Token) {
+    let t = self.visit_expr(condition);
+    if t.may_be_nil() || t.is_builtin() && t != Type::Bool {
+      self.error(keyword.line, &format!("Condition of '{}' must be Bool, not {}.", keyword.lexeme, t));
+    }
+  }
+
+  fn check_call(&self, signature: &Signature, arguments: &[Type], paren: &Token) {
+    if arguments.len() != signature.params.len() {
+      self.error(paren.line, &format!("Expected {} arguments but got {}.", signature.params.len(), arguments.len()));
+      return;
+    }
+
+    for (i, (param, argument)) in signature.params.iter().zip(arguments).enumerate() {
+      if !self.accepts(param, argument) {
+        self.error(paren.line, &format!("Argument {} of '{}' expects {}, got {}.", i + 1, signature.name, param, argument));
+      }
+    }
+  }
+
+  fn mark_reassigned(&self, target: &Expr) {
+    match target {
+      Expr::Variable { name } => {
+        self.reassigned.borrow_mut().insert(name.lexeme.clone());
+      }
+      Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.mark_reassigned(element)),
+      _ => {}
+    }
+  }
+
+  fn declare_target(&self, target: &Expr) {
+    match target {
+      Expr::Variable { name } => self.declare(&name.lexeme, Type::Any),
+      Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.declare_target(element)),
+      _ => {}
+    }
+  }
+
+  fn declare_pattern(&self, pattern: &Pattern) {
+    match pattern {
+      Pattern::Binding { name } => self.declare(&name.lexeme, Type::Any),
+      Pattern::List { elements, rest } => {
+        elements.iter().for_each(|element| self.declare_pattern(element));
+        if let Some(rest) = rest {
+          self.declare_pattern(rest);
+        }
+      }
+      Pattern::Tuple { elements } | Pattern::Class { fields: elements, .. } => elements.iter().for_each(|element| self.declare_pattern(element)),
+      Pattern::Map { entries } => entries.iter().for_each(|(_, pattern)| self.declare_pattern(pattern)),
+      Pattern::Wildcard | Pattern::Literal { .. } | Pattern::Value { .. } | Pattern::Range { .. } => {}
+    }
+  }
+
+  fn binary_type(&self, left: Type, right: Type, operator: &Token) -> Type {
+    use TokenType::*;
+    // Comparing values of different types is false at runtime, not an error
+    match operator.r#type {
+      EqualEqual | BangEqual | Is | Greater | GreaterEqual | Less | LessEqual => return Type::Bool,
+      Plus | Minus | Star | Slash => {}
+      _ => return Type::Any,
+    }
+
+    if let Some(t) = [&left, &right].into_iter().find(|t| t.may_be_nil()) {
+      self.error(operator.line, &format!("Operand of '{}' may be nil, its type is {}.", operator.lexeme, t));
+      return Type::Any;
+    }
+    if !left.is_builtin() || !right.is_builtin() {
+      return Type::Any;
+    }
+
+    let numeric = |t: &Type| matches!(t, Type::Int | Type::Num);
+    let t = match operator.r#type {
+      Plus if left == Type::String && right == Type::String => Some(Type::String),
+      Slash if numeric(&left) && numeric(&right) => Some(Type::Num),
+      Plus | Minus | Star if numeric(&left) && numeric(&right) => Some(left.clone().join(right.clone())),
+      _ => None,
+    };
+
+    t.unwrap_or_else(|| {
+      self.error(operator.line, &format!("Operator '{}' cannot be applied to {} and {}.", operator.lexeme, left, right));
+      Type::Any
+    })
+  }
+
+  // `x != nil` narrows `x` in the then branch, `x == nil` in the else branch
+  fn narrowed(&self, condition: &Expr) -> Option<(bool, String, Type)> {
+    let Expr::Binary { left, right, operator } = condition else {
+      return None;
+    };
+    let name = match (left.as_ref(), right.as_ref()) {
+      (Expr::Variable { name }, Expr::Literal { value: LiteralType::None }) | (Expr::Literal { value: LiteralType::None }, Expr::Variable { name }) => name,
+      _ => return None,
+    };
+    let Some(Type::Nullable(t)) = self.lookup(&name.lexeme) else {
+      return None;
+    };
+
+    match operator.r#type {
+      TokenType::BangEqual => Some((true, name.lexeme.clone(), *t)),
+      TokenType::EqualEqual => Some((false, name.lexeme.clone(), *t)),
+      _ => None,
+    }
+  }
+
+  fn check_branch(&self, branch: &Stmt, narrowed: Option<(String, Type)>) {
+    self.scoped(|| {
+      if let Some((name, t)) = narrowed {
+        self.declare(&name, t);
+      }
+      self.visit_stmt(branch);
+    });
+  }
+}
+
+// Whether every path through `statements` returns or throws
+fn always_exits(statements: &[Stmt]) -> bool {
+  statements.iter().any(|stmt| match stmt {
+    Stmt::Return { .. } | Stmt::Throw { .. } => true,
+    Stmt::Block { statements } => always_exits(statements),
+    Stmt::If { then_branch, else_branch: Some(else_branch), .. } => always_exits(std::slice::from_ref(then_branch)) && always_exits(std::slice::from_ref(else_branch)),
+    Stmt::While { condition: Expr::Literal { value: LiteralType::Bool(true) }, body, .. } => !breaks(body),
+    Stmt::Try { finally: Some(finally), .. } if always_exits(finally) => true,
+    Stmt::Try { body, catch, .. } => always_exits(body) && catch.as_ref().is_none_or(|(_, handler)| always_exits(handler)),
+    _ => false,
+  })
+}
+
+// Whether `stmt` breaks out of the loop it is in, not counting nested loops
+fn breaks(stmt: &Stmt) -> bool {
+  match stmt {
+    Stmt::Break => true,
+    Stmt::Block { statements } => statements.iter().any(breaks),
+    Stmt::If { then_branch, else_branch, .. } => breaks(then_branch) || else_branch.as_deref().is_some_and(breaks),
+    Stmt::Try { body, catch, finally } => body.iter().chain(catch.iter().flat_map(|(_, handler)| handler)).chain(finally.iter().flatten()).any(breaks),
+    _ => false,
+  }
+}
+
+impl Visitor<Type> for Checker {
+  fn visit_expr(&self, expr: &Expr) -> Type {
+    match expr {
+      Expr::Literal { value } => match value {
+        LiteralType::F64(n) if n.fract() == 0.0 => Type::Int,
+        LiteralType::F64(_) => Type::Num,
+        LiteralType::String(_) => Type::String,
+        LiteralType::Bool(_) => Type::Bool,
+        LiteralType::None => Type::Nil,
+      },
+      Expr::Grouping { expr } => self.visit_expr(expr),
+      Expr::Unary { right, operator } => {
+        let t = self.visit_expr(right);
+        match operator.r#type {
+          TokenType::Bang => Type::Bool,
+          _ if t.may_be_nil() => {
+            self.error(operator.line, &format!("Operand of '{}' may be nil, its type is {}.", operator.lexeme, t));
+            Type::Any
+          }
+          _ => match t {
+            Type::Int | Type::Num | Type::Any | Type::Instance(_) => t,
+            t => {
+              self.error(operator.line, &format!("Operator '{}' cannot be applied to {}.", operator.lexeme, t));
+              Type::Any
+            }
+          },
+        }
+      }
+      Expr::Binary { left, right, operator } => {
+        let left = self.visit_expr(left);
+        let right = self.visit_expr(right);
+        self.binary_type(left, right, operator)
+      }
+      Expr::Ternary { condition, left, right, .. } => {
+        self.visit_expr(condition);
+        let left = self.visit_expr(left);
+        left.join(self.visit_expr(right))
+      }
+      Expr::Variable { name } => self.lookup(&name.lexeme).unwrap_or(Type::Any),
+      Expr::Assign { name, value } => {
+        let t = self.visit_expr(value);
+        self.reassigned.borrow_mut().insert(name.lexeme.clone());
+        if let Some(declared) = self.lookup(&name.lexeme)
+          && !self.accepts(&declared, &t)
+        {
+          self.error(name.line, &format!("Cannot assign {} to '{}' of type {}.", t, name.lexeme, declared));
+        }
+        t
+      }
+      Expr::List { elements } => {
+        elements.iter().for_each(|element| _ = self.visit_expr(element));
+        Type::List
+      }
+      Expr::Tuple { elements, .. } => {
+        elements.iter().for_each(|element| _ = self.visit_expr(element));
+        Type::Tuple
+      }
+      Expr::Map { entries, .. } => {
+        for (key, value) in entries {
+          self.visit_expr(key);
+          self.visit_expr(value);
+        }
+        Type::Map
+      }
+      Expr::Index { object, index, .. } => {
+        self.visit_expr(object);
+        self.visit_expr(index);
+        Type::Any
+      }
+      Expr::IndexSet { object, index, value, .. } => {
+        self.visit_expr(object);
+        self.visit_expr(index);
+        self.visit_expr(value)
+      }
+      Expr::Get { object, .. } => {
+        self.visit_expr(object);
+        Type::Any
+      }
+      Expr::Set { object, value, .. } => {
+        self.visit_expr(object);
+        self.visit_expr(value)
+      }
+      Expr::Call { callee, arguments, paren } => {
+        let callee = self.visit_expr(callee);
+        let arguments: Vec<Type> = arguments.iter().map(|argument| self.visit_expr(argument)).collect();
+        match callee {
+          Type::Function(Some(signature)) => {
+            self.check_call(&signature, &arguments, paren);
+            signature.result.clone()
+          }
+          Type::Class(name) => {
+            match self.init_signature(&name) {
+              Some(init) => self.check_call(&init, &arguments, paren),
+              None if !arguments.is_empty() => self.error(paren.line, &format!("Expected 0 arguments but got {}.", arguments.len())),
+              None => {}
+            }
+            Type::Instance(name)
+          }
+          t if t.may_be_nil() => {
+            self.error(paren.line, &format!("Cannot call a value that may be nil, its type is {}.", t));
+            Type::Any
+          }
+          Type::Any | Type::Instance(_) | Type::Function(None) => Type::Any,
+          t => {
+            self.error(paren.line, &format!("Value of type {} is not callable.", t));
+            Type::Any
+          }
+        }
+      }
+      Expr::Destructure { target, value, .. } => {
+        self.mark_reassigned(target);
+        self.visit_expr(value)
+      }
+      Expr::Range { start, end, .. } => {
+        self.visit_expr(start);
+        self.visit_expr(end);
+        Type::Range
+      }
+      Expr::This { .. } | Expr::Super { .. } => Type::Any,
+      Expr::Lambda { params, body, keyword, types } => {
+        let signature = self.signature(&format!("lambda@{}", keyword.line), types);
+        self.check_function(&signature, params, body, keyword.line);
+        Type::Function(Some(signature))
+      }
+      Expr::Match { value, arms, .. } => {
+        self.visit_expr(value);
+        let types = arms.iter().map(|arm| {
+          self.scoped(|| {
+            self.declare_pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+              self.visit_expr(guard);
+            }
+            self.visit_expr(&arm.body)
+          })
+        });
+        types.reduce(Type::join).unwrap_or(Type::Any)
+      }
+    }
+  }
+}
+
+impl StmtVisitor<()> for Checker {
+  fn visit_stmt(&self, stmt: &Stmt) {
+    match stmt {
+      Stmt::Expression { expr } | Stmt::Throw { value: expr, .. } => _ = self.visit_expr(expr),
+      Stmt::Print { values, sep, end, .. } => values.iter().chain(sep).chain(end).for_each(|expr| _ = self.visit_expr(expr)),
+      Stmt::Var { name, initializer, annotation } => {
+        let t = initializer.as_ref().map(|initializer| self.visit_expr(initializer));
+        match (annotation, t) {
+          (Some(annotation), t) => {
+            let declared = self.annotated(annotation);
+            let t = t.unwrap_or(Type::Nil);
+            if !self.accepts(&declared, &t) {
+              self.error(name.line, &format!("Cannot initialize '{}' of type {} with {}.", name.lexeme, declared, t));
+            }
+            self.declare(&name.lexeme, declared);
+          }
+          (None, t) => self.declare_inferred(&name.lexeme, t.unwrap_or(Type::Nil)),
+        }
+      }
+      Stmt::Const { name, initializer, annotation } => {
+        let t = self.visit_expr(initializer);
+        let declared = match annotation {
+          Some(annotation) => self.annotated(annotation),
+          None => t.clone(),
+        };
+        if !self.accepts(&declared, &t) {
+          self.error(name.line, &format!("Cannot initialize '{}' of type {} with {}.", name.lexeme, declared, t));
+        }
+        self.declare(&name.lexeme, declared);
+      }
+      Stmt::Block { statements } => self.scoped(|| self.check_statements(statements)),
+      Stmt::Destructure { target, initializer, .. } => {
+        self.visit_expr(initializer);
+        self.declare_target(target);
+      }
+      Stmt::Function { name, params, body, types } => {
+        let signature = self.signature(&name.lexeme, types);
+        self.declare_inferred(&name.lexeme, Type::Function(Some(signature.clone())));
+        self.check_function(&signature, params, body, name.line);
+      }
+      Stmt::Return { value, keyword } => {
+        let t = value.as_ref().map_or(Type::Nil, |value| self.visit_expr(value));
+        let expected = self.results.borrow().last().cloned().unwrap_or(Type::Any);
+        if !self.accepts(&expected, &t) {
+          self.error(keyword.line, &format!("Expected a return value of type {}, got {}.", expected, t));
+        }
+      }
+      Stmt::If { condition, then_branch, else_branch, keyword } => {
+        self.check_condition(condition, keyword);
+        let narrowed = self.narrowed(condition);
+        let (then_narrowed, else_narrowed) = match narrowed {
+          Some((true, name, t)) => (Some((name, t)), None),
+          Some((false, name, t)) => (None, Some((name, t))),
+          None => (None, None),
+        };
+        self.check_branch(then_branch, then_narrowed);
+        if let Some(else_branch) = else_branch {
+          self.check_branch(else_branch, else_narrowed);
+        }
+      }
+      Stmt::While { condition, body, keyword } => {
+        self.check_condition(condition, keyword);
+        self.visit_stmt(body);
+      }
+      Stmt::ForIn { target, iterable, body, .. } => {
+        self.visit_expr(iterable);
+        self.scoped(|| {
+          self.declare_target(target);
+          self.visit_stmt(body);
+        });
+      }
+      Stmt::Class { name, superclass, traits, methods, getters, setters, statics } => {
+        let init = methods.iter().find_map(|method| match method {
+          Stmt::Function { name: method, types, .. } if method.lexeme == "init" => Some(self.signature(&name.lexeme, types)),
+          _ => None,
+        });
+        let superclass = match superclass {
+          Some(Expr::Variable { name }) => Some(name.lexeme.clone()),
+          _ => None,
+        };
+        self.classes.borrow_mut().insert(name.lexeme.clone(), ClassType { init, superclass });
+        self.declare_inferred(&name.lexeme, Type::Class(name.lexeme.clone()));
+
+        traits.iter().for_each(|t| _ = self.visit_expr(t));
+        [methods, getters, setters, statics].into_iter().for_each(|members| self.check_members(members));
+      }
+      Stmt::Trait { name, defaults, .. } => {
+        self.opaque.borrow_mut().insert(name.lexeme.clone());
+        self.declare(&name.lexeme, Type::Any);
+        self.check_members(defaults);
+      }
+      Stmt::Import { alias, names, .. } => alias.iter().chain(names).for_each(|name| self.declare(&name.lexeme, Type::Any)),
+      Stmt::Export { declaration, .. } => self.visit_stmt(declaration),
+      Stmt::Enum { name, .. } => {
+        self.opaque.borrow_mut().insert(name.lexeme.clone());
+        self.declare(&name.lexeme, Type::Any);
+      }
+      Stmt::Try { body, catch, finally } => {
+        self.scoped(|| self.check_statements(body));
+        if let Some((name, handler)) = catch {
+          self.scoped(|| {
+            self.declare(&name.lexeme, Type::Any);
+            self.check_statements(handler);
+          });
+        }
+        if let Some(finally) = finally {
+          self.scoped(|| self.check_statements(finally));
+        }
+      }
+      Stmt::Break | Stmt::Continue => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lexer::Scanner;
+  use crate::parser::Parser;
+
+  use super::*;
+
+  fn check(source: &str) -> Vec<String> {
+    let mut scanner = Scanner::new(source.to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let checker = Checker::new();
+    checker.check(&statements);
+    checker
+      .errors()
+      .into_iter()
+      .map(|err| match err {
+        RakiError::Type { line, message } => format!("{}: {}", line, message),
+        err => panic!("expected a type error, got {:?}", err),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn reports_annotation_mismatches() {
+    assert_eq!(check("var x: Int = \"a\";"), vec!["1: Cannot initialize 'x' of type Int with String."]);
+    assert_eq!(check("var x: Int = 1.5;\nvar y: Num = 1;\ny = true;"), vec!["1: Cannot initialize 'x' of type Int with Num.", "3: Cannot assign Bool to 'y' of type Num."]);
+    assert_eq!(check("fun f() -> Bool {\n  return 1;\n}"), vec!["2: Expected a return value of type Bool, got Int."]);
+    assert_eq!(check("var p: Pointt;"), vec!["1: Unknown type 'Pointt'."]);
+  }
+
+  #[test]
+  fn reports_missing_returns() {
+    assert_eq!(check("fun f() -> Int { }"), vec!["1: 'f' can end without returning a value of type Int."]);
+    assert_eq!(check("fun f(b: Bool) -> Int {\n  if (b) { return 1; }\n}"), vec!["1: 'f' can end without returning a value of type Int."]);
+    assert_eq!(check("class C {\n  size() -> Int { print 1; }\n}"), vec!["2: 'size' can end without returning a value of type Int."]);
+    assert_eq!(check("var f = fun () -> Int {\n  while (true) { break; }\n};"), vec!["1: 'lambda@1' can end without returning a value of type Int."]);
+
+    let source = "
+      fun a(b: Bool) -> Int { if (b) { return 1; } else { throw \"no\"; } }
+      fun c() -> Int { while (true) { for (i in 0..3) { break; } } }
+      fun d() -> Int { try { return 1; } catch (e) { return 2; } }
+      fun e() -> Int? { }
+      fun f() { }
+      fun g() -> Int { { return 1; } }
+    ";
+    assert!(check(source).is_empty());
+  }
+
+  #[test]
+  fn infers_local_types() {
+    assert_eq!(check("var s = \"a\";\nvar t = s - 1;"), vec!["2: Operator '-' cannot be applied to String and Int."]);
+    assert_eq!(check("fun f(a: String) -> Num { return 1; }\nvar n = f(\"a\") + \"b\";"), vec!["2: Operator '+' cannot be applied to Num and String."]);
+  }
+
+  #[test]
+  fn reports_nil_safety_violations() {
+    let source = "fun f(a: String, b: Num?) -> Bool {\n  var c = b + 1;\n  return true;\n}";
+    assert_eq!(check(source), vec!["2: Operand of '+' may be nil, its type is Num?."]);
+    assert_eq!(check("var x: Int;\nvar y: Int? = nil;\nvar z: Int = y;"), vec!["1: Cannot initialize 'x' of type Int with Nil.", "3: Cannot initialize 'z' of type Int with Int?."]);
+    assert!(check("fun g(n: Num?) -> Num {\n  if (n != nil) { return n + 1; }\n  return 0;\n}").is_empty());
+  }
+
+  #[test]
+  fn reports_wrong_arity_calls() {
+    let source = "fun f(a: String, b: Num?) -> Bool { return true; }\nf(\"a\");\nf(1, nil);\nclass P { init(x, y) {} }\nP(1);";
+    assert_eq!(check(source), vec!["2: Expected 2 arguments but got 1.", "3: Argument 1 of 'f' expects String, got Int.", "5: Expected 2 arguments but got 1."]);
+  }
+
+  #[test]
+  fn accepts_subclass_instances() {
+    let source = "class Base { init(x) {} }\nclass Sub < Base {}\nvar b: Base = Sub(1);\nvar s: Sub? = Base(1);\nSub();";
+    assert_eq!(check(source), vec!["4: Cannot initialize 's' of type Sub? with Base.", "5: Expected 1 arguments but got 0."]);
+  }
+
+  #[test]
+  fn accepts_unannotated_code() {
+    let source = "
+      var x = 1;
+      x = \"s\";
+      print x + \"t\";
+      var later;
+      later = [1];
+      fun add(a, b) { return a + b; }
+      var y = add(1, \"2\");
+      class Point { init(x, y) { this.x = x; } }
+      var p: Point = Point(1, 2);
+      var n = match 1 { 1 => 2, _ => 3 } + 1;
+      fun f(a) { return a; }
+      f = fun (a, b) { return a + b; };
+      fun handler(x) {}
+      handler = nil;
+      var s = \"a\";
+      print s < 1;
+      class Shape {}
+      Shape = nil;
+    ";
+    assert!(check(source).is_empty());
+  }
+}
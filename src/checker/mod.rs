@@ -0,0 +1,5 @@
+pub mod types;
+pub mod checker;
+
+pub use types::*;
+pub use checker::*;
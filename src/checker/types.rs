@@ -0,0 +1,107 @@
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+  // Unannotated values, never reported
+  Any,
+  Int,
+  Num,
+  String,
+  Bool,
+  Nil,
+  List,
+  Map,
+  Tuple,
+  Range,
+  Function(Option<Rc<Signature>>),
+  Class(String),
+  Instance(String),
+  Nullable(Box<Type>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Signature {
+  pub name: String,
+  pub params: Vec<Type>,
+  pub result: Type,
+}
+
+impl Type {
+  pub fn nullable(self) -> Type {
+    match self {
+      Type::Any | Type::Nil | Type::Nullable(_) => self,
+      t => Type::Nullable(Box::new(t)),
+    }
+  }
+
+  pub fn may_be_nil(&self) -> bool {
+    matches!(self, Type::Nil | Type::Nullable(_))
+  }
+
+  // Types whose operations are fully known, instances may overload operators
+  pub fn is_builtin(&self) -> bool {
+    !matches!(self, Type::Any | Type::Instance(_) | Type::Nullable(_))
+  }
+
+  // Whether a value of type `value` can be stored where `self` is expected
+  pub fn accepts(&self, value: &Type) -> bool {
+    match (self, value) {
+      (Type::Any, _) | (_, Type::Any) => true,
+      (Type::Num, Type::Int) => true,
+      (Type::Nullable(_), Type::Nil) => true,
+      (Type::Nullable(target), Type::Nullable(value)) => target.accepts(value),
+      (Type::Nullable(target), value) => target.accepts(value),
+      (_, Type::Nullable(_)) => false,
+      (Type::Function(None), Type::Function(_) | Type::Class(_)) => true,
+      (a, b) => a == b,
+    }
+  }
+
+  // The type of a value that is either `self` or `other`
+  pub fn join(self, other: Type) -> Type {
+    match (self, other) {
+      (a, b) if a == b => a,
+      (Type::Nil, t) | (t, Type::Nil) => t.nullable(),
+      (Type::Int, Type::Num) | (Type::Num, Type::Int) => Type::Num,
+      _ => Type::Any,
+    }
+  }
+}
+
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Type::Any => write!(f, "Any"),
+      Type::Int => write!(f, "Int"),
+      Type::Num => write!(f, "Num"),
+      Type::String => write!(f, "String"),
+      Type::Bool => write!(f, "Bool"),
+      Type::Nil => write!(f, "Nil"),
+      Type::List => write!(f, "List"),
+      Type::Map => write!(f, "Map"),
+      Type::Tuple => write!(f, "Tuple"),
+      Type::Range => write!(f, "Range"),
+      Type::Function(_) => write!(f, "Function"),
+      Type::Class(name) => write!(f, "class {}", name),
+      Type::Instance(name) => write!(f, "{}", name),
+      Type::Nullable(t) => write!(f, "{}?", t),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn nullable_types_accept_nil() {
+    assert!(Type::Num.accepts(&Type::Int));
+    assert!(!Type::Int.accepts(&Type::Num));
+    assert!(Type::Num.nullable().accepts(&Type::Nil));
+    assert!(Type::Num.nullable().accepts(&Type::Int.nullable()));
+    assert!(!Type::Num.accepts(&Type::Num.nullable()));
+    assert!(!Type::String.accepts(&Type::Nil));
+    assert_eq!(Type::Nil.join(Type::Int), Type::Nullable(Box::new(Type::Int)));
+  }
+}
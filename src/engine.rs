@@ -0,0 +1,109 @@
+use std::any::Any;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+  interpreter::{Arity, CancelHandle, Capabilities, Clock, FromRaki, Interpreter, IntoMethod, IntoNative, Limits, Object, ToRaki, analyze},
+  raki_log::RakiError,
+};
+
+// The embedding entry point. Globals and loaded modules persist across calls,
+// so an engine can run a script and then call into it.
+pub struct Engine {
+  interpreter: Interpreter,
+}
+
+impl Default for Engine {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Engine {
+  pub fn new() -> Engine {
+    Engine { interpreter: Interpreter::new() }
+  }
+
+  // Runs `source`, returning the value of its last statement when that is an
+  // expression and nil otherwise
+  pub fn eval(&self, source: &str) -> Result<Object, RakiError> {
+    let statements = analyze(source.to_string()).map_err(RakiError::Analysis)?;
+    self.interpreter.start_run();
+    self.interpreter.interpret_value(&statements)
+  }
+
+  // Runs a script, resolving its imports relative to it
+  pub fn run_file(&self, path: impl AsRef<Path>) -> Result<Object, RakiError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|err| RakiError::Io { path: path.display().to_string(), message: err.to_string() })?;
+    self.interpreter.set_script(path);
+    self.eval(&source)
+  }
+
+  // Parses, resolves and type checks `source` without running it
+  pub fn check(&self, source: &str) -> Result<(), Vec<RakiError>> {
+    analyze(source.to_string()).map(|_| ())
+  }
+
+  pub fn set_global(&self, name: &str, value: Object) {
+    self.interpreter.define_global(name, value);
+  }
+
+  pub fn get_global(&self, name: &str) -> Option<Object> {
+    self.interpreter.global(name)
+  }
+
+  pub fn call(&self, function: &Object, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    self.interpreter.start_run();
+    self.interpreter.call_value(function.clone(), arguments)
+  }
+
+  // Applies to every later `eval`, `run_file` and `call`, each of which gets
+  // a fresh step, time and allocation budget
+  pub fn set_limits(&self, limits: Limits) {
+    self.interpreter.set_limits(limits);
+  }
+
+  pub fn grant(&self, capabilities: Capabilities) {
+    self.interpreter.grant(capabilities);
+  }
+
+  // With a `FixedClock` and a fixed seed, scripts that read the time or draw
+  // random numbers do the same thing on every run
+  pub fn set_clock(&self, clock: impl Clock + 'static) {
+    self.interpreter.set_clock(clock);
+  }
+
+  pub fn seed(&self, seed: u64) {
+    self.interpreter.seed(seed);
+  }
+
+  pub fn cancel_handle(&self) -> CancelHandle {
+    self.interpreter.cancel_handle()
+  }
+
+  pub fn register_native(&self, name: &str, arity: impl Into<Arity>, function: impl Fn(&Interpreter, &[Object]) -> Result<Object, RakiError> + 'static) {
+    self.interpreter.register_native(name, arity, function);
+  }
+
+  // Registers a typed closure, like `|a: f64, b: f64| a + b`. Arguments that
+  // don't convert raise an error naming their position.
+  pub fn register<Args, F: IntoNative<Args> + 'static>(&self, name: &str, function: F) {
+    let owned = name.to_string();
+    self.interpreter.register_native(name, F::ARITY, move |_, arguments| function.call_native(&owned, arguments));
+  }
+
+  // Userdata created with `Value::new_userdata` gets its methods and
+  // properties from what was registered for its Rust type
+  pub fn register_method<T: Any, Args, F: IntoMethod<T, Args> + 'static>(&self, name: &str, method: F) {
+    self.interpreter.register_method(name, method);
+  }
+
+  pub fn register_getter<T: Any, R: ToRaki>(&self, name: &str, function: impl Fn(&T) -> R + 'static) {
+    self.interpreter.register_getter(name, function);
+  }
+
+  pub fn register_setter<T: Any, V: FromRaki>(&self, name: &str, function: impl Fn(&mut T, V) + 'static) {
+    self.interpreter.register_setter(name, function);
+  }
+}
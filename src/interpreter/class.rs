@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{Function, Object};
+
+#[derive(Debug)]
+pub struct Class {
+  pub name: String,
+  pub methods: HashMap<String, Rc<Function>>,
+  pub getters: HashMap<String, Rc<Function>>,
+  pub setters: HashMap<String, Rc<Function>>,
+  pub superclass: Option<Rc<Class>>,
+  pub traits: Vec<Rc<Trait>>,
+  // Static members, accessed on the class object itself
+  pub class_methods: HashMap<String, Rc<Function>>,
+  pub class_fields: RefCell<HashMap<String, Object>>,
+}
+
+impl Class {
+  pub fn new(name: &str, methods: HashMap<String, Rc<Function>>, superclass: Option<Rc<Class>>, traits: Vec<Rc<Trait>>) -> Class {
+    Class {
+      name: name.to_string(),
+      methods,
+      getters: HashMap::new(),
+      setters: HashMap::new(),
+      superclass,
+      traits,
+      class_methods: HashMap::new(),
+      class_fields: RefCell::new(HashMap::new()),
+    }
+  }
+
+  // Looks a member up in this class, then in its superclasses
+  fn find<T>(&self, lookup: &impl Fn(&Class) -> Option<T>) -> Option<T> {
+    lookup(self).or_else(|| self.superclass.as_ref().and_then(|superclass| superclass.find(lookup)))
+  }
+
+  pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+    self.find(&|class| class.methods.get(name).cloned())
+  }
+
+  pub fn find_getter(&self, name: &str) -> Option<Rc<Function>> {
+    self.find(&|class| class.getters.get(name).cloned())
+  }
+
+  pub fn find_setter(&self, name: &str) -> Option<Rc<Function>> {
+    self.find(&|class| class.setters.get(name).cloned())
+  }
+
+  pub fn find_class_method(&self, name: &str) -> Option<Rc<Function>> {
+    self.find(&|class| class.class_methods.get(name).cloned())
+  }
+
+  pub fn find_class_field(&self, name: &str) -> Option<Object> {
+    self.find(&|class| class.class_fields.borrow().get(name).cloned())
+  }
+
+  // Assigns the class field where it is defined, defining it on this class
+  // when no superclass has it
+  pub fn set_class_field(&self, name: &str, value: Object) {
+    let mut class = self;
+    while !class.class_fields.borrow().contains_key(name) {
+      match &class.superclass {
+        Some(superclass) => class = superclass,
+        None => {
+          class = self;
+          break;
+        }
+      }
+    }
+    class.class_fields.borrow_mut().insert(name.to_string(), value);
+  }
+
+  pub fn is_subclass_of(self: &Rc<Class>, class: &Rc<Class>) -> bool {
+    Rc::ptr_eq(self, class) || self.superclass.as_ref().is_some_and(|superclass| superclass.is_subclass_of(class))
+  }
+
+  pub fn implements(&self, t: &Rc<Trait>) -> bool {
+    self.traits.iter().any(|other| Rc::ptr_eq(other, t)) || self.superclass.as_ref().is_some_and(|superclass| superclass.implements(t))
+  }
+}
+
+#[derive(Debug)]
+pub struct Trait {
+  pub name: String,
+  // Names and arities of the methods implementing classes must define
+  pub required: Vec<(String, usize)>,
+  pub defaults: HashMap<String, Rc<Function>>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+  pub class: Rc<Class>,
+  pub fields: HashMap<String, Object>,
+}
+
+impl Instance {
+  pub fn new(class: Rc<Class>) -> Instance {
+    Instance { class, fields: HashMap::new() }
+  }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Object, RakiMap};
+
+// Why a Raki value could not become a Rust value. `path` points into nested
+// lists, maps and tuples, like `[2]` or `["name"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+  pub kind: ErrorKind,
+  pub expected: String,
+  pub found: String,
+  pub path: String,
+}
+
+impl ConversionError {
+  pub fn new(expected: &str, value: &Object) -> ConversionError {
+    ConversionError { kind: ErrorKind::TypeError, expected: expected.to_string(), found: value.type_name().to_string(), path: String::new() }
+  }
+
+  fn inside(mut self, step: String) -> ConversionError {
+    self.path = step + &self.path;
+    self
+  }
+}
+
+impl fmt::Display for ConversionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Value{} must be {}, not {}.", self.path, self.expected, self.found)
+  }
+}
+
+pub trait ToRaki {
+  fn to_raki(self) -> Object;
+}
+
+// What a native or method returns: a value, or a value or an error
+pub trait NativeResult {
+  fn into_result(self) -> Result<Object, RakiError>;
+}
+
+impl<T: ToRaki> NativeResult for T {
+  fn into_result(self) -> Result<Object, RakiError> {
+    Ok(self.to_raki())
+  }
+}
+
+impl<T: ToRaki> NativeResult for Result<T, RakiError> {
+  fn into_result(self) -> Result<Object, RakiError> {
+    self.map(ToRaki::to_raki)
+  }
+}
+
+pub trait FromRaki: Sized {
+  fn from_raki(value: &Object) -> Result<Self, ConversionError>;
+}
+
+// Converts argument `i` of a native, reporting a mismatch by its position
+pub fn argument<T: FromRaki>(function: &str, arguments: &[Object], i: usize) -> Result<T, RakiError> {
+  T::from_raki(&arguments[i]).map_err(|err| RakiError::Runtime {
+    kind: err.kind,
+    line: 0,
+    message: format!("Argument {}{} of {}() must be {}, not {}.", i + 1, err.path, function, err.expected, err.found),
+  })
+}
+
+impl ToRaki for Object {
+  fn to_raki(self) -> Object {
+    self
+  }
+}
+
+impl FromRaki for Object {
+  fn from_raki(value: &Object) -> Result<Object, ConversionError> {
+    Ok(value.clone())
+  }
+}
+
+impl ToRaki for () {
+  fn to_raki(self) -> Object {
+    Object::None
+  }
+}
+
+impl ToRaki for f64 {
+  fn to_raki(self) -> Object {
+    Object::Double(self)
+  }
+}
+
+impl FromRaki for f64 {
+  fn from_raki(value: &Object) -> Result<f64, ConversionError> {
+    match value {
+      Object::Double(n) => Ok(*n),
+      _ => Err(ConversionError::new("a number", value)),
+    }
+  }
+}
+
+// Raki only has doubles, so integers round-trip while they fit in 53 bits
+impl ToRaki for i64 {
+  fn to_raki(self) -> Object {
+    Object::Double(self as f64)
+  }
+}
+
+impl FromRaki for i64 {
+  fn from_raki(value: &Object) -> Result<i64, ConversionError> {
+    match f64::from_raki(value).map_err(|_| ConversionError::new("an integer", value))? {
+      n if n.fract() == 0.0 && n.abs() <= 2f64.powi(53) => Ok(n as i64),
+      n => Err(ConversionError { kind: ErrorKind::ValueError, found: n.to_string(), ..ConversionError::new("an integer", value) }),
+    }
+  }
+}
+
+impl ToRaki for bool {
+  fn to_raki(self) -> Object {
+    Object::Boolean(self)
+  }
+}
+
+impl FromRaki for bool {
+  fn from_raki(value: &Object) -> Result<bool, ConversionError> {
+    match value {
+      Object::Boolean(b) => Ok(*b),
+      _ => Err(ConversionError::new("a bool", value)),
+    }
+  }
+}
+
+impl ToRaki for String {
+  fn to_raki(self) -> Object {
+    Object::String(self)
+  }
+}
+
+impl ToRaki for &str {
+  fn to_raki(self) -> Object {
+    Object::String(self.to_string())
+  }
+}
+
+impl FromRaki for String {
+  fn from_raki(value: &Object) -> Result<String, ConversionError> {
+    match value {
+      Object::String(s) => Ok(s.clone()),
+      _ => Err(ConversionError::new("a string", value)),
+    }
+  }
+}
+
+impl<T: ToRaki> ToRaki for Option<T> {
+  fn to_raki(self) -> Object {
+    match self {
+      Some(value) => value.to_raki(),
+      None => Object::None,
+    }
+  }
+}
+
+impl<T: FromRaki> FromRaki for Option<T> {
+  fn from_raki(value: &Object) -> Result<Option<T>, ConversionError> {
+    match value {
+      Object::None => Ok(None),
+      _ => T::from_raki(value).map(Some).map_err(|err| match err.path.is_empty() {
+        true => ConversionError { expected: format!("{} or nil", err.expected), ..err },
+        false => err,
+      }),
+    }
+  }
+}
+
+impl<T: ToRaki> ToRaki for Vec<T> {
+  fn to_raki(self) -> Object {
+    Object::new_list(self.into_iter().map(ToRaki::to_raki).collect())
+  }
+}
+
+impl<T: FromRaki> FromRaki for Vec<T> {
+  fn from_raki(value: &Object) -> Result<Vec<T>, ConversionError> {
+    match value {
+      Object::List(list) => list.borrow().iter().enumerate().map(|(i, element)| T::from_raki(element).map_err(|err| err.inside(format!("[{}]", i)))).collect(),
+      _ => Err(ConversionError::new("a list", value)),
+    }
+  }
+}
+
+// Entries are sorted by key so the resulting map prints the same every time
+impl<T: ToRaki> ToRaki for HashMap<String, T> {
+  fn to_raki(self) -> Object {
+    let mut entries: Vec<(String, T)> = self.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut map = RakiMap::new();
+    for (key, value) in entries {
+      map.insert(Object::String(key), value.to_raki());
+    }
+    Object::new_map(map)
+  }
+}
+
+impl<T: FromRaki> FromRaki for HashMap<String, T> {
+  fn from_raki(value: &Object) -> Result<HashMap<String, T>, ConversionError> {
+    let Object::Map(map) = value else { return Err(ConversionError::new("a map", value)) };
+    map
+      .borrow()
+      .iter()
+      .map(|(key, value)| match key {
+        Object::String(key) => T::from_raki(value).map(|value| (key.clone(), value)).map_err(|err| err.inside(format!("[\"{}\"]", key))),
+        _ => Err(ConversionError { found: format!("map with a {} key", key.type_name()), ..ConversionError::new("a map with string keys", key) }),
+      })
+      .collect()
+  }
+}
+
+macro_rules! tuple_conversions {
+  ($len:expr; $($name:ident $i:tt),+) => {
+    impl<$($name: ToRaki),+> ToRaki for ($($name,)+) {
+      fn to_raki(self) -> Object {
+        Object::new_tuple(vec![$(self.$i.to_raki()),+])
+      }
+    }
+
+    impl<$($name: FromRaki),+> FromRaki for ($($name,)+) {
+      fn from_raki(value: &Object) -> Result<Self, ConversionError> {
+        let expected = format!("a tuple of {}", $len);
+        match value {
+          Object::Tuple(elements) if elements.len() == $len => Ok(($($name::from_raki(&elements[$i]).map_err(|err| err.inside(format!("[{}]", $i)))?,)+)),
+          Object::Tuple(elements) => Err(ConversionError { found: format!("tuple of {}", elements.len()), ..ConversionError::new(&expected, value) }),
+          _ => Err(ConversionError::new(&expected, value)),
+        }
+      }
+    }
+  };
+}
+
+tuple_conversions!(2; A 0, B 1);
+tuple_conversions!(3; A 0, B 1, C 2);
+tuple_conversions!(4; A 0, B 1, C 2, D 3);
+
+// A typed Rust closure that can be registered as a native. `Args` only tells
+// the implementations for each parameter count apart.
+pub trait IntoNative<Args> {
+  const ARITY: usize;
+
+  fn call_native(&self, name: &str, arguments: &[Object]) -> Result<Object, RakiError>;
+}
+
+macro_rules! into_native {
+  ($len:expr; $($name:ident $i:tt),*) => {
+    impl<F, R, $($name),*> IntoNative<($($name,)*)> for F
+    where
+      F: Fn($($name),*) -> R,
+      R: NativeResult,
+      $($name: FromRaki),*
+    {
+      const ARITY: usize = $len;
+
+      #[allow(unused_variables)]
+      fn call_native(&self, name: &str, arguments: &[Object]) -> Result<Object, RakiError> {
+        self($(argument::<$name>(name, arguments, $i)?),*).into_result()
+      }
+    }
+  };
+}
+
+into_native!(0;);
+into_native!(1; A 0);
+into_native!(2; A 0, B 1);
+into_native!(3; A 0, B 1, C 2);
+into_native!(4; A 0, B 1, C 2, D 3);
+into_native!(5; A 0, B 1, C 2, D 3, E 4);
+into_native!(6; A 0, B 1, C 2, D 3, E 4, G 5);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn round_trips_values() {
+    assert_eq!(f64::from_raki(&2.5.to_raki()), Ok(2.5));
+    assert_eq!(i64::from_raki(&(-7i64).to_raki()), Ok(-7));
+    assert_eq!(bool::from_raki(&true.to_raki()), Ok(true));
+    assert_eq!(String::from_raki(&"hi".to_raki()), Ok("hi".to_string()));
+    assert_eq!(Option::<f64>::from_raki(&None::<f64>.to_raki()), Ok(None));
+    assert_eq!(Vec::<String>::from_raki(&vec!["a", "b"].to_raki()), Ok(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(<(f64, String)>::from_raki(&(1.0, "x").to_raki()), Ok((1.0, "x".to_string())));
+
+    let map = HashMap::from([("b".to_string(), 2.0), ("a".to_string(), 1.0)]);
+    assert_eq!(map.clone().to_raki().to_string(), "{\"a\": 1, \"b\": 2}");
+    assert_eq!(HashMap::<String, f64>::from_raki(&map.clone().to_raki()), Ok(map));
+  }
+
+  #[test]
+  fn describes_mismatches() {
+    let err = i64::from_raki(&Object::Double(1.5)).unwrap_err();
+    assert_eq!((err.kind, err.to_string()), (ErrorKind::ValueError, "Value must be an integer, not 1.5.".to_string()));
+
+    let err = Option::<bool>::from_raki(&Object::Double(1.0)).unwrap_err();
+    assert_eq!(err.to_string(), "Value must be a bool or nil, not number.");
+
+    let err = Vec::<f64>::from_raki(&Object::new_list(vec![Object::Double(1.0), Object::String("x".to_string())])).unwrap_err();
+    assert_eq!(err.to_string(), "Value[1] must be a number, not string.");
+
+    let err = <(f64, f64)>::from_raki(&Object::new_tuple(vec![Object::Double(1.0)])).unwrap_err();
+    assert_eq!(err.to_string(), "Value must be a tuple of 2, not tuple of 1.");
+  }
+
+  fn arity<Args, F: IntoNative<Args>>(_: &F) -> usize {
+    F::ARITY
+  }
+
+  #[test]
+  fn calls_typed_closures() {
+    let add = |a: f64, b: f64| a + b;
+    assert_eq!(arity(&add), 2);
+    assert_eq!(arity(&|| ()), 0);
+    assert_eq!(add.call_native("add", &[Object::Double(1.0), Object::Double(2.0)]), Ok(Object::Double(3.0)));
+
+    let err = add.call_native("add", &[Object::Double(1.0), Object::Boolean(true)]).unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 0, message: "Argument 2 of add() must be a number, not bool.".to_string() });
+
+    let first = |names: Vec<String>| names.into_iter().next();
+    let err = first.call_native("first", &[Object::new_list(vec![Object::Double(1.0)])]).unwrap_err();
+    assert_eq!(err.to_string(), "TypeError on line 0: Argument 1[0] of first() must be a string, not number.");
+  }
+}
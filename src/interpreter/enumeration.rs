@@ -0,0 +1,36 @@
+use std::rc::Rc;
+
+use super::Object;
+
+#[derive(Debug)]
+pub struct Enum {
+  pub name: String,
+  pub variants: Vec<Rc<Variant>>,
+}
+
+impl Enum {
+  pub fn find_variant(&self, name: &str) -> Option<Rc<Variant>> {
+    self.variants.iter().find(|variant| variant.name == name).cloned()
+  }
+}
+
+#[derive(Debug)]
+pub struct Variant {
+  pub enum_name: String,
+  pub name: String,
+  pub fields: Vec<String>,
+}
+
+// A value built from one of an enum's variants, like `Shape.Circle(2)`
+#[derive(Debug)]
+pub struct EnumValue {
+  pub variant: Rc<Variant>,
+  pub values: Vec<Object>,
+}
+
+impl EnumValue {
+  pub fn field(&self, name: &str) -> Option<Object> {
+    let index = self.variant.fields.iter().position(|field| field == name)?;
+    self.values.get(index).cloned()
+  }
+}
@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::lexer::Token;
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::Object;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+  values: HashMap<String, Object>,
+  constants: HashSet<String>,
+  enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+  pub fn new() -> Environment {
+    Environment::default()
+  }
+
+  pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+    Environment { values: HashMap::new(), constants: HashSet::new(), enclosing: Some(enclosing) }
+  }
+
+  pub fn define(&mut self, name: &str, value: Object) {
+    self.values.insert(name.to_string(), value);
+    self.constants.remove(name);
+  }
+
+  pub fn define_constant(&mut self, name: &str, value: Object) {
+    self.values.insert(name.to_string(), value);
+    self.constants.insert(name.to_string());
+  }
+
+  pub fn get(&self, name: &Token) -> Result<Object, RakiError> {
+    match self.lookup(&name.lexeme) {
+      Some(value) => Ok(value),
+      None => Err(RakiError::Runtime { kind: ErrorKind::NameError, line: name.line, message: format!("Undefined variable '{}'.", name.lexeme) }),
+    }
+  }
+
+  pub fn lookup(&self, name: &str) -> Option<Object> {
+    if let Some(value) = self.values.get(name) {
+      return Some(value.clone());
+    }
+
+    match &self.enclosing {
+      Some(enclosing) => enclosing.borrow().lookup(name),
+      None => None,
+    }
+  }
+
+  pub fn assign(&mut self, name: &Token, value: Object) -> Result<(), RakiError> {
+    if let Some(slot) = self.values.get_mut(&name.lexeme) {
+      // The resolver rejects these statically, except for REPL globals
+      // declared by earlier lines
+      if self.constants.contains(&name.lexeme) {
+        return Err(RakiError::Runtime { kind: ErrorKind::TypeError, line: name.line, message: format!("Cannot assign to constant '{}'.", name.lexeme) });
+      }
+      *slot = value;
+      return Ok(());
+    }
+
+    match &self.enclosing {
+      Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+      None => Err(RakiError::Runtime { kind: ErrorKind::NameError, line: name.line, message: format!("Undefined variable '{}'.", name.lexeme) }),
+    }
+  }
+}
@@ -0,0 +1,305 @@
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Interpreter, Object, argument};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+  Left,
+  Right,
+  Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sign {
+  Negative,
+  Always,
+  Space,
+}
+
+// A parsed `[[fill]align][sign][#][0][width][.precision][type]`, after the
+// mini-language of Python's `format`
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+  fill: char,
+  align: Option<Align>,
+  sign: Sign,
+  // `0x`, `0o` and `0b` prefixes
+  alternate: bool,
+  zero: bool,
+  width: usize,
+  precision: Option<usize>,
+  kind: Option<char>,
+}
+
+fn error(kind: ErrorKind, message: String) -> RakiError {
+  RakiError::Runtime { kind, line: 0, message }
+}
+
+fn align(c: char) -> Option<Align> {
+  match c {
+    '<' => Some(Align::Left),
+    '>' => Some(Align::Right),
+    '^' => Some(Align::Center),
+    _ => None,
+  }
+}
+
+impl Spec {
+  fn parse(text: &str) -> Result<Spec, RakiError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spec = Spec { fill: ' ', align: None, sign: Sign::Negative, alternate: false, zero: false, width: 0, precision: None, kind: None };
+    let mut i = 0;
+    if let Some(a) = chars.get(1).and_then(|c| align(*c)) {
+      spec.fill = chars[0];
+      spec.align = Some(a);
+      i = 2;
+    } else if let Some(a) = chars.first().and_then(|c| align(*c)) {
+      spec.align = Some(a);
+      i = 1;
+    }
+
+    let digits = |i: &mut usize| {
+      let start = *i;
+      while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+        *i += 1;
+      }
+      chars[start..*i].iter().collect::<String>().parse::<usize>().ok()
+    };
+    let sign = match chars.get(i) {
+      Some('+') => Some(Sign::Always),
+      Some(' ') => Some(Sign::Space),
+      Some('-') => Some(Sign::Negative),
+      _ => None,
+    };
+    if let Some(sign) = sign {
+      spec.sign = sign;
+      i += 1;
+    }
+    if chars.get(i) == Some(&'#') {
+      spec.alternate = true;
+      i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+      spec.zero = true;
+      i += 1;
+    }
+    spec.width = digits(&mut i).unwrap_or(0);
+    if chars.get(i) == Some(&'.') {
+      i += 1;
+      spec.precision = Some(digits(&mut i).ok_or_else(|| invalid(text))?);
+    }
+    if let Some(kind @ ('d' | 'f' | 'e' | 'x' | 'X' | 'o' | 'b')) = chars.get(i) {
+      spec.kind = Some(*kind);
+      i += 1;
+    }
+    if i != chars.len() {
+      return Err(invalid(text));
+    }
+    Ok(spec)
+  }
+}
+
+fn invalid(spec: &str) -> RakiError {
+  error(ErrorKind::ValueError, format!("Invalid format spec '{}'.", spec))
+}
+
+// The sign and radix prefix, and the digits after them
+fn number(value: &Object, spec: &Spec) -> Result<(String, String), RakiError> {
+  let Object::Double(n) = value else {
+    let expected = spec.kind.map(|kind| format!("Format '{}'", kind)).unwrap_or_else(|| "A sign, '#' or '0'".to_string());
+    return Err(error(ErrorKind::TypeError, format!("{} needs a number, not {}.", expected, value.type_name())));
+  };
+  let digits = match spec.kind {
+    Some(kind @ ('d' | 'x' | 'X' | 'o' | 'b')) => {
+      if spec.precision.is_some() {
+        return Err(error(ErrorKind::ValueError, format!("Format '{}' doesn't take a precision.", kind)));
+      }
+      if n.fract() != 0.0 || n.abs() > 2f64.powi(53) {
+        return Err(error(ErrorKind::ValueError, format!("Format '{}' needs an integer, not {}.", kind, n)));
+      }
+      let n = n.abs() as u64;
+      match kind {
+        'x' => format!("{:x}", n),
+        'X' => format!("{:X}", n),
+        'o' => format!("{:o}", n),
+        'b' => format!("{:b}", n),
+        _ => n.to_string(),
+      }
+    }
+    Some('e') => match spec.precision {
+      Some(precision) => format!("{:.*e}", precision, n.abs()),
+      None => format!("{:e}", n.abs()),
+    },
+    Some('f') => format!("{:.*}", spec.precision.unwrap_or(6), n.abs()),
+    _ => match spec.precision {
+      Some(precision) => format!("{:.*}", precision, n.abs()),
+      None => Object::Double(n.abs()).to_string(),
+    },
+  };
+
+  let mut prefix = match spec.sign {
+    _ if n.is_sign_negative() && *n != 0.0 => "-",
+    Sign::Always => "+",
+    Sign::Space => " ",
+    Sign::Negative => "",
+  }
+  .to_string();
+  if spec.alternate {
+    prefix += match spec.kind {
+      Some('x' | 'X') => "0x",
+      Some('o') => "0o",
+      Some('b') => "0b",
+      _ => "",
+    };
+  }
+  Ok((prefix, digits))
+}
+
+fn render(interpreter: &Interpreter, value: &Object, spec: &Spec) -> Result<String, RakiError> {
+  let numeric = matches!(value, Object::Double(_)) || spec.kind.is_some() || spec.sign != Sign::Negative || spec.alternate || spec.zero;
+  if !numeric {
+    let text = interpreter.display(value)?;
+    let text: String = match spec.precision {
+      Some(precision) => text.chars().take(precision).collect(),
+      None => text,
+    };
+    return pad(interpreter, &text, spec, Align::Left);
+  }
+
+  // The digits of a double, at most 309 before the point and `precision`
+  // after it
+  if let Some(precision) = spec.precision {
+    interpreter.new_string("format", 0, precision.checked_add(320))?;
+  }
+  let (prefix, digits) = number(value, spec)?;
+  // Zeros go between the sign and the digits
+  if spec.zero && spec.align.is_none() {
+    let zeros = Spec { fill: '0', align: Some(Align::Right), width: spec.width.saturating_sub(prefix.chars().count()), ..spec.clone() };
+    return Ok(prefix + &pad(interpreter, &digits, &zeros, Align::Right)?);
+  }
+  pad(interpreter, &(prefix + &digits), spec, Align::Right)
+}
+
+// Wide fields are charged before they're built, see `Interpreter::new_string`
+fn pad(interpreter: &Interpreter, text: &str, spec: &Spec, default: Align) -> Result<String, RakiError> {
+  let padding = spec.width.saturating_sub(text.chars().count());
+  let len = spec.fill.len_utf8().checked_mul(padding).and_then(|len| len.checked_add(text.len()));
+  let mut result = interpreter.new_string("format", 0, len)?;
+  let fill = |result: &mut String, n: usize| result.extend(std::iter::repeat_n(spec.fill, n));
+  match spec.align.unwrap_or(default) {
+    Align::Left => {
+      result.push_str(text);
+      fill(&mut result, padding);
+    }
+    Align::Right => {
+      fill(&mut result, padding);
+      result.push_str(text);
+    }
+    Align::Center => {
+      fill(&mut result, padding / 2);
+      result.push_str(text);
+      fill(&mut result, padding - padding / 2);
+    }
+  }
+  Ok(result)
+}
+
+// `format("{:>8.2} {}", x, name)`. Fields are `{}` for the next argument or
+// `{1}` for a given one, with an optional `:spec`. `{{` and `}}` are braces.
+pub fn format(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let template: String = argument("format", arguments, 0)?;
+  let values = &arguments[1..];
+  let mut result = String::new();
+  let mut next = 0;
+  let mut chars = template.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '{' if chars.clone().next() == Some('{') => {
+        chars.next();
+        result.push('{');
+      }
+      '}' if chars.clone().next() == Some('}') => {
+        chars.next();
+        result.push('}');
+      }
+      '}' => return Err(error(ErrorKind::ValueError, "Single '}' in format string, write '}}' for a brace.".to_string())),
+      '{' => {
+        let field: String = chars.by_ref().take_while(|c| *c != '}').collect();
+        // `take_while` also stops at the end, without a closing brace
+        if !template[..template.len() - chars.as_str().len()].ends_with('}') {
+          return Err(error(ErrorKind::ValueError, "Unclosed '{' in format string, write '{{' for a brace.".to_string()));
+        }
+        let (index, spec) = field.split_once(':').unwrap_or((&field, ""));
+        let index = match index {
+          "" => {
+            next += 1;
+            next - 1
+          }
+          index => index.parse().map_err(|_| error(ErrorKind::ValueError, format!("Invalid format field '{{{}}}'.", field)))?,
+        };
+        let value = values.get(index).ok_or_else(|| error(ErrorKind::IndexError, format!("Format string needs argument {} but got {}.", index + 1, values.len())))?;
+        result.push_str(&render(interpreter, value, &Spec::parse(spec)?)?);
+      }
+      c => result.push(c),
+    }
+  }
+  Ok(Object::String(result))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn format_values(template: &str, values: Vec<Object>) -> Result<String, String> {
+    let arguments: Vec<Object> = std::iter::once(Object::String(template.to_string())).chain(values).collect();
+    match format(&Interpreter::new(), &arguments) {
+      Ok(result) => Ok(result.to_string()),
+      Err(RakiError::Runtime { message, .. }) => Err(message),
+      Err(err) => panic!("unexpected error {:?}", err),
+    }
+  }
+
+  fn n(value: f64) -> Object {
+    Object::Double(value)
+  }
+
+  #[test]
+  fn formats_numbers() {
+    assert_eq!(format_values("{:>8.2}|{:<6}|{:^7.1f}|", vec![n(12.3456), n(0.1 + 0.2), n(2.0)]), Ok("   12.35|0.30000000000000004|  2.0  |".to_string()));
+    assert_eq!(format_values("{:+} {:+.1} {: } {:08.3}", vec![n(5.0), n(-0.25), n(7.0), n(-3.5)]), Ok("+5 -0.2  7 -003.500".to_string()));
+    assert_eq!(format_values("{:x} {:#X} {:#010b} {:o} {:d}", vec![n(255.0), n(255.0), n(5.0), n(8.0), n(-42.0)]), Ok("ff 0xFF 0b00000101 10 -42".to_string()));
+    assert_eq!(format_values("{:.3e} {:f}", vec![n(1234.5), n(1.5)]), Ok("1.234e3 1.500000".to_string()));
+    assert_eq!(format_values("{:*>6x}", vec![n(-26.0)]), Ok("***-1a".to_string()));
+  }
+
+  #[test]
+  fn formats_other_values() {
+    let list = Object::new_list(vec![Object::String("a".to_string()), Object::None]);
+    assert_eq!(format_values("{1}-{0}-{1}", vec![Object::String("x".to_string()), n(1.0)]), Ok("1-x-1".to_string()));
+    assert_eq!(format_values("{:<5}|{:>5.2}|{}", vec![Object::None, Object::String("abc".to_string()), list]), Ok("nil  |   ab|[\"a\", nil]".to_string()));
+    assert_eq!(format_values("{{{}}} {:_^9}", vec![Object::Boolean(true), Object::String("mid".to_string())]), Ok("{true} ___mid___".to_string()));
+  }
+
+  #[test]
+  fn rejects_bad_templates() {
+    assert_eq!(format_values("{} {}", vec![n(1.0)]), Err("Format string needs argument 2 but got 1.".to_string()));
+    assert_eq!(format_values("a } b", vec![]), Err("Single '}' in format string, write '}}' for a brace.".to_string()));
+    assert_eq!(format_values("a {", vec![]), Err("Unclosed '{' in format string, write '{{' for a brace.".to_string()));
+    assert_eq!(format_values("{:>>}", vec![n(1.0)]), Ok("1".to_string()));
+    assert_eq!(format_values("{:5q}", vec![n(1.0)]), Err("Invalid format spec '5q'.".to_string()));
+    assert_eq!(format_values("{:x}", vec![n(1.5)]), Err("Format 'x' needs an integer, not 1.5.".to_string()));
+    assert_eq!(format_values("{:.2x}", vec![n(1.0)]), Err("Format 'x' doesn't take a precision.".to_string()));
+    assert_eq!(format_values("{:f}", vec![Object::String("1".to_string())]), Err("Format 'f' needs a number, not string.".to_string()));
+    assert_eq!(format_values("{:+}", vec![Object::None]), Err("A sign, '#' or '0' needs a number, not nil.".to_string()));
+  }
+
+  #[test]
+  fn rejects_fields_too_large_to_build() {
+    let too_large = Err("format() result is too large.".to_string());
+    assert_eq!(format_values("{:>99999999999999999}", vec![n(1.0)]), too_large);
+    assert_eq!(format_values("{:099999999999999999}", vec![n(-1.0)]), too_large);
+    assert_eq!(format_values("{:.99999999999999999}", vec![n(1.0)]), too_large);
+    assert_eq!(format_values("{:.18446744073709551615f}", vec![n(1.0)]), too_large);
+  }
+}
@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::{lexer::Token, parser::Stmt};
+
+use super::{Environment, Object};
+
+pub struct Function {
+  pub name: String,
+  pub params: Rc<[Token]>,
+  pub body: Rc<[Stmt]>,
+  pub closure: Rc<RefCell<Environment>>,
+  pub is_initializer: bool,
+}
+
+impl Function {
+  pub fn new(name: &str, params: &[Token], body: &[Stmt], closure: Rc<RefCell<Environment>>) -> Function {
+    Function { name: name.to_string(), params: params.into(), body: body.into(), closure, is_initializer: false }
+  }
+
+  pub fn arity(&self) -> usize {
+    self.params.len()
+  }
+
+  // Method closures get their own scope holding `this`
+  pub fn bind(&self, instance: Object) -> Function {
+    let mut environment = Environment::with_enclosing(self.closure.clone());
+    environment.define("this", instance);
+    Function {
+      name: self.name.clone(),
+      params: self.params.clone(),
+      body: self.body.clone(),
+      closure: Rc::new(RefCell::new(environment)),
+      is_initializer: self.is_initializer,
+    }
+  }
+}
+
+impl fmt::Debug for Function {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<fn {}>", self.name)
+  }
+}
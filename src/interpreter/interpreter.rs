@@ -1,88 +1,2832 @@
+use std::any::{Any, TypeId};
+use std::cell::{OnceCell, Ref, RefCell, RefMut};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+use std::io::{self, Write};
+use std::{env, fs};
+
 use crate::lexer::TokenType;
-use crate::raki_log::{RakiError, raki_log};
+use crate::raki_log::{ErrorKind, RakiError};
 use crate::{
-  lexer::{LiteralType, Token},
-  parser::{Expr, Visitor},
+  lexer::{LiteralType, Scanner, Token},
+  parser::{Expr, MatchArm, Parser, Pattern, Stmt, StmtVisitor, Visitor},
 };
 
-use super::Object;
+use super::{
+  Arity, Budget, CancelHandle, Capabilities, Class, Clock, Enum, EnumValue, Environment, FromRaki, Function, Instance, IntoMethod, Limits, Method, Module, NativeFunction, Object, PRELUDE,
+  RakiIterator, RakiMap, Rng, SLEEP_SLICE, print_container, SystemClock, ToRaki, Trait, UserData, UserType, Variant, analyze, format, fs_module, getter, io_module, json_module, math_module, native_method, number_argument, random_module, re_module, register_regex, setter,
+  shallow_size, time_module,
+};
 
-pub struct Interpreter {}
+// How a statement finished, so `return`, `break` and `continue` can unwind to
+// the enclosing call or loop
+#[derive(Debug, PartialEq)]
+pub enum Flow {
+  Normal,
+  Return(Object),
+  Break,
+  Continue,
+}
 
-impl Interpreter {
-  fn error(&self) -> RakiError {
-    let err: RakiError;
-    err = RakiError::Runtime {};
-    raki_log(&err);
-    err
+pub struct Interpreter {
+  // Shared by every module, holds the prelude
+  builtins: Rc<RefCell<Environment>>,
+  globals: Rc<RefCell<Environment>>,
+  environment: RefCell<Rc<RefCell<Environment>>>,
+  error_class: OnceCell<Rc<Class>>,
+  // Directory of the main script, searched after the importing file's own.
+  // Only set for scripts run from a file, its modules need no read grant.
+  root: RefCell<Option<PathBuf>>,
+  // Directories from `RAKI_PATH`, searched last
+  search_paths: Vec<PathBuf>,
+  // Files whose top level is running, outermost first
+  importing: RefCell<Vec<PathBuf>>,
+  modules: RefCell<HashMap<PathBuf, Rc<Module>>>,
+  // Methods and properties of userdata, by Rust type
+  user_types: RefCell<HashMap<TypeId, UserType>>,
+  budget: Budget,
+  capabilities: RefCell<Capabilities>,
+  clock: RefCell<Rc<dyn Clock>>,
+  rng: RefCell<Rng>,
+}
+
+impl Default for Interpreter {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
 impl Interpreter {
-  fn visit_binary_expr(&self, left: &Expr, right: &Expr, operator: &Token) -> Result<Object, RakiError> {
-    let left: Object = self.visit_expr(left)?;
-    let right: Object = self.visit_expr(right)?;
+  pub fn new() -> Interpreter {
+    let builtins = Rc::new(RefCell::new(Environment::new()));
+    let globals = Rc::new(RefCell::new(Environment::with_enclosing(builtins.clone())));
+    let interpreter = Interpreter {
+      environment: RefCell::new(builtins.clone()),
+      builtins,
+      globals,
+      error_class: OnceCell::new(),
+      root: RefCell::new(None),
+      search_paths: env::var_os("RAKI_PATH").map(|paths| env::split_paths(&paths).collect()).unwrap_or_default(),
+      importing: RefCell::new(Vec::new()),
+      modules: RefCell::new(HashMap::new()),
+      user_types: RefCell::new(HashMap::new()),
+      budget: Budget::default(),
+      capabilities: RefCell::new(Capabilities::default()),
+      clock: RefCell::new(Rc::new(SystemClock::default())),
+      rng: RefCell::new(Rng::default()),
+    };
 
-    match operator.r#type {
-      TokenType::Plus => return left + right,
-      TokenType::Minus => return left - right,
-      TokenType::Star => return left * right,
-      TokenType::Slash => return left / right,
-      TokenType::Greater => return Ok(Object::Boolean(left > right)),
-      TokenType::GreaterEqual => return Ok(Object::Boolean(left >= right)),
-      TokenType::Less => return Ok(Object::Boolean(left < right)),
-      TokenType::LessEqual => return Ok(Object::Boolean(left <= right)),
-      TokenType::BangEqual => return Ok(Object::Boolean(left != right)),
-      TokenType::EqualEqual => return Ok(Object::Boolean(left == right)),
-      _ => return Ok(Object::None),
+    let mut scanner = Scanner::new(PRELUDE.to_string());
+    let statements = Parser::new(scanner.scan_tokens().expect("prelude scans")).parse_program();
+    interpreter.interpret(&statements).expect("prelude runs");
+    if let Some(Object::Class(class)) = interpreter.builtins.borrow().lookup("Error") {
+      let _ = interpreter.error_class.set(class);
     }
+    interpreter.builtins.borrow_mut().define("math", Object::Module(Rc::new(math_module())));
+    interpreter.builtins.borrow_mut().define("fs", Object::Module(Rc::new(fs_module())));
+    interpreter.builtins.borrow_mut().define("io", Object::Module(Rc::new(io_module())));
+    interpreter.builtins.borrow_mut().define("json", Object::Module(Rc::new(json_module())));
+    interpreter.builtins.borrow_mut().define("time", Object::Module(Rc::new(time_module())));
+    interpreter.builtins.borrow_mut().define("random", Object::Module(Rc::new(random_module())));
+    interpreter.builtins.borrow_mut().define("re", Object::Module(Rc::new(re_module())));
+    register_regex(&interpreter);
+    interpreter.register_native("format", Arity::Variadic(1), format);
+    interpreter.register_native("repr", 1, |interpreter, arguments| match &arguments[0] {
+      Object::String(_) => Ok(Object::String(arguments[0].repr())),
+      value => interpreter.display(value).map(Object::String),
+    });
+    // Seconds since the Unix epoch
+    interpreter.register_native("clock", 0, |interpreter, _| Ok(Object::Double(interpreter.clock().timestamp())));
+    interpreter.environment.replace(interpreter.globals.clone());
+
+    interpreter
   }
 
-  fn visit_literal_expr(&self, lit: &LiteralType) -> Result<Object, RakiError> {
-    match lit {
-      LiteralType::String(s) => return Ok(Object::String(s.clone())),
-      LiteralType::F64(val) => return Ok(Object::Double(*val)),
-      LiteralType::Bool(val) => return Ok(Object::Boolean(*val)),
-      LiteralType::None => return Ok(Object::None),
+  // Defines a Rust function as a global visible from every module
+  pub fn register_native(&self, name: &str, arity: impl Into<Arity>, function: impl Fn(&Interpreter, &[Object]) -> Result<Object, RakiError> + 'static) {
+    let function = NativeFunction::new(name, arity.into(), function);
+    self.builtins.borrow_mut().define(name, Object::Native(Rc::new(function)));
+  }
+
+  // Lets scripts call `name` on userdata holding a `T`
+  pub fn register_method<T: Any, Args, F: IntoMethod<T, Args> + 'static>(&self, name: &str, method: F) {
+    let owned = name.to_string();
+    let method: Rc<Method> = Rc::new(move |_, receiver, arguments| method.call_method(&owned, receiver, arguments));
+    self.user_types.borrow_mut().entry(TypeId::of::<T>()).or_default().methods.insert(name.to_string(), (F::ARITY, method));
+  }
+
+  // Like `register_method`, for built-in types whose methods need the
+  // interpreter. Arguments are left to the method to convert.
+  pub fn register_native_method<T: Any>(&self, name: &str, arity: usize, method: impl Fn(&Interpreter, &mut T, &[Object]) -> Result<Object, RakiError> + 'static) {
+    self.user_types.borrow_mut().entry(TypeId::of::<T>()).or_default().methods.insert(name.to_string(), (arity, native_method(method)));
+  }
+
+  pub fn register_getter<T: Any, R: ToRaki>(&self, name: &str, function: impl Fn(&T) -> R + 'static) {
+    self.user_types.borrow_mut().entry(TypeId::of::<T>()).or_default().getters.insert(name.to_string(), getter(function));
+  }
+
+  pub fn register_setter<T: Any, V: FromRaki>(&self, name: &str, function: impl Fn(&mut T, V) + 'static) {
+    self.user_types.borrow_mut().entry(TypeId::of::<T>()).or_default().setters.insert(name.to_string(), setter(name, function));
+  }
+
+  fn user_member<V: Clone>(&self, data: &UserData, members: impl Fn(&UserType) -> &HashMap<String, V>, name: &str) -> Option<V> {
+    self.user_types.borrow().get(&data.type_id).and_then(|user_type| members(user_type).get(name).cloned())
+  }
+
+  // Calls a Raki function or native from Rust, like from inside a native
+  pub fn call_value(&self, callee: Object, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    let token = Token { r#type: TokenType::Identifier, lexeme: callee.to_string(), literal: LiteralType::None, line: 0 };
+    self.call(callee, arguments, &token)
+  }
+
+  // Imports in `path` are resolved relative to it, and its directory becomes
+  // the project root
+  pub fn set_script(&self, path: &Path) {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Some(root) = path.parent() {
+      *self.root.borrow_mut() = Some(root.to_path_buf());
     }
+    *self.importing.borrow_mut() = vec![path];
   }
 
-  fn visit_unary_expr(&self, right: &Expr, operator: &Token) -> Result<Object, RakiError> {
-    let right = self.visit_expr(right)?;
-    match operator.r#type {
-      TokenType::Minus => match right {
-        Object::Double(val) => return Ok(Object::Double(-val)),
-        _ => return Err(self.error()),
+  pub fn interpret(&self, statements: &[Stmt]) -> Result<(), RakiError> {
+    for stmt in statements {
+      self.visit_stmt(stmt)?;
+    }
+
+    Ok(())
+  }
+
+  // Like `interpret`, returning the value of a trailing expression statement
+  pub fn interpret_value(&self, statements: &[Stmt]) -> Result<Object, RakiError> {
+    let Some((Stmt::Expression { expr }, rest)) = statements.split_last() else {
+      return self.interpret(statements).map(|_| Object::None);
+    };
+    self.interpret(rest)?;
+    self.visit_expr(expr)
+  }
+
+  pub fn set_limits(&self, limits: Limits) {
+    *self.budget.limits.borrow_mut() = limits;
+  }
+
+  // Lets the `fs` and `io` modules reach outside the sandbox
+  pub fn grant(&self, capabilities: Capabilities) {
+    *self.capabilities.borrow_mut() = capabilities;
+  }
+
+  pub fn capabilities(&self) -> Ref<'_, Capabilities> {
+    self.capabilities.borrow()
+  }
+
+  // Replaces the system clock behind `time` and `clock()`, like with a
+  // `FixedClock` to make runs reproducible
+  pub fn set_clock(&self, clock: impl Clock + 'static) {
+    *self.clock.borrow_mut() = Rc::new(clock);
+  }
+
+  pub fn clock(&self) -> Rc<dyn Clock> {
+    self.clock.borrow().clone()
+  }
+
+  // Restarts the `random` module's sequence
+  pub fn seed(&self, seed: u64) {
+    *self.rng.borrow_mut() = Rng::new(seed);
+  }
+
+  pub fn rng(&self) -> RefMut<'_, Rng> {
+    self.rng.borrow_mut()
+  }
+
+  // Sleeps on the interpreter's clock, failing instead of oversleeping the
+  // time limit. Wakes every `SLEEP_SLICE` to see if the host cancelled.
+  pub fn sleep(&self, duration: Duration) -> Result<(), RakiError> {
+    let (mut left, result) = match self.budget.remaining() {
+      Some(remaining) if duration > remaining => (remaining, Err(self.budget.timed_out())),
+      _ => (duration, Ok(())),
+    };
+    while !left.is_zero() {
+      self.budget.check_cancelled()?;
+      let slice = left.min(SLEEP_SLICE);
+      self.clock().sleep(slice);
+      left -= slice;
+    }
+    result
+  }
+
+  // Fails before nesting in natives, like serializing a deep value, could
+  // overflow the native stack
+  pub fn check_stack(&self) -> Result<(), RakiError> {
+    self.budget.check_stack()
+  }
+
+  pub fn cancel_handle(&self) -> CancelHandle {
+    self.budget.cancel_handle()
+  }
+
+  // Resets the step, time and allocation budgets, and measures the stack from
+  // here. Hosts call this before each top-level run, not when natives call
+  // back into Raki.
+  pub fn start_run(&self) {
+    self.budget.start();
+  }
+
+  // Charges memory a native uses against the allocation budget
+  pub fn charge(&self, bytes: usize) -> Result<(), RakiError> {
+    self.budget.allocate(bytes)
+  }
+
+  // Charges a newly created value against the allocation budget
+  fn allocate(&self, value: Object) -> Result<Object, RakiError> {
+    self.budget.allocate(shallow_size(&value))?;
+    Ok(value)
+  }
+
+  pub fn define_global(&self, name: &str, value: Object) {
+    self.globals.borrow_mut().define(name, value);
+  }
+
+  pub fn global(&self, name: &str) -> Option<Object> {
+    self.globals.borrow().lookup(name)
+  }
+
+  // Looks `path` up next to the importing file, then in the project root (the
+  // working directory for code that isn't run from a file), then in each
+  // `RAKI_PATH` directory. Files outside the main script's directory and
+  // `RAKI_PATH` need a read grant, and aren't even checked for existence
+  // without one.
+  fn find_module(&self, path: &str, keyword: &Token) -> Result<PathBuf, RakiError> {
+    let base = self.importing.borrow().last().and_then(|file| file.parent().map(Path::to_path_buf));
+    let root = self.root.borrow().clone();
+    let roots: Vec<PathBuf> = root.iter().chain(&self.search_paths).cloned().collect();
+    let project = root.unwrap_or_else(|| env::current_dir().unwrap_or_default());
+    let directories: Vec<PathBuf> = base.into_iter().chain([project]).chain(self.search_paths.iter().cloned()).collect();
+    let candidates: Vec<PathBuf> = directories.iter().map(|directory| directory.join(path)).filter(|file| self.capabilities().allows_import(file, &roots)).collect();
+    if candidates.is_empty() {
+      return Err(self.error(ErrorKind::PermissionError, keyword.line, &format!("import is not allowed to read '{}'.", path)));
+    }
+    if let Some(file) = candidates.into_iter().find(|file| file.is_file()) {
+      return Ok(file.canonicalize().unwrap_or(file));
+    }
+
+    let searched: Vec<String> = directories.iter().map(|directory| directory.display().to_string()).collect();
+    Err(self.error(ErrorKind::ImportError, keyword.line, &format!("Cannot find module '{}', searched {}.", path, searched.join(", "))))
+  }
+
+  // Runs the module the first time it is imported, later imports share it
+  fn load_module(&self, path: &str, keyword: &Token) -> Result<Rc<Module>, RakiError> {
+    let file = self.find_module(path, keyword)?;
+    if let Some(module) = self.modules.borrow().get(&file) {
+      return Ok(module.clone());
+    }
+    if self.importing.borrow().contains(&file) {
+      let root = self.root.borrow().clone().unwrap_or_default();
+      let chain: Vec<String> = self.importing.borrow().iter().chain([&file]).map(|file| file.strip_prefix(&root).unwrap_or(file).display().to_string()).collect();
+      return Err(self.error(ErrorKind::ImportError, keyword.line, &format!("Circular import: {}.", chain.join(" -> "))));
+    }
+
+    let source = fs::read_to_string(&file).map_err(|err| self.error(ErrorKind::ImportError, keyword.line, &format!("Could not read module '{}': {}.", path, err)))?;
+    let statements = analyze(source).map_err(|errors| {
+      // Messages can quote the source, so without a read grant only the line
+      // is given
+      if !self.capabilities().allows_read(&file) {
+        let location = match &errors[0] {
+          RakiError::Syntax { line, .. } | RakiError::Type { line, .. } | RakiError::Runtime { line, .. } | RakiError::Thrown { line, .. } => format!(" on line {}", line),
+          _ => String::new(),
+        };
+        return self.error(ErrorKind::ImportError, keyword.line, &format!("Module '{}' is not valid Raki{}.", path, location));
+      }
+      let location = match &errors[0] {
+        RakiError::Scanner(message) => message.clone(),
+        RakiError::Syntax { line, at, message } => format!("line {}{}: {}", line, at, message),
+        RakiError::Type { line, message } | RakiError::Runtime { line, message, .. } => format!("line {}: {}", line, message),
+        RakiError::Thrown { line, value } => format!("line {}: {}", line, value),
+        RakiError::Io { message, .. } | RakiError::Limit { message, .. } => message.clone(),
+        err @ RakiError::Analysis(_) => err.to_string(),
+      };
+      self.error(ErrorKind::ImportError, keyword.line, &format!("Error in module '{}' on {}", path, location))
+    })?;
+
+    let name = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let module = Rc::new(Module::new(&name, Environment::with_enclosing(self.builtins.clone()), &statements));
+    self.importing.borrow_mut().push(file.clone());
+    let previous = self.environment.replace(module.environment.clone());
+    let result = self.interpret(&statements);
+    self.environment.replace(previous);
+    self.importing.borrow_mut().pop();
+    result?;
+
+    self.modules.borrow_mut().insert(file, module.clone());
+    Ok(module)
+  }
+
+  fn import(&self, path: &str, alias: &Option<Token>, names: &[Token], keyword: &Token) -> Result<(), RakiError> {
+    let module = self.load_module(path, keyword)?;
+    let environment = self.environment.borrow().clone();
+    if let Some(alias) = alias {
+      environment.borrow_mut().define(&alias.lexeme, Object::Module(module.clone()));
+    }
+    for name in names {
+      match module.get(&name.lexeme) {
+        Some(value) => environment.borrow_mut().define(&name.lexeme, value),
+        None => return Err(self.error(ErrorKind::NameError, name.line, &format!("Module '{}' has no export '{}'.", module.name, name.lexeme))),
+      }
+    }
+    Ok(())
+  }
+
+  fn error(&self, kind: ErrorKind, line: u32, message: &str) -> RakiError {
+    RakiError::Runtime { kind, line, message: message.to_string() }
+  }
+
+  fn error_class(&self) -> &Rc<Class> {
+    self.error_class.get().expect("prelude defines Error")
+  }
+
+  // The value a `catch` clause binds: thrown values as they are, built-in
+  // errors as `Error` instances
+  fn error_object(&self, err: RakiError) -> Object {
+    match err {
+      RakiError::Thrown { value, .. } => value,
+      RakiError::Runtime { kind, line, message } => {
+        let mut instance = Instance::new(self.error_class().clone());
+        instance.fields.insert("message".to_string(), Object::String(message));
+        instance.fields.insert("kind".to_string(), Object::String(kind.as_str().to_string()));
+        instance.fields.insert("line".to_string(), Object::Double(line as f64));
+        Object::Instance(Rc::new(RefCell::new(instance)))
+      }
+      err => Object::String(format!("{:?}", err)),
+    }
+  }
+
+  fn throw(&self, value: Object, keyword: &Token) -> RakiError {
+    // Errors created by scripts learn their line when first thrown
+    if let Object::Instance(instance) = &value {
+      let mut instance = instance.borrow_mut();
+      if Rc::ptr_eq(&instance.class, self.error_class()) && instance.fields.get("line") == Some(&Object::None) {
+        instance.fields.insert("line".to_string(), Object::Double(keyword.line as f64));
+      }
+    }
+
+    RakiError::Thrown { line: keyword.line, value }
+  }
+
+  fn execute_try(&self, body: &[Stmt], catch: &Option<(Token, Vec<Stmt>)>, finally: &Option<Vec<Stmt>>) -> Result<Flow, RakiError> {
+    let mut result = self.execute_block(body, self.new_scope());
+
+    if let (Some((name, handler)), Err(err @ (RakiError::Runtime { .. } | RakiError::Thrown { .. }))) = (catch, &result) {
+      let mut environment = self.new_scope();
+      environment.define(&name.lexeme, self.error_object(err.clone()));
+      result = self.execute_block(handler, environment);
+    }
+
+    // A `finally` block that fails or jumps replaces the pending outcome
+    if let Some(finally) = finally {
+      match self.execute_block(finally, self.new_scope())? {
+        Flow::Normal => {}
+        flow => return Ok(flow),
+      }
+    }
+
+    result
+  }
+
+  // Errors raised by `Object` operations don't know where they happened
+  fn error_at(&self, line: u32, err: RakiError) -> RakiError {
+    match err {
+      RakiError::Runtime { kind, message, .. } => self.error(kind, line, &message),
+      err => err,
+    }
+  }
+
+  fn execute_print(&self, values: &[Expr], sep: &Option<Expr>, end: &Option<Expr>, keyword: &Token) -> Result<(), RakiError> {
+    let values = values.iter().map(|expr| self.stringify(&self.visit_expr(expr)?, keyword)).collect::<Result<Vec<String>, RakiError>>()?;
+    let option = |expr: &Option<Expr>, name: &str, default: &str| match expr {
+      None => Ok(default.to_string()),
+      Some(expr) => match self.visit_expr(expr)? {
+        Object::String(s) => Ok(s),
+        obj => Err(self.error(ErrorKind::TypeError, keyword.line, &format!("print {} must be a string, not {}.", name, obj.type_name()))),
       },
-      TokenType::Bang => match right {
-        Object::Boolean(val) => return Ok(Object::Boolean(!val)),
-        Object::None => return Ok(Object::Boolean(false)),
-        _ => return Err(self.error()),
+    };
+    let sep = option(sep, "sep", " ")?;
+    let end = option(end, "end", "\n")?;
+    print!("{}{}", values.join(&sep), end);
+    // Output without a newline would otherwise wait in the buffer
+    if !end.ends_with('\n') {
+      let _ = io::stdout().flush();
+    }
+    Ok(())
+  }
+
+  fn execute_block(&self, statements: &[Stmt], environment: Environment) -> Result<Flow, RakiError> {
+    self.with_environment(environment, || self.execute_statements(statements))
+  }
+
+  // Runs `f` with `environment` as the current scope, restoring the previous
+  // one even when `f` fails
+  fn with_environment<T>(&self, environment: Environment, f: impl FnOnce() -> Result<T, RakiError>) -> Result<T, RakiError> {
+    let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
+    let result = f();
+    self.environment.replace(previous);
+    result
+  }
+
+  fn new_scope(&self) -> Environment {
+    Environment::with_enclosing(self.environment.borrow().clone())
+  }
+
+  fn execute_statements(&self, statements: &[Stmt]) -> Result<Flow, RakiError> {
+    for stmt in statements {
+      match self.visit_stmt(stmt)? {
+        Flow::Normal => {}
+        flow => return Ok(flow),
+      }
+    }
+
+    Ok(Flow::Normal)
+  }
+
+  fn call_function(&self, function: &Function, arguments: Vec<Object>, paren: &Token) -> Result<Object, RakiError> {
+    if arguments.len() != function.arity() {
+      return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Expected {} arguments but got {}.", function.arity(), arguments.len())));
+    }
+
+    let mut environment = Environment::with_enclosing(function.closure.clone());
+    for (param, argument) in function.params.iter().zip(arguments) {
+      environment.define(&param.lexeme, argument);
+    }
+
+    let flow = self.budget.nested(|| self.execute_block(&function.body, environment))?;
+    if function.is_initializer {
+      return Ok(function.closure.borrow().lookup("this").unwrap_or(Object::None));
+    }
+
+    match flow {
+      Flow::Return(value) => Ok(value),
+      _ => Ok(Object::None),
+    }
+  }
+
+  fn call(&self, callee: Object, arguments: Vec<Object>, paren: &Token) -> Result<Object, RakiError> {
+    match callee {
+      Object::Function(function) => self.call_function(&function, arguments, paren),
+      Object::Native(function) => {
+        if !function.arity.accepts(arguments.len()) {
+          return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Expected {} arguments but got {}.", function.arity, arguments.len())));
+        }
+        // Errors from Raki code the native called back into keep their line
+        let result = (function.function)(self, &arguments).map_err(|err| match err {
+          RakiError::Runtime { line: 0, .. } => self.error_at(paren.line, err),
+          err => err,
+        })?;
+        self.allocate(result)
+      }
+      Object::Class(class) => {
+        let instance = self.allocate(Object::Instance(Rc::new(RefCell::new(Instance::new(class.clone())))))?;
+        match class.find_method("init") {
+          Some(init) => self.call_function(&init.bind(instance), arguments, paren),
+          None if arguments.is_empty() => Ok(instance),
+          None => Err(self.error(ErrorKind::TypeError, paren.line, &format!("Expected 0 arguments but got {}.", arguments.len()))),
+        }
+      }
+      Object::Constructor(variant) => {
+        if arguments.len() != variant.fields.len() {
+          return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Expected {} arguments but got {}.", variant.fields.len(), arguments.len())));
+        }
+        Ok(Object::EnumValue(Rc::new(EnumValue { variant, values: arguments })))
+      }
+      Object::Instance(instance) => match self.call_method(&instance, "__call__", arguments, paren)? {
+        Some(result) => Ok(result),
+        None => Err(self.error(ErrorKind::TypeError, paren.line, &format!("Instances of {} are not callable, define a __call__() method.", instance.borrow().class.name))),
       },
-      _ => return Ok(Object::None),
+      _ => Err(self.error(ErrorKind::TypeError, paren.line, "Can only call functions and classes.")),
+    }
+  }
+
+  // Calls a method looked up on the instance's class, `None` when the class
+  // doesn't define it
+  fn call_method(&self, instance: &Rc<RefCell<Instance>>, name: &str, arguments: Vec<Object>, token: &Token) -> Result<Option<Object>, RakiError> {
+    let method = instance.borrow().class.find_method(name);
+    match method {
+      Some(method) => self.call_function(&method.bind(Object::Instance(instance.clone())), arguments, token).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  // Calls the special method overloading `operator`, trying the reflected
+  // method of the right operand when the left one doesn't define it
+  fn call_operator(&self, left: &Object, right: &Object, operator: &Token) -> Result<Option<Object>, RakiError> {
+    let (method, reflected) = match operator.r#type {
+      TokenType::Plus => ("__add__", "__radd__"),
+      TokenType::Minus => ("__sub__", "__rsub__"),
+      TokenType::Star => ("__mul__", "__rmul__"),
+      TokenType::Slash => ("__div__", "__rdiv__"),
+      TokenType::Less => ("__lt__", "__gt__"),
+      TokenType::Greater => ("__gt__", "__lt__"),
+      TokenType::LessEqual => ("__le__", "__ge__"),
+      TokenType::GreaterEqual => ("__ge__", "__le__"),
+      TokenType::EqualEqual | TokenType::BangEqual => ("__eq__", "__eq__"),
+      _ => return Ok(None),
+    };
+
+    if let Object::Instance(instance) = left
+      && let Some(result) = self.call_method(instance, method, vec![right.clone()], operator)?
+    {
+      return Ok(Some(result));
+    }
+    if let Object::Instance(instance) = right
+      && let Some(result) = self.call_method(instance, reflected, vec![left.clone()], operator)?
+    {
+      return Ok(Some(result));
+    }
+
+    // Instances without `__eq__` compare by identity
+    if method == "__eq__" {
+      return Ok(None);
+    }
+    let message = match (left, right) {
+      (Object::Instance(instance), _) => format!("Instances of {} don't support '{}', define a {}() method.", instance.borrow().class.name, operator.lexeme, method),
+      (_, Object::Instance(instance)) => format!("Instances of {} don't support '{}' on the right, define a {}() method.", instance.borrow().class.name, operator.lexeme, reflected),
+      _ => return Ok(None),
+    };
+    Err(self.error(ErrorKind::TypeError, operator.line, &message))
+  }
+
+  // How `print` shows a value, using `__str__()` methods
+  pub fn display(&self, value: &Object) -> Result<String, RakiError> {
+    let token = Token { r#type: TokenType::Identifier, lexeme: String::new(), literal: LiteralType::None, line: 0 };
+    self.stringify(value, &token)
+  }
+
+  // Like `Display`, but instances are shown with their `__str__()` method when
+  // their class defines one
+  fn stringify(&self, value: &Object, token: &Token) -> Result<String, RakiError> {
+    self.budget.check_stack()?;
+    let nested = |value: &Object| match value {
+      Object::String(_) => Ok(value.repr()),
+      value => self.stringify(value, token),
+    };
+
+    match value {
+      Object::Instance(instance) => match self.call_method(instance, "__str__", Vec::new(), token)? {
+        Some(Object::String(s)) => Ok(s),
+        Some(obj) => Err(self.error(ErrorKind::TypeError, token.line, &format!("__str__() must return a string, not {}.", obj.type_name()))),
+        None => Ok(value.to_string()),
+      },
+      Object::List(list) => {
+        let elements = print_container(Rc::as_ptr(list) as *const (), || {
+          let elements = list.borrow().clone();
+          elements.iter().map(nested).collect::<Result<Vec<String>, RakiError>>()
+        });
+        Ok(format!("[{}]", elements.transpose()?.map(|elements| elements.join(", ")).as_deref().unwrap_or("...")))
+      }
+      Object::Tuple(elements) if elements.len() == 1 => Ok(format!("({},)", nested(&elements[0])?)),
+      Object::Tuple(elements) => {
+        let elements = elements.iter().map(nested).collect::<Result<Vec<String>, RakiError>>()?;
+        Ok(format!("({})", elements.join(", ")))
+      }
+      Object::Map(map) => {
+        let entries = print_container(Rc::as_ptr(map) as *const (), || {
+          let entries: Vec<(Object, Object)> = map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+          entries.iter().map(|(k, v)| Ok(format!("{}: {}", nested(k)?, nested(v)?))).collect::<Result<Vec<String>, RakiError>>()
+        });
+        Ok(format!("{{{}}}", entries.transpose()?.map(|entries| entries.join(", ")).as_deref().unwrap_or("...")))
+      }
+      value => Ok(value.to_string()),
     }
   }
 
-  fn visit_ternary_expr(&self, condition: &Expr, left: &Expr, right: &Expr) -> Result<Object, RakiError> {
-    if let Object::Boolean(obj) = self.visit_expr(condition)? {
-      match obj {
-        true => return self.visit_expr(left),
-        false => return self.visit_expr(right)
+  fn get_property(&self, object: Object, name: &Token) -> Result<Object, RakiError> {
+    if let Object::Instance(instance) = &object {
+      if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+        return Ok(value.clone());
+      }
+      let class = instance.borrow().class.clone();
+      if let Some(getter) = class.find_getter(&name.lexeme) {
+        return self.call_function(&getter.bind(object.clone()), Vec::new(), name);
+      }
+      if let Some(method) = class.find_method(&name.lexeme) {
+        return Ok(Object::Function(Rc::new(method.bind(object.clone()))));
+      }
+    }
+    // Class methods see the class they are accessed on as `this`
+    if let Object::Class(class) = &object {
+      if let Some(value) = class.find_class_field(&name.lexeme) {
+        return Ok(value);
+      }
+      if let Some(method) = class.find_class_method(&name.lexeme) {
+        return Ok(Object::Function(Rc::new(method.bind(object.clone()))));
+      }
+    }
+    if let Object::Enum(enumeration) = &object {
+      return match enumeration.find_variant(&name.lexeme) {
+        Some(variant) if variant.fields.is_empty() => Ok(Object::EnumValue(Rc::new(EnumValue { variant, values: Vec::new() }))),
+        Some(variant) => Ok(Object::Constructor(variant)),
+        None => Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined variant '{}' on enum {}.", name.lexeme, enumeration.name))),
+      };
+    }
+    if let Object::EnumValue(value) = &object
+      && let Some(field) = value.field(&name.lexeme)
+    {
+      return Ok(field);
+    }
+    if let Object::Module(module) = &object {
+      return module.get(&name.lexeme).ok_or_else(|| self.error(ErrorKind::NameError, name.line, &format!("Module '{}' has no export '{}'.", module.name, name.lexeme)));
+    }
+    if let Object::UserData(data) = &object {
+      if let Some(getter) = self.user_member(data, |user_type| &user_type.getters, &name.lexeme) {
+        return getter(data).map_err(|err| self.error_at(name.line, err));
+      }
+      if let Some((arity, method)) = self.user_member(data, |user_type| &user_type.methods, &name.lexeme) {
+        let receiver = data.clone();
+        return Ok(Object::Native(Rc::new(NativeFunction::new(&name.lexeme, Arity::Fixed(arity), move |interpreter, arguments| method(interpreter, &receiver, arguments)))));
       }
     }
 
-    return Err(self.error());
+    Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined property '{}' on {}.", name.lexeme, object.type_name())))
   }
-}
 
-impl Visitor<Result<Object, RakiError>> for Interpreter {
-  fn visit_expr(&self, e: &Expr) -> Result<Object, RakiError> {
-    match e {
-      Expr::Binary { left, right, operator } => return self.visit_binary_expr(left, right, operator),
-      Expr::Grouping { expr } => return self.visit_expr(&expr),
-      Expr::Literal { value } => return self.visit_literal_expr(value),
-      Expr::Unary { right, operator } => return self.visit_unary_expr(right, operator),
-      Expr::Ternary { condition, left, right } => return self.visit_ternary_expr(condition, left, right),
+  fn condition(&self, condition: &Expr, keyword: &Token) -> Result<bool, RakiError> {
+    match self.visit_expr(condition)? {
+      Object::Boolean(value) => Ok(value),
+      obj => Err(self.error(ErrorKind::TypeError, keyword.line, &format!("Condition of '{}' must be a bool, not {}.", keyword.lexeme, obj.type_name()))),
+    }
+  }
+
+  fn iterator(&self, iterable: Object, keyword: &Token) -> Result<RakiIterator, RakiError> {
+    match iterable {
+      Object::List(list) => Ok(RakiIterator::list(list)),
+      Object::Tuple(values) => Ok(RakiIterator::values(values.to_vec())),
+      Object::Map(map) => Ok(RakiIterator::values(map.borrow().keys().cloned().collect())),
+      Object::String(s) => Ok(RakiIterator::values(s.chars().map(|c| Object::String(c.to_string())).collect())),
+      Object::Range { start, end, inclusive } => Ok(RakiIterator::Range { next: start, end, inclusive }),
+      Object::Instance(instance) => match self.call_method(&instance, "iter", Vec::new(), keyword)? {
+        Some(iterator) => Ok(RakiIterator::User { iterator }),
+        None => Err(self.error(ErrorKind::TypeError, keyword.line, &format!("Instances of {} are not iterable, define an iter() method.", instance.borrow().class.name))),
+      },
+      obj => Err(self.error(ErrorKind::TypeError, keyword.line, &format!("Value of type {} is not iterable.", obj.type_name()))),
+    }
+  }
+
+  // User iterators signal the end by returning nil from `next()`
+  fn next_value(&self, iterator: &mut RakiIterator, keyword: &Token) -> Result<Option<Object>, RakiError> {
+    let RakiIterator::User { iterator } = iterator else {
+      return iterator.next_builtin().map_err(|err| self.error_at(keyword.line, err));
+    };
+
+    let Object::Instance(instance) = iterator else {
+      return Err(self.error(ErrorKind::TypeError, keyword.line, &format!("iter() must return an instance with a next() method, not {}.", iterator.type_name())));
+    };
+
+    match self.call_method(instance, "next", Vec::new(), keyword)? {
+      Some(Object::None) => Ok(None),
+      Some(value) => Ok(Some(value)),
+      None => Err(self.error(ErrorKind::TypeError, keyword.line, &format!("Iterator of class {} has no next() method.", instance.borrow().class.name))),
+    }
+  }
+
+  // Binds `value` to a variable, index or (nested) tuple target, either
+  // declaring new variables or assigning existing ones
+  // `token` gives the line for targets that can't be assigned to
+  fn destructure(&self, target: &Expr, value: Object, declare: bool, token: &Token) -> Result<(), RakiError> {
+    match target {
+      Expr::Tuple { elements, paren } => {
+        let values = match &value {
+          Object::Tuple(values) => values.as_ref().clone(),
+          Object::List(values) => values.borrow().clone(),
+          obj => return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Cannot destructure a value of type {}.", obj.type_name()))),
+        };
+        if values.len() != elements.len() {
+          return Err(self.error(ErrorKind::ValueError, paren.line, &format!("Cannot destructure {} values into {} targets.", values.len(), elements.len())));
+        }
+
+        for (element, value) in elements.iter().zip(values) {
+          self.destructure(element, value, declare, token)?;
+        }
+        Ok(())
+      }
+      Expr::Variable { name } if declare => {
+        self.environment.borrow().borrow_mut().define(&name.lexeme, value);
+        Ok(())
+      }
+      Expr::Variable { name } => self.environment.borrow().borrow_mut().assign(name, value),
+      Expr::Index { object, index, bracket } => {
+        let object = self.visit_expr(object)?;
+        let index = self.visit_expr(index)?;
+        self.set_index(object, index, value, bracket).map(|_| ())
+      }
+      _ => Err(self.error(ErrorKind::TypeError, token.line, "Invalid destructuring target.")),
+    }
+  }
+
+  // Whether `value` fits `pattern`, collecting the names it binds
+  fn match_pattern(&self, pattern: &Pattern, value: &Object, bindings: &mut Vec<(String, Object)>) -> Result<bool, RakiError> {
+    match pattern {
+      Pattern::Wildcard => Ok(true),
+      Pattern::Binding { name } => {
+        bindings.push((name.lexeme.clone(), value.clone()));
+        Ok(true)
+      }
+      Pattern::Literal { value: literal } => Ok(self.visit_literal_expr(literal)? == *value),
+      Pattern::Value { value: expr } => Ok(self.visit_expr(expr)? == *value),
+      Pattern::Range { start, end, inclusive } => match value {
+        Object::Double(n) if *inclusive => Ok(start <= n && n <= end),
+        Object::Double(n) => Ok(start <= n && n < end),
+        _ => Ok(false),
+      },
+      Pattern::List { elements, rest } => {
+        let Object::List(list) = value else {
+          return Ok(false);
+        };
+        let values = list.borrow().clone();
+        let fits = match rest {
+          Some(_) => values.len() >= elements.len(),
+          None => values.len() == elements.len(),
+        };
+        if !fits || !self.match_all(elements, &values, bindings)? {
+          return Ok(false);
+        }
+
+        match rest {
+          Some(rest) => self.match_pattern(rest, &Object::new_list(values[elements.len()..].to_vec()), bindings),
+          None => Ok(true),
+        }
+      }
+      Pattern::Tuple { elements } => match value {
+        Object::Tuple(values) if values.len() == elements.len() => self.match_all(elements, values, bindings),
+        _ => Ok(false),
+      },
+      Pattern::Map { entries } => {
+        let Object::Map(map) = value else {
+          return Ok(false);
+        };
+        for (key, pattern) in entries {
+          let entry = map.borrow().get(&self.visit_literal_expr(key)?).cloned();
+          match entry {
+            Some(entry) if self.match_pattern(pattern, &entry, bindings)? => {}
+            _ => return Ok(false),
+          }
+        }
+        Ok(true)
+      }
+      Pattern::Class { path, fields, paren } => {
+        let class = match self.visit_expr(path)? {
+          Object::Class(class) => class,
+          Object::Constructor(variant) => {
+            let Object::EnumValue(value) = value else {
+              return Ok(false);
+            };
+            if fields.len() > variant.fields.len() {
+              return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Pattern for {}.{} has {} fields but the variant has {}.", variant.enum_name, variant.name, fields.len(), variant.fields.len())));
+            }
+            return match Rc::ptr_eq(&value.variant, &variant) {
+              true => self.match_all(fields, &value.values, bindings),
+              false => Ok(false),
+            };
+          }
+          // `Shape.Empty()` names a variant without fields
+          unit @ Object::EnumValue(_) if fields.is_empty() => return Ok(unit == *value),
+          obj => return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Class patterns need a class or enum variant, not {}.", obj.type_name()))),
+        };
+        let Object::Instance(instance) = value else {
+          return Ok(false);
+        };
+        if !Rc::ptr_eq(&instance.borrow().class, &class) {
+          return Ok(false);
+        }
+
+        // Positional fields are the ones named by the initializer's parameters
+        let params = class.find_method("init").map(|init| init.params.clone()).unwrap_or_default();
+        if fields.len() > params.len() {
+          return Err(self.error(ErrorKind::TypeError, paren.line, &format!("Pattern for {} has {} fields but init takes {} parameters.", class.name, fields.len(), params.len())));
+        }
+        for (param, pattern) in params.iter().zip(fields) {
+          let field = instance.borrow().fields.get(&param.lexeme).cloned();
+          match field {
+            Some(field) if self.match_pattern(pattern, &field, bindings)? => {}
+            _ => return Ok(false),
+          }
+        }
+        Ok(true)
+      }
+    }
+  }
+
+  fn match_all(&self, patterns: &[Pattern], values: &[Object], bindings: &mut Vec<(String, Object)>) -> Result<bool, RakiError> {
+    for (pattern, value) in patterns.iter().zip(values) {
+      if !self.match_pattern(pattern, value, bindings)? {
+        return Ok(false);
+      }
+    }
+
+    Ok(true)
+  }
+
+  fn methods(&self, functions: &[Stmt], closure: Rc<RefCell<Environment>>) -> HashMap<String, Rc<Function>> {
+    functions
+      .iter()
+      .filter_map(|method| match method {
+        Stmt::Function { name, params, body, .. } => {
+          let mut function = Function::new(&name.lexeme, params, body, closure.clone());
+          function.is_initializer = name.lexeme == "init";
+          Some((name.lexeme.clone(), Rc::new(function)))
+        }
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn define_class(&self, declaration: &Stmt) -> Result<(), RakiError> {
+    let Stmt::Class { name, superclass, traits, methods, getters, setters, statics } = declaration else {
+      return Ok(());
+    };
+    let superclass = match superclass {
+      Some(expr) => match self.visit_expr(expr)? {
+        Object::Class(class) => Some(class),
+        obj => return Err(self.error(ErrorKind::TypeError, name.line, &format!("Superclass must be a class, not {}.", obj.type_name()))),
+      },
+      None => None,
+    };
+    let traits = traits
+      .iter()
+      .map(|expr| match self.visit_expr(expr)? {
+        Object::Trait(t) => Ok(t),
+        obj => Err(self.error(ErrorKind::TypeError, name.line, &format!("Can only implement traits, not {}.", obj.type_name()))),
+      })
+      .collect::<Result<Vec<Rc<Trait>>, RakiError>>()?;
+
+    // Methods of subclasses see their superclass as `super`
+    let mut closure = self.environment.borrow().clone();
+    if let Some(superclass) = &superclass {
+      let mut environment = Environment::with_enclosing(closure);
+      environment.define("super", Object::Class(superclass.clone()));
+      closure = Rc::new(RefCell::new(environment));
+    }
+
+    // Default methods fill in what the class neither defines nor inherits
+    let mut methods = self.methods(methods, closure.clone());
+    for t in &traits {
+      for (method, default) in &t.defaults {
+        let inherited = superclass.as_ref().is_some_and(|superclass| superclass.find_method(method).is_some());
+        if !inherited && !methods.contains_key(method) {
+          methods.insert(method.clone(), default.clone());
+        }
+      }
+    }
+
+    let mut class = Class::new(&name.lexeme, methods, superclass, traits);
+    class.getters = self.methods(getters, closure.clone());
+    class.setters = self.methods(setters, closure.clone());
+    class.class_methods = self.methods(statics, closure);
+    for t in &class.traits {
+      for (method, arity) in &t.required {
+        match class.find_method(method) {
+          None => return Err(self.error(ErrorKind::TypeError, name.line, &format!("Class {} is missing method '{}' required by trait {}.", class.name, method, t.name))),
+          Some(found) if found.arity() != *arity => {
+            return Err(self.error(ErrorKind::TypeError, name.line, &format!("Method '{}' of class {} takes {} parameters but trait {} requires {}.", method, class.name, found.arity(), t.name, arity)));
+          }
+          Some(_) => {}
+        }
+      }
+    }
+
+    // Class fields are initialized once the class exists, so they can refer
+    // to it
+    let class = Rc::new(class);
+    self.environment.borrow().borrow_mut().define(&name.lexeme, Object::Class(class.clone()));
+    for field in statics {
+      if let Stmt::Var { name, initializer, .. } = field {
+        let value = match initializer {
+          Some(initializer) => self.visit_expr(initializer)?,
+          None => Object::None,
+        };
+        class.class_fields.borrow_mut().insert(name.lexeme.clone(), value);
+      }
+    }
+    Ok(())
+  }
+
+  // `x is C` holds for instances of `C` and its subclasses, and for instances
+  // of classes implementing the trait `C`
+  fn is_instance(&self, value: &Object, target: &Object, operator: &Token) -> Result<bool, RakiError> {
+    let class = match value {
+      Object::Instance(instance) => Some(instance.borrow().class.clone()),
+      _ => None,
+    };
+
+    match target {
+      Object::Class(target) => Ok(class.is_some_and(|class| class.is_subclass_of(target))),
+      Object::Trait(target) => Ok(class.is_some_and(|class| class.implements(target))),
+      obj => Err(self.error(ErrorKind::TypeError, operator.line, &format!("Right operand of 'is' must be a class or trait, not {}.", obj.type_name()))),
+    }
+  }
+
+  fn check_key(&self, key: &Object, line: u32) -> Result<(), RakiError> {
+    match key.is_hashable() {
+      true => Ok(()),
+      false => Err(self.error(ErrorKind::TypeError, line, &format!("Unhashable map key of type {}.", key.type_name()))),
+    }
+  }
+
+  fn list_index(&self, len: usize, index: &Object, line: u32) -> Result<usize, RakiError> {
+    match index {
+      Object::Double(i) if i.fract() == 0.0 && *i >= 0.0 && (*i as usize) < len => Ok(*i as usize),
+      Object::Double(i) => Err(self.error(ErrorKind::IndexError, line, &format!("Index {} out of bounds for list of length {}.", i, len))),
+      obj => Err(self.error(ErrorKind::TypeError, line, &format!("List indices must be numbers, not {}.", obj.type_name()))),
     }
   }
+
+  // Strings are indexed by character, not by byte
+  fn string_index(&self, s: &str, index: &Object, line: u32) -> Result<Object, RakiError> {
+    let len = s.chars().count();
+    match index {
+      Object::Double(i) if i.fract() == 0.0 && *i >= 0.0 && (*i as usize) < len => Ok(Object::String(s.chars().nth(*i as usize).unwrap_or_default().to_string())),
+      Object::Double(i) => Err(self.error(ErrorKind::IndexError, line, &format!("Index {} out of bounds for string of length {}.", i, len))),
+      obj => Err(self.error(ErrorKind::TypeError, line, &format!("String indices must be numbers, not {}.", obj.type_name()))),
+    }
+  }
+
+  fn check_arity(&self, name: &Token, expected: usize, arguments: &[Object]) -> Result<(), RakiError> {
+    match arguments.len() == expected {
+      true => Ok(()),
+      false => Err(self.error(ErrorKind::TypeError, name.line, &format!("Expected {} arguments but got {}.", expected, arguments.len()))),
+    }
+  }
+
+  // For built-ins with optional trailing parameters
+  fn check_arity_between(&self, name: &Token, min: usize, max: usize, arguments: &[Object]) -> Result<(), RakiError> {
+    match (min..=max).contains(&arguments.len()) {
+      true => Ok(()),
+      false => Err(self.error(ErrorKind::TypeError, name.line, &format!("Expected {} to {} arguments but got {}.", min, max, arguments.len()))),
+    }
+  }
+
+  fn string_argument<'a>(&self, name: &Token, arguments: &'a [Object], i: usize) -> Result<&'a str, RakiError> {
+    match &arguments[i] {
+      Object::String(s) => Ok(s),
+      obj => Err(self.error(ErrorKind::TypeError, name.line, &format!("Argument {} of {}() must be a string, not {}.", i + 1, name.lexeme, obj.type_name()))),
+    }
+  }
+
+  fn number_argument(&self, name: &Token, arguments: &[Object], i: usize) -> Result<f64, RakiError> {
+    number_argument(&name.lexeme, arguments, i).map_err(|err| self.error_at(name.line, err))
+  }
+
+  // A count or position, which has to be a whole non-negative number
+  // An empty string with room for `len` bytes, charged to the allocation budget
+  // first. Lengths that overflow or can't be allocated are a ValueError
+  // rather than a panic or an abort.
+  pub fn new_string(&self, function: &str, line: u32, len: Option<usize>) -> Result<String, RakiError> {
+    let too_large = || self.error(ErrorKind::ValueError, line, &format!("{}() result is too large.", function));
+    let len = len.ok_or_else(too_large)?;
+    self.budget.allocate(mem::size_of::<Object>().saturating_add(len))?;
+    let mut result = String::new();
+    result.try_reserve_exact(len).map_err(|_| too_large())?;
+    Ok(result)
+  }
+
+  fn count_argument(&self, name: &Token, arguments: &[Object], i: usize) -> Result<usize, RakiError> {
+    match self.number_argument(name, arguments, i)? {
+      n if n.fract() == 0.0 && n >= 0.0 => Ok(n as usize),
+      n => Err(self.error(ErrorKind::ValueError, name.line, &format!("Argument {} of {}() must be a non-negative integer, not {}.", i + 1, name.lexeme, n))),
+    }
+  }
+}
+
+impl Interpreter {
+  fn visit_binary_expr(&self, left: &Expr, right: &Expr, operator: &Token) -> Result<Object, RakiError> {
+    let left: Object = self.visit_expr(left)?;
+    let right: Object = self.visit_expr(right)?;
+
+    if operator.r#type != TokenType::Is
+      && (matches!(left, Object::Instance(_)) || matches!(right, Object::Instance(_)))
+      && let Some(result) = self.call_operator(&left, &right, operator)?
+    {
+      return match (operator.r#type, result) {
+        (TokenType::BangEqual, Object::Boolean(equal)) => Ok(Object::Boolean(!equal)),
+        (TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, result) | (_, result @ Object::Boolean(_)) => Ok(result),
+        (_, obj) => Err(self.error(ErrorKind::TypeError, operator.line, &format!("Overloaded '{}' must return a bool, not {}.", operator.lexeme, obj.type_name()))),
+      };
+    }
+
+    let result = match operator.r#type {
+      TokenType::Is => return self.is_instance(&left, &right, operator).map(Object::Boolean),
+      TokenType::Plus => (left + right).and_then(|result| self.allocate(result)),
+      TokenType::Minus => left - right,
+      TokenType::Star => (left * right).and_then(|result| self.allocate(result)),
+      TokenType::Slash => left / right,
+      TokenType::Greater => return Ok(Object::Boolean(left > right)),
+      TokenType::GreaterEqual => return Ok(Object::Boolean(left >= right)),
+      TokenType::Less => return Ok(Object::Boolean(left < right)),
+      TokenType::LessEqual => return Ok(Object::Boolean(left <= right)),
+      TokenType::BangEqual => return Ok(Object::Boolean(left != right)),
+      TokenType::EqualEqual => return Ok(Object::Boolean(left == right)),
+      _ => return Ok(Object::None),
+    };
+
+    result.map_err(|err| self.error_at(operator.line, err))
+  }
+
+  fn visit_match_expr(&self, value: &Expr, arms: &[MatchArm], keyword: &Token) -> Result<Object, RakiError> {
+    let value = self.visit_expr(value)?;
+
+    for arm in arms {
+      let mut bindings = Vec::new();
+      if !self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+        continue;
+      }
+
+      let mut environment = self.new_scope();
+      for (name, value) in bindings {
+        environment.define(&name, value);
+      }
+      let result = self.with_environment(environment, || {
+        if let Some(guard) = &arm.guard {
+          match self.visit_expr(guard)? {
+            Object::Boolean(true) => {}
+            Object::Boolean(false) => return Ok(None),
+            obj => return Err(self.error(ErrorKind::TypeError, arm.arrow.line, &format!("Match guard must be a bool, not {}.", obj.type_name()))),
+          }
+        }
+        self.visit_expr(&arm.body).map(Some)
+      })?;
+
+      if let Some(result) = result {
+        return Ok(result);
+      }
+    }
+
+    Err(self.error(ErrorKind::MatchError, keyword.line, &format!("No match arm matched value {}.", value.repr())))
+  }
+
+  fn visit_literal_expr(&self, lit: &LiteralType) -> Result<Object, RakiError> {
+    match lit {
+      LiteralType::String(s) => return Ok(Object::String(s.clone())),
+      LiteralType::F64(val) => return Ok(Object::Double(*val)),
+      LiteralType::Bool(val) => return Ok(Object::Boolean(*val)),
+      LiteralType::None => return Ok(Object::None),
+    }
+  }
+
+  fn visit_unary_expr(&self, right: &Expr, operator: &Token) -> Result<Object, RakiError> {
+    let right = self.visit_expr(right)?;
+    match operator.r#type {
+      TokenType::Minus => match right {
+        Object::Double(val) => return Ok(Object::Double(-val)),
+        obj => return Err(self.error(ErrorKind::TypeError, operator.line, &format!("Bad operand type for unary -: {}.", obj.type_name()))),
+      },
+      TokenType::Bang => match right {
+        Object::Boolean(val) => return Ok(Object::Boolean(!val)),
+        Object::None => return Ok(Object::Boolean(false)),
+        obj => return Err(self.error(ErrorKind::TypeError, operator.line, &format!("Bad operand type for unary !: {}.", obj.type_name()))),
+      },
+      _ => return Ok(Object::None),
+    }
+  }
+
+  fn visit_ternary_expr(&self, condition: &Expr, left: &Expr, right: &Expr, operator: &Token) -> Result<Object, RakiError> {
+    match self.visit_expr(condition)? {
+      Object::Boolean(true) => return self.visit_expr(left),
+      Object::Boolean(false) => return self.visit_expr(right),
+      obj => return Err(self.error(ErrorKind::TypeError, operator.line, &format!("Ternary condition must be a bool, not {}.", obj.type_name()))),
+    }
+  }
+
+  fn visit_assign_expr(&self, name: &Token, value: &Expr) -> Result<Object, RakiError> {
+    let value = self.visit_expr(value)?;
+    self.environment.borrow().borrow_mut().assign(name, value.clone())?;
+    Ok(value)
+  }
+
+  fn visit_list_expr(&self, elements: &[Expr]) -> Result<Object, RakiError> {
+    let elements = elements.iter().map(|e| self.visit_expr(e)).collect::<Result<Vec<Object>, RakiError>>()?;
+    self.allocate(Object::new_list(elements))
+  }
+
+  fn visit_map_expr(&self, entries: &[(Expr, Expr)], brace: &Token) -> Result<Object, RakiError> {
+    let mut map = RakiMap::new();
+    for (key, value) in entries {
+      let key = self.visit_expr(key)?;
+      self.check_key(&key, brace.line)?;
+      map.insert(key, self.visit_expr(value)?);
+    }
+
+    self.allocate(Object::new_map(map))
+  }
+
+  fn visit_index_expr(&self, object: &Expr, index: &Expr, bracket: &Token) -> Result<Object, RakiError> {
+    let object = self.visit_expr(object)?;
+    let index = self.visit_expr(index)?;
+
+    match object {
+      Object::List(list) => {
+        let list = list.borrow();
+        let i = self.list_index(list.len(), &index, bracket.line)?;
+        Ok(list[i].clone())
+      }
+      Object::Tuple(elements) => {
+        let i = self.list_index(elements.len(), &index, bracket.line)?;
+        Ok(elements[i].clone())
+      }
+      Object::String(s) => self.string_index(&s, &index, bracket.line),
+      Object::Map(map) => {
+        self.check_key(&index, bracket.line)?;
+        match map.borrow().get(&index) {
+          Some(value) => Ok(value.clone()),
+          None => Err(self.error(ErrorKind::KeyError, bracket.line, &format!("Key '{}' not found in map.", index))),
+        }
+      }
+      Object::Instance(instance) => match self.call_method(&instance, "__index__", vec![index], bracket)? {
+        Some(value) => Ok(value),
+        None => Err(self.error(ErrorKind::TypeError, bracket.line, &format!("Instances of {} can't be indexed, define an __index__() method.", instance.borrow().class.name))),
+      },
+      obj => Err(self.error(ErrorKind::TypeError, bracket.line, &format!("Can't index into a value of type {}.", obj.type_name()))),
+    }
+  }
+
+  fn visit_index_set_expr(&self, object: &Expr, index: &Expr, value: &Expr, bracket: &Token) -> Result<Object, RakiError> {
+    let object = self.visit_expr(object)?;
+    let index = self.visit_expr(index)?;
+    let value = self.visit_expr(value)?;
+    self.set_index(object, index, value, bracket)
+  }
+
+  fn set_index(&self, object: Object, index: Object, value: Object, bracket: &Token) -> Result<Object, RakiError> {
+    match object {
+      Object::List(list) => {
+        let mut list = list.borrow_mut();
+        let i = self.list_index(list.len(), &index, bracket.line)?;
+        list[i] = value.clone();
+      }
+      Object::Map(map) => {
+        self.check_key(&index, bracket.line)?;
+        if !map.borrow().contains_key(&index) {
+          self.budget.allocate(3 * mem::size_of::<Object>())?;
+        }
+        map.borrow_mut().insert(index, value.clone());
+      }
+      Object::Tuple(_) => return Err(self.error(ErrorKind::TypeError, bracket.line, "Tuples are immutable.")),
+      obj => return Err(self.error(ErrorKind::TypeError, bracket.line, &format!("Can't assign by index into a value of type {}.", obj.type_name()))),
+    }
+
+    Ok(value)
+  }
+
+  fn visit_get_expr(&self, object: &Expr, name: &Token) -> Result<Object, RakiError> {
+    let object = self.visit_expr(object)?;
+    self.get_property(object, name)
+  }
+
+  fn visit_set_expr(&self, object: &Expr, name: &Token, value: &Expr) -> Result<Object, RakiError> {
+    let object = self.visit_expr(object)?;
+    let value = self.visit_expr(value)?;
+    match &object {
+      Object::Instance(instance) => {
+        let setter = instance.borrow().class.find_setter(&name.lexeme);
+        match setter {
+          Some(setter) => _ = self.call_function(&setter.bind(object.clone()), vec![value.clone()], name)?,
+          None => {
+            if !instance.borrow().fields.contains_key(&name.lexeme) {
+              self.budget.allocate(2 * mem::size_of::<Object>())?;
+            }
+            instance.borrow_mut().fields.insert(name.lexeme.clone(), value.clone());
+          }
+        }
+      }
+      Object::Class(class) => class.set_class_field(&name.lexeme, value.clone()),
+      Object::UserData(data) => match self.user_member(data, |user_type| &user_type.setters, &name.lexeme) {
+        Some(setter) => setter(data, &value).map_err(|err| self.error_at(name.line, err))?,
+        None => return Err(self.error(ErrorKind::TypeError, name.line, &format!("Property '{}' of {} can't be set.", name.lexeme, data.name))),
+      },
+      _ => return Err(self.error(ErrorKind::TypeError, name.line, "Only instances and classes have fields.")),
+    }
+    Ok(value)
+  }
+
+  fn visit_range_expr(&self, start: &Expr, end: &Expr, operator: &Token) -> Result<Object, RakiError> {
+    match (self.visit_expr(start)?, self.visit_expr(end)?) {
+      (Object::Double(start), Object::Double(end)) => Ok(Object::Range { start, end, inclusive: operator.r#type == TokenType::DotDotEqual }),
+      (start, end) => Err(self.error(ErrorKind::TypeError, operator.line, &format!("Range bounds must be numbers, not {} and {}.", start.type_name(), end.type_name()))),
+    }
+  }
+
+  fn visit_call_expr(&self, callee: &Expr, arguments: &[Expr], paren: &Token) -> Result<Object, RakiError> {
+    // Methods of built-in values are dispatched directly instead of going
+    // through a bound method object
+    if let Expr::Get { object, name } = callee {
+      let receiver = self.visit_expr(object)?;
+      if !matches!(receiver, Object::Instance(_) | Object::Class(_) | Object::Enum(_) | Object::Module(_) | Object::UserData(_)) {
+        let arguments = arguments.iter().map(|a| self.visit_expr(a)).collect::<Result<Vec<Object>, RakiError>>()?;
+        return self.call_builtin_method(receiver, name, arguments);
+      }
+
+      let method = self.get_property(receiver, name)?;
+      let arguments = arguments.iter().map(|a| self.visit_expr(a)).collect::<Result<Vec<Object>, RakiError>>()?;
+      return self.call(method, arguments, paren);
+    }
+
+    let callee = self.visit_expr(callee)?;
+    let arguments = arguments.iter().map(|a| self.visit_expr(a)).collect::<Result<Vec<Object>, RakiError>>()?;
+    self.call(callee, arguments, paren)
+  }
+
+  fn visit_tuple_expr(&self, elements: &[Expr]) -> Result<Object, RakiError> {
+    let elements = elements.iter().map(|e| self.visit_expr(e)).collect::<Result<Vec<Object>, RakiError>>()?;
+    self.allocate(Object::new_tuple(elements))
+  }
+
+  fn visit_destructure_expr(&self, target: &Expr, value: &Expr, operator: &Token) -> Result<Object, RakiError> {
+    let value = self.visit_expr(value)?;
+    self.destructure(target, value.clone(), false, operator)?;
+    Ok(value)
+  }
+
+  fn call_builtin_method(&self, receiver: Object, name: &Token, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    match &receiver {
+      Object::List(list) => self.call_list_method(list, name, arguments),
+      Object::Map(map) => self.call_map_method(map, name, arguments),
+      // These charge their result before building it
+      Object::String(s) if matches!(name.lexeme.as_str(), "repeat" | "pad_left" | "pad_right") => self.call_string_method(s, name, arguments),
+      Object::String(s) => self.call_string_method(s, name, arguments).and_then(|result| self.allocate(result)),
+      obj => Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined method '{}' on {}.", name.lexeme, obj.type_name()))),
+    }
+  }
+
+  fn call_list_method(&self, list: &Rc<RefCell<Vec<Object>>>, name: &Token, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    match name.lexeme.as_str() {
+      "len" => {
+        self.check_arity(name, 0, &arguments)?;
+        Ok(Object::Double(list.borrow().len() as f64))
+      }
+      "push" => {
+        self.check_arity(name, 1, &arguments)?;
+        self.budget.allocate(mem::size_of::<Object>())?;
+        list.borrow_mut().extend(arguments);
+        Ok(Object::None)
+      }
+      "pop" => {
+        self.check_arity(name, 0, &arguments)?;
+        match list.borrow_mut().pop() {
+          Some(value) => Ok(value),
+          None => Err(self.error(ErrorKind::IndexError, name.line, "Can't pop from an empty list.")),
+        }
+      }
+      "map" => {
+        self.check_arity(name, 1, &arguments)?;
+        let elements = list.borrow().clone();
+        let mapped = elements.into_iter().map(|e| self.call(arguments[0].clone(), vec![e], name)).collect::<Result<Vec<Object>, RakiError>>()?;
+        self.allocate(Object::new_list(mapped))
+      }
+      "filter" => {
+        self.check_arity(name, 1, &arguments)?;
+        let mut filtered = Vec::new();
+        for element in list.borrow().clone() {
+          match self.call(arguments[0].clone(), vec![element.clone()], name)? {
+            Object::Boolean(true) => filtered.push(element),
+            Object::Boolean(false) => {}
+            obj => return Err(self.error(ErrorKind::TypeError, name.line, &format!("filter() callback must return a bool, not {}.", obj.type_name()))),
+          }
+        }
+        self.allocate(Object::new_list(filtered))
+      }
+      "sort" => {
+        if arguments.len() > 1 {
+          return Err(self.error(ErrorKind::TypeError, name.line, &format!("Expected at most 1 arguments but got {}.", arguments.len())));
+        }
+        let mut elements = list.borrow().clone();
+        self.sort(&mut elements, arguments.first(), name)?;
+        *list.borrow_mut() = elements;
+        Ok(Object::None)
+      }
+      _ => Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined method '{}' on list.", name.lexeme))),
+    }
+  }
+
+  // Sorts by the natural order, or by a comparator returning a negative, zero
+  // or positive number
+  fn sort(&self, elements: &mut [Object], comparator: Option<&Object>, name: &Token) -> Result<(), RakiError> {
+    let mut failure = None;
+    elements.sort_by(|a, b| {
+      if failure.is_some() {
+        return Ordering::Equal;
+      }
+
+      let ordering = match comparator {
+        Some(comparator) => match self.call(comparator.clone(), vec![a.clone(), b.clone()], name) {
+          Ok(Object::Double(n)) => n.partial_cmp(&0.0).ok_or_else(|| self.error(ErrorKind::ValueError, name.line, "sort() comparator returned NaN.")),
+          Ok(obj) => Err(self.error(ErrorKind::TypeError, name.line, &format!("sort() comparator must return a number, not {}.", obj.type_name()))),
+          Err(err) => Err(err),
+        },
+        None => a.partial_cmp(b).ok_or_else(|| self.error(ErrorKind::TypeError, name.line, &format!("Can't compare {} with {}.", a.type_name(), b.type_name()))),
+      };
+
+      ordering.unwrap_or_else(|err| {
+        failure = Some(err);
+        Ordering::Equal
+      })
+    });
+
+    match failure {
+      Some(err) => Err(err),
+      None => Ok(()),
+    }
+  }
+
+  // Positions and lengths count characters, not bytes
+  fn call_string_method(&self, s: &str, name: &Token, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    let string = |s: String| Ok(Object::String(s));
+    let strings = |parts: Vec<&str>| Ok(Object::new_list(parts.into_iter().map(|part| Object::String(part.to_string())).collect()));
+    match name.lexeme.as_str() {
+      "len" => {
+        self.check_arity(name, 0, &arguments)?;
+        Ok(Object::Double(s.chars().count() as f64))
+      }
+      "upper" => {
+        self.check_arity(name, 0, &arguments)?;
+        string(s.to_uppercase())
+      }
+      "lower" => {
+        self.check_arity(name, 0, &arguments)?;
+        string(s.to_lowercase())
+      }
+      "trim" => {
+        self.check_arity(name, 0, &arguments)?;
+        string(s.trim().to_string())
+      }
+      // Without a separator the string is split on runs of whitespace
+      "split" => {
+        self.check_arity_between(name, 0, 1, &arguments)?;
+        match arguments.is_empty() {
+          true => strings(s.split_whitespace().collect()),
+          false => match self.string_argument(name, &arguments, 0)? {
+            "" => Err(self.error(ErrorKind::ValueError, name.line, "split() separator can't be empty.")),
+            separator => strings(s.split(separator).collect()),
+          },
+        }
+      }
+      "join" => {
+        self.check_arity(name, 1, &arguments)?;
+        let parts = match &arguments[0] {
+          Object::List(list) => list.borrow().clone(),
+          Object::Tuple(elements) => elements.to_vec(),
+          obj => return Err(self.error(ErrorKind::TypeError, name.line, &format!("Argument 1 of join() must be a list, not {}.", obj.type_name()))),
+        };
+        let parts = parts
+          .iter()
+          .enumerate()
+          .map(|(i, part)| match part {
+            Object::String(part) => Ok(part.as_str()),
+            obj => Err(self.error(ErrorKind::TypeError, name.line, &format!("join() expects a list of strings, found {} at index {}.", obj.type_name(), i))),
+          })
+          .collect::<Result<Vec<&str>, RakiError>>()?;
+        string(parts.join(s))
+      }
+      "replace" => {
+        self.check_arity(name, 2, &arguments)?;
+        match self.string_argument(name, &arguments, 0)? {
+          "" => Err(self.error(ErrorKind::ValueError, name.line, "replace() pattern can't be empty.")),
+          pattern => string(s.replace(pattern, self.string_argument(name, &arguments, 1)?)),
+        }
+      }
+      "starts_with" => {
+        self.check_arity(name, 1, &arguments)?;
+        Ok(Object::Boolean(s.starts_with(self.string_argument(name, &arguments, 0)?)))
+      }
+      "ends_with" => {
+        self.check_arity(name, 1, &arguments)?;
+        Ok(Object::Boolean(s.ends_with(self.string_argument(name, &arguments, 0)?)))
+      }
+      // The character position of the first occurrence, nil when missing
+      "find" => {
+        self.check_arity(name, 1, &arguments)?;
+        match s.find(self.string_argument(name, &arguments, 0)?) {
+          Some(byte) => Ok(Object::Double(s[..byte].chars().count() as f64)),
+          None => Ok(Object::None),
+        }
+      }
+      // Characters from `start` up to, but not including, `end`
+      "substring" => {
+        self.check_arity_between(name, 1, 2, &arguments)?;
+        let len = s.chars().count();
+        let start = self.count_argument(name, &arguments, 0)?;
+        let end = match arguments.len() {
+          2 => self.count_argument(name, &arguments, 1)?,
+          _ => len,
+        };
+        if start > end || end > len {
+          return Err(self.error(ErrorKind::IndexError, name.line, &format!("substring() range {}..{} out of bounds for string of length {}.", start, end, len)));
+        }
+        string(s.chars().skip(start).take(end - start).collect())
+      }
+      "chars" => {
+        self.check_arity(name, 0, &arguments)?;
+        Ok(Object::new_list(s.chars().map(|c| Object::String(c.to_string())).collect()))
+      }
+      "repeat" => {
+        self.check_arity(name, 1, &arguments)?;
+        let count = self.count_argument(name, &arguments, 0)?;
+        let mut result = self.new_string(&name.lexeme, name.line, s.len().checked_mul(count))?;
+        if !s.is_empty() {
+          (0..count).for_each(|_| result.push_str(s));
+        }
+        string(result)
+      }
+      // Pads with `fill`, a space by default, up to `width` characters
+      "pad_left" | "pad_right" => {
+        self.check_arity_between(name, 1, 2, &arguments)?;
+        let width = self.count_argument(name, &arguments, 0)?;
+        let fill = match arguments.len() {
+          2 => self.string_argument(name, &arguments, 1)?,
+          _ => " ",
+        };
+        let mut chars = fill.chars();
+        let (Some(fill), None) = (chars.next(), chars.next()) else {
+          return Err(self.error(ErrorKind::ValueError, name.line, &format!("{}() fill must be a single character, not {}.", name.lexeme, Object::String(fill.to_string()).repr())));
+        };
+        let padding = width.saturating_sub(s.chars().count());
+        let mut result = self.new_string(&name.lexeme, name.line, fill.len_utf8().checked_mul(padding).and_then(|len| len.checked_add(s.len())))?;
+        if name.lexeme == "pad_right" {
+          result.push_str(s);
+        }
+        result.extend(std::iter::repeat_n(fill, padding));
+        if name.lexeme == "pad_left" {
+          result.push_str(s);
+        }
+        string(result)
+      }
+      "to_number" => {
+        self.check_arity(name, 0, &arguments)?;
+        match s.trim().parse::<f64>() {
+          Ok(n) => Ok(Object::Double(n)),
+          Err(_) => Err(self.error(ErrorKind::ValueError, name.line, &format!("Can't convert {} to a number.", Object::String(s.to_string()).repr()))),
+        }
+      }
+      _ => Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined method '{}' on string.", name.lexeme))),
+    }
+  }
+
+  fn call_map_method(&self, map: &Rc<RefCell<RakiMap>>, name: &Token, arguments: Vec<Object>) -> Result<Object, RakiError> {
+    match name.lexeme.as_str() {
+      "keys" => {
+        self.check_arity(name, 0, &arguments)?;
+        self.allocate(Object::new_list(map.borrow().keys().cloned().collect()))
+      }
+      "values" => {
+        self.check_arity(name, 0, &arguments)?;
+        self.allocate(Object::new_list(map.borrow().values().cloned().collect()))
+      }
+      "entries" => {
+        self.check_arity(name, 0, &arguments)?;
+        let entries = map.borrow().iter().map(|(k, v)| Object::new_tuple(vec![k.clone(), v.clone()])).collect();
+        self.allocate(Object::new_list(entries))
+      }
+      "has" => {
+        self.check_arity(name, 1, &arguments)?;
+        self.check_key(&arguments[0], name.line)?;
+        Ok(Object::Boolean(map.borrow().contains_key(&arguments[0])))
+      }
+      "remove" => {
+        self.check_arity(name, 1, &arguments)?;
+        self.check_key(&arguments[0], name.line)?;
+        Ok(map.borrow_mut().remove(&arguments[0]).unwrap_or(Object::None))
+      }
+      _ => Err(self.error(ErrorKind::NameError, name.line, &format!("Undefined method '{}' on map.", name.lexeme))),
+    }
+  }
+}
+
+impl Visitor<Result<Object, RakiError>> for Interpreter {
+  fn visit_expr(&self, e: &Expr) -> Result<Object, RakiError> {
+    self.budget.step()?;
+    self.budget.check_stack()?;
+    match e {
+      Expr::Binary { left, right, operator } => return self.visit_binary_expr(left, right, operator),
+      Expr::Grouping { expr } => return self.visit_expr(expr),
+      Expr::Literal { value } => return self.visit_literal_expr(value),
+      Expr::Unary { right, operator } => return self.visit_unary_expr(right, operator),
+      Expr::Ternary { condition, left, right, operator } => return self.visit_ternary_expr(condition, left, right, operator),
+      Expr::Variable { name } => return self.environment.borrow().borrow().get(name),
+      Expr::Assign { name, value } => return self.visit_assign_expr(name, value),
+      Expr::List { elements, .. } => return self.visit_list_expr(elements),
+      Expr::Map { entries, brace } => return self.visit_map_expr(entries, brace),
+      Expr::Index { object, index, bracket } => return self.visit_index_expr(object, index, bracket),
+      Expr::IndexSet { object, index, value, bracket } => return self.visit_index_set_expr(object, index, value, bracket),
+      Expr::Get { object, name } => return self.visit_get_expr(object, name),
+      Expr::Call { callee, arguments, paren } => return self.visit_call_expr(callee, arguments, paren),
+      Expr::Tuple { elements, .. } => return self.visit_tuple_expr(elements),
+      Expr::Destructure { target, value, operator } => return self.visit_destructure_expr(target, value, operator),
+      Expr::Range { start, end, operator } => return self.visit_range_expr(start, end, operator),
+      Expr::Set { object, name, value } => return self.visit_set_expr(object, name, value),
+      Expr::This { keyword } => return self.environment.borrow().borrow().get(keyword),
+      Expr::Match { value, arms, keyword } => return self.visit_match_expr(value, arms, keyword),
+      Expr::Super { keyword, method } => {
+        let superclass = self.environment.borrow().borrow().get(keyword)?;
+        let instance = self.environment.borrow().borrow().lookup("this").unwrap_or(Object::None);
+        let Object::Class(superclass) = superclass else {
+          return Err(self.error(ErrorKind::TypeError, keyword.line, "Can't use 'super' outside of a subclass."));
+        };
+        match superclass.find_method(&method.lexeme) {
+          Some(found) => return Ok(Object::Function(Rc::new(found.bind(instance)))),
+          None => return Err(self.error(ErrorKind::NameError, method.line, &format!("Undefined method '{}' on superclass {}.", method.lexeme, superclass.name))),
+        }
+      }
+      Expr::Lambda { params, body, keyword, .. } => {
+        let function = Function::new(&format!("lambda@{}", keyword.line), params, body, self.environment.borrow().clone());
+        return Ok(Object::Function(Rc::new(function)));
+      }
+    }
+  }
+}
+
+impl StmtVisitor<Result<Flow, RakiError>> for Interpreter {
+  fn visit_stmt(&self, s: &Stmt) -> Result<Flow, RakiError> {
+    self.budget.step()?;
+    self.budget.check_stack()?;
+    match s {
+      Stmt::Expression { expr } => {
+        self.visit_expr(expr)?;
+      }
+      Stmt::Print { values, sep, end, keyword } => self.execute_print(values, sep, end, keyword)?,
+      Stmt::Var { name, initializer, .. } => {
+        let value = match initializer {
+          Some(expr) => self.visit_expr(expr)?,
+          None => Object::None,
+        };
+        self.environment.borrow().borrow_mut().define(&name.lexeme, value);
+      }
+      Stmt::Const { name, initializer, .. } => {
+        let value = self.visit_expr(initializer)?;
+        self.environment.borrow().borrow_mut().define_constant(&name.lexeme, value);
+      }
+      Stmt::Block { statements } => return self.execute_block(statements, self.new_scope()),
+      Stmt::Destructure { target, initializer, keyword } => {
+        let value = self.visit_expr(initializer)?;
+        self.destructure(target, value, true, keyword)?;
+      }
+      Stmt::Function { name, params, body, .. } => {
+        let function = Function::new(&name.lexeme, params, body, self.environment.borrow().clone());
+        self.environment.borrow().borrow_mut().define(&name.lexeme, Object::Function(Rc::new(function)));
+      }
+      Stmt::Return { value, .. } => {
+        let value = match value {
+          Some(expr) => self.visit_expr(expr)?,
+          None => Object::None,
+        };
+        return Ok(Flow::Return(value));
+      }
+      Stmt::If { condition, then_branch, else_branch, keyword } => {
+        if self.condition(condition, keyword)? {
+          return self.visit_stmt(then_branch);
+        }
+        if let Some(else_branch) = else_branch {
+          return self.visit_stmt(else_branch);
+        }
+      }
+      Stmt::While { condition, body, keyword } => {
+        while self.condition(condition, keyword)? {
+          match self.visit_stmt(body)? {
+            Flow::Break => break,
+            Flow::Return(value) => return Ok(Flow::Return(value)),
+            Flow::Normal | Flow::Continue => {}
+          }
+        }
+      }
+      Stmt::ForIn { target, iterable, body, keyword } => {
+        let iterable = self.visit_expr(iterable)?;
+        let mut iterator = self.iterator(iterable, keyword)?;
+        while let Some(value) = self.next_value(&mut iterator, keyword)? {
+          // Every iteration gets a fresh scope so closures capture its value
+          let flow = self.with_environment(self.new_scope(), || {
+            self.destructure(target, value, true, keyword)?;
+            self.visit_stmt(body)
+          })?;
+
+          match flow {
+            Flow::Break => break,
+            Flow::Return(value) => return Ok(Flow::Return(value)),
+            Flow::Normal | Flow::Continue => {}
+          }
+        }
+      }
+      Stmt::Throw { value, keyword } => {
+        let value = self.visit_expr(value)?;
+        return Err(self.throw(value, keyword));
+      }
+      Stmt::Try { body, catch, finally } => return self.execute_try(body, catch, finally),
+      Stmt::Break => return Ok(Flow::Break),
+      Stmt::Continue => return Ok(Flow::Continue),
+      Stmt::Class { .. } => self.define_class(s)?,
+      Stmt::Import { path, alias, names, keyword } => self.import(path, alias, names, keyword)?,
+      Stmt::Export { declaration, .. } => return self.visit_stmt(declaration),
+      Stmt::Trait { name, required, defaults } => {
+        let required = required.iter().map(|(method, params)| (method.lexeme.clone(), params.len())).collect();
+        let t = Trait { name: name.lexeme.clone(), required, defaults: self.methods(defaults, self.environment.borrow().clone()) };
+        self.environment.borrow().borrow_mut().define(&name.lexeme, Object::Trait(Rc::new(t)));
+      }
+      Stmt::Enum { name, variants } => {
+        let variants = variants
+          .iter()
+          .map(|(variant, fields)| {
+            let fields = fields.iter().map(|field| field.lexeme.clone()).collect();
+            Rc::new(Variant { enum_name: name.lexeme.clone(), name: variant.lexeme.clone(), fields })
+          })
+          .collect();
+
+        let enumeration = Enum { name: name.lexeme.clone(), variants };
+        self.environment.borrow().borrow_mut().define(&name.lexeme, Object::Enum(Rc::new(enumeration)));
+      }
+    }
+
+    Ok(Flow::Normal)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::interpreter::FixedClock;
+  use crate::{lexer::Scanner, parser::Parser};
+
+  // Runs `source` and returns the value bound to the global `result`
+  fn eval(source: &str) -> Result<Object, RakiError> {
+    let mut scanner = Scanner::new(source.to_string());
+    let mut parser = Parser::new(scanner.scan_tokens()?);
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let interpreter = Interpreter::new();
+    interpreter.interpret(&statements)?;
+    interpreter.globals.borrow().get(&Token { r#type: TokenType::Identifier, lexeme: "result".to_string(), literal: LiteralType::None, line: 0 })
+  }
+
+  fn runtime_message(err: RakiError) -> String {
+    match err {
+      RakiError::Runtime { message, .. } => message,
+      err => panic!("expected a runtime error, got {:?}", err),
+    }
+  }
+
+  #[test]
+  fn evaluates_ternaries() {
+    let result = eval("var result = [1 < 2 ? \"yes\" : \"no\", false ? 1 : true ? 2 : 3];");
+    assert_eq!(result.unwrap().to_string(), "[\"yes\", 2]");
+
+    let err = eval("var x = 1;\nvar y = x\n  ? 1 : 2;").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 3, message: "Ternary condition must be a bool, not number.".to_string() });
+  }
+
+  #[test]
+  fn evaluates_map_literals() {
+    let result = eval("var m = {\"a\": 1, \"b\": 2}; var result = m[\"b\"];");
+    assert_eq!(result, Ok(Object::Double(2.0)));
+  }
+
+  #[test]
+  fn disambiguates_maps_from_blocks() {
+    let result = eval("var result = 0; { result = 1; } {\"a\": 1};");
+    assert_eq!(result, Ok(Object::Double(1.0)));
+  }
+
+  #[test]
+  fn assigns_map_entries() {
+    let result = eval("var m = {}; m[1] = \"one\"; m[true] = nil; m[1] = \"uno\"; var result = m;");
+    assert_eq!(result.unwrap().to_string(), "{1: \"uno\", true: nil}");
+  }
+
+  #[test]
+  fn iterates_maps_in_insertion_order() {
+    let result = eval("var m = {\"z\": 1, \"a\": 2}; m[\"m\"] = 3; var result = [m.keys(), m.values(), m.entries()];");
+    assert_eq!(result.unwrap().to_string(), "[[\"z\", \"a\", \"m\"], [1, 2, 3], [(\"z\", 1), (\"a\", 2), (\"m\", 3)]]");
+  }
+
+  #[test]
+  fn checks_and_removes_map_keys() {
+    let result = eval("var m = {\"a\": 1, \"b\": 2}; var removed = m.remove(\"a\"); var result = [removed, m.has(\"a\"), m.has(\"b\"), m.remove(\"a\")];");
+    assert_eq!(result.unwrap().to_string(), "[1, false, true, nil]");
+  }
+
+  #[test]
+  fn rejects_unhashable_keys() {
+    let err = eval("var m = {}; m[[1, 2]] = 3;").unwrap_err();
+    assert_eq!(runtime_message(err), "Unhashable map key of type list.");
+
+    let err = eval("var m = {{}: 1};").unwrap_err();
+    assert_eq!(runtime_message(err), "Unhashable map key of type map.");
+  }
+
+  #[test]
+  fn reports_missing_keys() {
+    let err = eval("var m = {\"a\": 1};\nm[\"b\"];").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::KeyError, line: 2, message: "Key 'b' not found in map.".to_string() });
+  }
+
+  #[test]
+  fn indexes_lists() {
+    let result = eval("var l = [1, 2, 3]; l[0] = l[2]; var result = l;");
+    assert_eq!(result.unwrap().to_string(), "[3, 2, 3]");
+
+    let err = eval("var l = [1]; l[1];").unwrap_err();
+    assert_eq!(runtime_message(err), "Index 1 out of bounds for list of length 1.");
+  }
+
+  #[test]
+  fn swaps_with_tuple_assignment() {
+    let result = eval("var a = 1; var b = 2; (a, b) = (b, a); var result = (a, b);");
+    assert_eq!(result.unwrap().to_string(), "(2, 1)");
+  }
+
+  #[test]
+  fn destructures_function_results() {
+    let result = eval("fun point() { return (3, (4, 5)); } var (x, (y, z)) = point(); var result = [x, y, z];");
+    assert_eq!(result.unwrap().to_string(), "[3, 4, 5]");
+  }
+
+  #[test]
+  fn destructures_into_indices() {
+    let result = eval("var l = [0, 0]; var m = {}; (l[1], m[\"k\"]) = [1, 2]; var result = (l, m);");
+    assert_eq!(result.unwrap().to_string(), "([0, 1], {\"k\": 2})");
+  }
+
+  #[test]
+  fn reports_destructuring_arity_mismatch() {
+    let err = eval("var (a, b) = (1, 2, 3);").unwrap_err();
+    assert_eq!(runtime_message(err), "Cannot destructure 3 values into 2 targets.");
+
+    let err = eval("var (a, b) = 1;").unwrap_err();
+    assert_eq!(runtime_message(err), "Cannot destructure a value of type number.");
+  }
+
+  #[test]
+  fn reports_invalid_destructuring_targets_on_their_line() {
+    // The parser rejects these, so the target is built by hand
+    let equals = Token { r#type: TokenType::Equal, lexeme: "=".to_string(), literal: LiteralType::None, line: 3 };
+    let target = Expr::Tuple { elements: vec![Expr::Literal { value: LiteralType::F64(1.0) }], paren: equals.clone() };
+    let err = Interpreter::new().destructure(&target, Object::new_tuple(vec![Object::Double(2.0)]), false, &equals).unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 3, message: "Invalid destructuring target.".to_string() });
+  }
+
+  #[test]
+  fn tuples_are_immutable_and_hashable() {
+    let err = eval("var t = (1, 2); t[0] = 3;").unwrap_err();
+    assert_eq!(runtime_message(err), "Tuples are immutable.");
+
+    let result = eval("var m = {}; m[(1, \"a\")] = 2; var result = [m[(1, \"a\")], (1,), ()];");
+    assert_eq!(result.unwrap().to_string(), "[2, (1,), ()]");
+  }
+
+  #[test]
+  fn calls_closures() {
+    let result = eval("fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; } var c = counter(); c(); var result = c();");
+    assert_eq!(result, Ok(Object::Double(2.0)));
+
+    let err = eval("fun f(a) {} f();").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 1 arguments but got 0.");
+  }
+
+  #[test]
+  fn iterates_lists_tuples_and_strings() {
+    let result = eval("var out = []; for (x in [1, 2]) { out.push(x); } for (x in (3,)) out.push(x); for c in \"hé\" { out.push(c); } var result = out;");
+    assert_eq!(result.unwrap().to_string(), "[1, 2, 3, \"h\", \"é\"]");
+  }
+
+  #[test]
+  fn iterates_ranges() {
+    let result = eval("var out = []; for (i in 0..3) out.push(i); for (i in 5..=6) out.push(i); for (i in 2..2) out.push(i); var result = out;");
+    assert_eq!(result.unwrap().to_string(), "[0, 1, 2, 5, 6]");
+  }
+
+  #[test]
+  fn iterates_maps_with_destructuring() {
+    let result = eval("var m = {\"a\": 1, \"b\": 2}; var out = []; for (k in m) out.push(k); for (k, v) in m.entries() { out.push(v); } for ((k, v) in m.entries()) out.push(k + k); var result = out;");
+    assert_eq!(result.unwrap().to_string(), "[\"a\", \"b\", 1, 2, \"aa\", \"bb\"]");
+  }
+
+  #[test]
+  fn iterates_user_classes() {
+    let source = "
+      class Countdown {
+        init(from) { this.from = from; }
+        iter() { return CountdownIter(this.from); }
+      }
+      class CountdownIter {
+        init(n) { this.n = n; }
+        next() {
+          if (this.n == 0) return nil;
+          this.n = this.n - 1;
+          return this.n + 1;
+        }
+      }
+      var result = [];
+      for (i in Countdown(3)) result.push(i);
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[3, 2, 1]");
+
+    let err = eval("class Point {} for (p in Point()) {}").unwrap_err();
+    assert_eq!(runtime_message(err), "Instances of Point are not iterable, define an iter() method.");
+  }
+
+  #[test]
+  fn breaks_and_continues_loops() {
+    let result = eval("var out = []; for (i in 0..10) { if (i == 1) continue; if (i == 4) break; out.push(i); } var i = 0; while (true) { i = i + 1; if (i == 3) break; } var result = (out, i);");
+    assert_eq!(result.unwrap().to_string(), "([0, 2, 3], 3)");
+  }
+
+  #[test]
+  fn returns_from_inside_loops() {
+    let result = eval("fun find(xs, x) { for (y in xs) { if (y == x) return true; } return false; } var result = [find([1, 2], 2), find([1, 2], 3)];");
+    assert_eq!(result.unwrap().to_string(), "[true, false]");
+  }
+
+  #[test]
+  fn detects_list_mutation_during_iteration() {
+    let err = eval("var l = [1, 2]; for (x in l) { l.push(x); }").unwrap_err();
+    assert_eq!(runtime_message(err), "List changed size during iteration.");
+
+    let result = eval("var l = [1, 2]; for (x in l) { l[0] = x; } var result = l;");
+    assert_eq!(result.unwrap().to_string(), "[2, 2]");
+  }
+
+  #[test]
+  fn rejects_non_iterables() {
+    let err = eval("for (x in 1) {}").unwrap_err();
+    assert_eq!(runtime_message(err), "Value of type number is not iterable.");
+  }
+
+  #[test]
+  fn evaluates_anonymous_functions() {
+    let result = eval("var add = fun (a, b) { return a + b; }; var result = add(1, 2);");
+    assert_eq!(result, Ok(Object::Double(3.0)));
+
+    let result = eval("fun (x) { return x; };\nvar result = fun () {};");
+    assert_eq!(result.unwrap().to_string(), "<fn lambda@2>");
+  }
+
+  #[test]
+  fn evaluates_arrow_functions() {
+    let result = eval("var offset = 10; var f = (a, b) => a * b + offset; var g = () => { return {\"k\": 1}; }; var h = (x) => {\"k\": x}; var result = [f(2, 3), g(), h(2)];");
+    assert_eq!(result.unwrap().to_string(), "[16, {\"k\": 1}, {\"k\": 2}]");
+  }
+
+  #[test]
+  fn passes_lambdas_as_callbacks() {
+    let result = eval("var l = [3, 1, 2]; var doubled = l.map((x) => x * 2); var odd = l.filter((x) => x != 2); l.sort((a, b) => b - a); var result = (doubled, odd, l);");
+    assert_eq!(result.unwrap().to_string(), "([6, 2, 4], [3, 1], [3, 2, 1])");
+
+    let result = eval("var l = [\"b\", \"c\", \"a\"]; l.sort(); var result = l;");
+    assert_eq!(result.unwrap().to_string(), "[\"a\", \"b\", \"c\"]");
+  }
+
+  #[test]
+  fn reports_sort_failures() {
+    let err = eval("var l = [1, \"a\"]; l.sort();").unwrap_err();
+    assert_eq!(runtime_message(err), "Can't compare string with number.");
+  }
+
+  #[test]
+  fn catches_builtin_errors() {
+    let result = eval("var result;\ntry {\n  1 + \"a\";\n} catch (e) {\n  result = [e.kind, e.message, e.line];\n}");
+    assert_eq!(result.unwrap().to_string(), "[\"TypeError\", \"Unsupported operand types for +: number and string.\", 3]");
+
+    let result = eval("var result = []; try { undefined; } catch (e) { result.push(e.kind); } try { [][0]; } catch (e) { result.push(e.kind); }");
+    assert_eq!(result.unwrap().to_string(), "[\"NameError\", \"IndexError\"]");
+  }
+
+  #[test]
+  fn catches_thrown_values() {
+    let result = eval("fun fail() { throw (\"custom\", 1); } var result; try { fail(); } catch (e) { result = e; }");
+    assert_eq!(result.unwrap().to_string(), "(\"custom\", 1)");
+
+    let result = eval("var result;\ntry {\n  throw Error(\"boom\");\n} catch (e) {\n  result = [e.kind, e.message, e.line];\n}");
+    assert_eq!(result.unwrap().to_string(), "[\"Error\", \"boom\", 3]");
+  }
+
+  #[test]
+  fn runs_finally_blocks() {
+    let source = "
+      var log = [];
+      fun returns() { try { return 1; } finally { log.push(\"return\"); } }
+      returns();
+      for (i in 0..3) { try { break; } finally { log.push(\"break\"); } }
+      try { try { throw 1; } finally { log.push(\"throw\"); } } catch (e) { log.push(e); }
+      try { log.push(\"normal\"); } finally { log.push(\"finally\"); }
+      var result = log;
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[\"return\", \"break\", \"throw\", 1, \"normal\", \"finally\"]");
+  }
+
+  #[test]
+  fn propagates_uncaught_exceptions() {
+    let err = eval("var x = 1;\nthrow x + 1;").unwrap_err();
+    assert_eq!(err, RakiError::Thrown { line: 2, value: Object::Double(2.0) });
+
+    let err = eval("try { throw 1; } catch (e) { throw e + 1; }").unwrap_err();
+    assert_eq!(err, RakiError::Thrown { line: 1, value: Object::Double(2.0) });
+  }
+
+  #[test]
+  fn matches_literal_range_and_binding_patterns() {
+    let source = "
+      fun describe(n) {
+        return match n {
+          0 => \"zero\",
+          -3 => \"minus three\",
+          1..=9 => \"digit\",
+          \"ten\" => \"word\",
+          other if other > 100 => \"big\",
+          _ => \"other\",
+        };
+      }
+      var result = [describe(0), describe(-3), describe(9), describe(\"ten\"), describe(101), describe(50)];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[\"zero\", \"minus three\", \"digit\", \"word\", \"big\", \"other\"]");
+  }
+
+  #[test]
+  fn matches_list_map_and_tuple_patterns() {
+    let result = eval("var result = match [1, 2, 3] { [] => nil, [first, ...rest] => (first, rest) };");
+    assert_eq!(result.unwrap().to_string(), "(1, [2, 3])");
+
+    let result = eval("var result = match {\"type\": \"user\", \"id\": 7, \"extra\": true} { {\"type\": \"admin\"} => 0, {\"type\": \"user\", \"id\": id} => id };");
+    assert_eq!(result, Ok(Object::Double(7.0)));
+
+    let result = eval("var result = match (1, (2, 3)) { (a, (b, c)) => a + b + c };");
+    assert_eq!(result, Ok(Object::Double(6.0)));
+  }
+
+  #[test]
+  fn matches_class_instance_patterns() {
+    let source = "
+      class Point { init(x, y) { this.x = x; this.y = y; } }
+      class Other {}
+      fun quadrant(p) {
+        return match p {
+          Point(0, 0) => \"origin\",
+          Point(x, y) if x > 0 => \"right\",
+          Point(x) => \"left\",
+          _ => \"not a point\",
+        };
+      }
+      var result = [quadrant(Point(0, 0)), quadrant(Point(2, -1)), quadrant(Point(-2, 1)), quadrant(Other())];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[\"origin\", \"right\", \"left\", \"not a point\"]");
+  }
+
+  #[test]
+  fn reports_unmatched_values() {
+    let err = eval("var x = match \"abc\" {\n  1 => 1,\n};").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::MatchError, line: 1, message: "No match arm matched value \"abc\".".to_string() });
+
+    let err = eval("var x = match 1 { n if n => n };").unwrap_err();
+    assert_eq!(runtime_message(err), "Match guard must be a bool, not number.");
+  }
+
+  #[test]
+  fn builds_and_prints_enum_values() {
+    let source = "
+      enum Shape { Circle(r), Rect(w, h), Empty }
+      var c = Shape.Circle(2);
+      var result = [c, Shape.Rect(1, \"a\"), Shape.Empty, c.r, Shape.Circle(2) == c, Shape.Circle(3) == c, Shape.Empty == Shape.Empty];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[Shape.Circle(2), Shape.Rect(1, \"a\"), Shape.Empty, 2, true, false, true]");
+
+    let err = eval("enum Shape { Circle(r) }\nShape.Square;").unwrap_err();
+    assert_eq!(runtime_message(err), "Undefined variant 'Square' on enum Shape.");
+    let err = eval("enum Shape { Circle(r) }\nShape.Circle(1, 2);").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 1 arguments but got 2.");
+  }
+
+  #[test]
+  fn matches_enum_variants() {
+    let source = "
+      enum Shape { Circle(r), Rect(w, h), Empty }
+      fun area(shape) {
+        return match shape {
+          Shape.Circle(r) => 3 * r * r,
+          Shape.Rect(w, h) if w == h => \"square\",
+          Shape.Rect(w, h) => w * h,
+          Shape.Empty => 0,
+        };
+      }
+      var result = [area(Shape.Circle(1)), area(Shape.Rect(2, 2)), area(Shape.Rect(2, 3)), area(Shape.Empty)];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[3, \"square\", 6, 0]");
+
+    let result = eval("enum State { Idle, Running } var m = {State.Idle: \"idle\"}; var result = m[State.Idle];");
+    assert_eq!(result, Ok(Object::String("idle".to_string())));
+  }
+
+  #[test]
+  fn rejects_assignments_to_repl_constants() {
+    // Each REPL line is parsed and resolved on its own
+    let interpreter = Interpreter::new();
+    let run = |source: &str| {
+      let mut scanner = Scanner::new(source.to_string());
+      interpreter.interpret(&Parser::new(scanner.scan_tokens().unwrap()).parse_program())
+    };
+
+    run("const limit = 10;").unwrap();
+    let err = run("limit = 11;").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 1, message: "Cannot assign to constant 'limit'.".to_string() });
+    let err = run("var n; (n, limit) = (1, 2);").unwrap_err();
+    assert_eq!(runtime_message(err), "Cannot assign to constant 'limit'.");
+
+    run("var limit = 12; limit = 13;").unwrap();
+  }
+
+  #[test]
+  fn inherits_methods_and_calls_super() {
+    let source = "
+      class Animal { init(name) { this.name = name; } speak() { return this.name + \" makes a sound\"; } }
+      class Dog < Animal { speak() { return super.speak() + \" and barks\"; } }
+      var result = Dog(\"Rex\").speak();
+    ";
+    assert_eq!(eval(source), Ok(Object::String("Rex makes a sound and barks".to_string())));
+
+    let err = eval("var A = 1; class B < A {}").unwrap_err();
+    assert_eq!(runtime_message(err), "Superclass must be a class, not number.");
+  }
+
+  #[test]
+  fn checks_trait_conformance() {
+    let source = "
+      trait Comparable { compare(other); less(other) { return this.compare(other) < 0; } }
+      class Money impl Comparable { init(cents) { this.cents = cents; } compare(other) { return this.cents - other.cents; } }
+      var result = Money(1).less(Money(2));
+    ";
+    assert_eq!(eval(source), Ok(Object::Boolean(true)));
+
+    let err = eval("trait Comparable { compare(other); } class Money impl Comparable {}").unwrap_err();
+    assert_eq!(runtime_message(err), "Class Money is missing method 'compare' required by trait Comparable.");
+
+    let err = eval("trait Comparable { compare(other); } class Money impl Comparable { compare() {} }").unwrap_err();
+    assert_eq!(runtime_message(err), "Method 'compare' of class Money takes 0 parameters but trait Comparable requires 1.");
+
+    // Required methods may be inherited
+    let result = eval("trait Named { name(); } class A { name() { return \"a\"; } } class B < A impl Named {} var result = B().name();");
+    assert_eq!(result, Ok(Object::String("a".to_string())));
+  }
+
+  #[test]
+  fn tests_instances_with_is() {
+    let source = "
+      trait Shape { area(); }
+      class Base {}
+      class Square < Base impl Shape { area() { return 4; } }
+      var s = Square();
+      var result = [s is Square, s is Base, s is Shape, Base() is Square, Base() is Shape, 1 is Base];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[true, true, true, false, false, false]");
+
+    let err = eval("class A {} A() is 1;").unwrap_err();
+    assert_eq!(runtime_message(err), "Right operand of 'is' must be a class or trait, not number.");
+  }
+
+  #[test]
+  fn overloads_arithmetic_and_comparison() {
+    let source = "
+      class Vector {
+        init(x, y) { this.x = x; this.y = y; }
+        __add__(other) { return Vector(this.x + other.x, this.y + other.y); }
+        __mul__(k) { return Vector(this.x * k, this.y * k); }
+        __rmul__(k) { return this * k; }
+        __eq__(other) { return other is Vector ? this.x == other.x ? this.y == other.y : false : false; }
+        __lt__(other) { return this.x < other.x; }
+      }
+      var v = Vector(1, 2) + Vector(3, 4);
+      var w = 2 * v;
+      var result = [v.x, v.y, w.y, v == Vector(4, 6), v != Vector(4, 6), v == 1, Vector(1, 0) < v, v > Vector(1, 0)];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[4, 6, 12, true, false, false, true, true]");
+
+    // Without `__eq__` instances compare by identity
+    let result = eval("class A {} var a = A(); var result = [a == a, a == A()];");
+    assert_eq!(result.unwrap().to_string(), "[true, false]");
+  }
+
+  #[test]
+  fn reports_missing_operator_methods() {
+    let err = eval("class Money {} Money() + 1;").unwrap_err();
+    assert_eq!(runtime_message(err), "Instances of Money don't support '+', define a __add__() method.");
+
+    let err = eval("class Money {} 1 - Money();").unwrap_err();
+    assert_eq!(runtime_message(err), "Instances of Money don't support '-' on the right, define a __rsub__() method.");
+
+    let err = eval("class Money { __lt__(other) { return 1; } } Money() < Money();").unwrap_err();
+    assert_eq!(runtime_message(err), "Overloaded '<' must return a bool, not number.");
+  }
+
+  #[test]
+  fn overloads_indexing_and_calls() {
+    let source = "
+      class Grid { __index__(i) { return i * 10; } __call__(a, b) { return a + b; } }
+      var g = Grid();
+      var result = [g[2], g(1, 2)];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[20, 3]");
+
+    let err = eval("class A {} A()[0];").unwrap_err();
+    assert_eq!(runtime_message(err), "Instances of A can't be indexed, define an __index__() method.");
+    let err = eval("class A {} A()();").unwrap_err();
+    assert_eq!(runtime_message(err), "Instances of A are not callable, define a __call__() method.");
+  }
+
+  #[test]
+  fn stringifies_with_str_methods() {
+    let interpreter = Interpreter::new();
+    let mut scanner = Scanner::new("class Money { __str__() { return \"$5\"; } } class Bad { __str__() { return 5; } }".to_string());
+    interpreter.interpret(&Parser::new(scanner.scan_tokens().unwrap()).parse_program()).unwrap();
+    let token = Token { r#type: TokenType::Print, lexeme: "print".to_string(), literal: LiteralType::None, line: 1 };
+    let global = |name: &str| interpreter.globals.borrow().lookup(name).unwrap();
+    let money = interpreter.call(global("Money"), Vec::new(), &token).unwrap();
+
+    assert_eq!(interpreter.stringify(&money, &token), Ok("$5".to_string()));
+    let nested = Object::new_list(vec![money.clone(), Object::new_tuple(vec![money]), Object::String("a".to_string())]);
+    assert_eq!(interpreter.stringify(&nested, &token), Ok("[$5, ($5,), \"a\"]".to_string()));
+
+    let bad = interpreter.call(global("Bad"), Vec::new(), &token).unwrap();
+    assert_eq!(runtime_message(interpreter.stringify(&bad, &token).unwrap_err()), "__str__() must return a string, not number.");
+  }
+
+  #[test]
+  fn prints_and_compares_cyclic_containers() {
+    let source = "
+      var a = [1]; a.push(a);
+      var b = [1]; b.push(b);
+      var m = {}; m[\"self\"] = m; m[\"list\"] = a;
+      var result = [repr(a), repr(m), a == b, a == [1, [1]]];";
+    assert_eq!(eval(source).unwrap().to_string(), "[\"[1, [...]]\", \"{\\\"self\\\": {...}, \\\"list\\\": [1, [...]]}\", true, false]");
+  }
+
+  #[test]
+  fn calls_getters_and_setters() {
+    let source = "
+      class Rect {
+        init(w, h) { this.w = w; this.h = h; }
+        area { return this.w * this.h; }
+        set side(value) { this.w = value; this.h = value; }
+      }
+      var r = Rect(2, 3);
+      var before = r.area;
+      r.side = 4;
+      var result = [before, r.area, r.w];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[6, 16, 4]");
+
+    // Fields shadow getters, which shadow methods
+    let source = "
+      class A { name { return \"getter\"; } size() { return \"method\"; } }
+      class B < A { size { return \"getter\"; } }
+      var a = A();
+      var b = B();
+      a.name = \"field\";
+      var result = [a.name, b.name, b.size];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[\"field\", \"getter\", \"getter\"]");
+  }
+
+  #[test]
+  fn accesses_class_members() {
+    let source = "
+      class Math {
+        class PI = 3;
+        class count;
+        class square(n) { this.count = this.count == nil ? 1 : this.count + 1; return n * n; }
+      }
+      class Geometry < Math { class circle(r) { return this.PI * this.square(r); } }
+      var area = Geometry.circle(2);
+      Math.square(3);
+      var result = [Math.square(3), area, Math.count, Geometry.count];
+    ";
+    assert_eq!(eval(source).unwrap().to_string(), "[9, 12, 3, 3]");
+
+    let err = eval("class A { f() {} } A.f();").unwrap_err();
+    assert_eq!(runtime_message(err), "Undefined property 'f' on class.");
+    let err = eval("1.5.x = 1;").unwrap_err();
+    assert_eq!(runtime_message(err), "Only instances and classes have fields.");
+  }
+
+  // Writes `files` to a fresh directory, runs its main.raki and returns the
+  // global `result`
+  fn eval_files(test: &str, files: &[(&str, &str)]) -> Result<Object, RakiError> {
+    eval_files_with(test, files, |_, _| {})
+  }
+
+  fn eval_files_with(test: &str, files: &[(&str, &str)], setup: impl FnOnce(&Interpreter, &Path)) -> Result<Object, RakiError> {
+    let directory = env::temp_dir().join(format!("raki_{}_{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    for (path, source) in files {
+      let file = directory.join(path);
+      fs::create_dir_all(file.parent().unwrap()).unwrap();
+      fs::write(file, source).unwrap();
+    }
+
+    let interpreter = Interpreter::new();
+    setup(&interpreter, &directory);
+    let main = directory.join("main.raki");
+    interpreter.set_script(&main);
+    let statements = analyze(fs::read_to_string(&main).unwrap()).map_err(|errors| errors[0].clone())?;
+    let result = interpreter.interpret(&statements).and_then(|_| interpreter.globals.borrow().get(&Token { r#type: TokenType::Identifier, lexeme: "result".to_string(), literal: LiteralType::None, line: 0 }));
+    fs::remove_dir_all(&directory).unwrap();
+    result
+  }
+
+  #[test]
+  fn imports_modules() {
+    let files = [
+      ("main.raki", "import \"utils/strings.raki\" as s;\nfrom \"math.raki\" import square, PI;\nvar result = [s.shout(\"hi\"), s.pi(), square(3), PI, s];"),
+      // `suffix.raki` is found next to the importing file, `math.raki` in the root
+      ("utils/strings.raki", "from \"suffix.raki\" import mark;\nimport \"math.raki\" as m;\nexport fun shout(x) { return x + mark; }\nexport fun pi() { return m.PI; }"),
+      ("utils/suffix.raki", "export const mark = \"!\";"),
+      ("math.raki", "export const PI = 3;\nexport fun square(x) { return x * x; }"),
+    ];
+    assert_eq!(eval_files("imports", &files).unwrap().to_string(), "[\"hi!\", 3, 9, 3, <module strings>]");
+  }
+
+  #[test]
+  fn keeps_module_globals_separate() {
+    let files = [
+      ("main.raki", "var x = \"main\";\nimport \"a.raki\" as a;\nimport \"a.raki\" as again;\nvar result = [x, a.get(), a == again, a.runs];"),
+      ("a.raki", "var x = \"a\";\nexport var runs = [];\nruns.push(1);\nexport fun get() { return x; }"),
+    ];
+    assert_eq!(eval_files("globals", &files).unwrap().to_string(), "[\"main\", \"a\", true, [1]]");
+
+    let files = [("main.raki", "import \"a.raki\" as a;\na.hidden;"), ("a.raki", "var hidden = 1;")];
+    assert_eq!(runtime_message(eval_files("hidden", &files).unwrap_err()), "Module 'a' has no export 'hidden'.");
+    let files = [("main.raki", "from \"a.raki\" import hidden;"), ("a.raki", "var hidden = 1;")];
+    assert_eq!(runtime_message(eval_files("hidden_from", &files).unwrap_err()), "Module 'a' has no export 'hidden'.");
+  }
+
+  #[test]
+  fn reports_import_errors() {
+    let files = [("main.raki", "import \"a.raki\" as a;"), ("a.raki", "import \"b.raki\" as b;"), ("b.raki", "from \"a.raki\" import x;")];
+    let err = eval_files("circular", &files).unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ImportError, line: 1, message: "Circular import: main.raki -> a.raki -> b.raki -> a.raki.".to_string() });
+
+    let files = [("main.raki", "\nimport \"bad.raki\" as bad;"), ("bad.raki", "var = 1;")];
+    let err = eval_files("invalid", &files).unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ImportError, line: 2, message: "Module 'bad.raki' is not valid Raki on line 1.".to_string() });
+    let readable = |interpreter: &Interpreter, directory: &Path| interpreter.grant(Capabilities { read: vec![directory.to_path_buf()], ..Capabilities::default() });
+    let err = eval_files_with("invalid_readable", &files, readable).unwrap_err();
+    assert_eq!(runtime_message(err), "Error in module 'bad.raki' on line 1 at =: Expect variable name.");
+
+    let files = [("main.raki", "import \"missing.raki\" as m;")];
+    assert!(runtime_message(eval_files("missing", &files).unwrap_err()).starts_with("Cannot find module 'missing.raki', searched "));
+  }
+
+  #[test]
+  fn string_len_counts_characters() {
+    let result = eval("var result = [\"\".len(), \"héllo\".len(), \"日本\".len()];");
+    assert_eq!(result.unwrap().to_string(), "[0, 5, 2]");
+  }
+
+  #[test]
+  fn string_upper() {
+    let result = eval("var result = [\"abc\".upper(), \"straße\".upper()];");
+    assert_eq!(result.unwrap().to_string(), "[\"ABC\", \"STRASSE\"]");
+  }
+
+  #[test]
+  fn string_lower() {
+    let result = eval("var result = [\"ÀBC\".lower(), \"ΑΒ\".lower()];");
+    assert_eq!(result.unwrap().to_string(), "[\"àbc\", \"αβ\"]");
+  }
+
+  #[test]
+  fn string_trim() {
+    let result = eval("var result = \" \t a b \u{3000}\".trim();");
+    assert_eq!(result, Ok(Object::String("a b".to_string())));
+  }
+
+  #[test]
+  fn string_split() {
+    let result = eval("var result = [\"a,b,,c\".split(\",\"), \" one  two \".split(), \"a→b\".split(\"→\")];");
+    assert_eq!(result.unwrap().to_string(), "[[\"a\", \"b\", \"\", \"c\"], [\"one\", \"two\"], [\"a\", \"b\"]]");
+
+    let err = eval("\"abc\".split(\"\");").unwrap_err();
+    assert_eq!(runtime_message(err), "split() separator can't be empty.");
+  }
+
+  #[test]
+  fn string_join() {
+    let result = eval("var result = [\", \".join([\"a\", \"b\", \"c\"]), \"-\".join((\"x\",)), \"\".join([])];");
+    assert_eq!(result.unwrap().to_string(), "[\"a, b, c\", \"x\", \"\"]");
+
+    let err = eval("\",\".join([\"a\", 1]);").unwrap_err();
+    assert_eq!(runtime_message(err), "join() expects a list of strings, found number at index 1.");
+  }
+
+  #[test]
+  fn string_replace() {
+    let result = eval("var result = [\"a-b-c\".replace(\"-\", \"+\"), \"café\".replace(\"é\", \"e\")];");
+    assert_eq!(result.unwrap().to_string(), "[\"a+b+c\", \"cafe\"]");
+
+    let err = eval("\"abc\".replace(1, \"x\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of replace() must be a string, not number.");
+  }
+
+  #[test]
+  fn string_starts_with() {
+    let result = eval("var result = [\"über\".starts_with(\"ü\"), \"über\".starts_with(\"u\"), \"a\".starts_with(\"\")];");
+    assert_eq!(result.unwrap().to_string(), "[true, false, true]");
+  }
+
+  #[test]
+  fn string_ends_with() {
+    let result = eval("var result = [\"file.raki\".ends_with(\".raki\"), \"file.raki\".ends_with(\".rs\")];");
+    assert_eq!(result.unwrap().to_string(), "[true, false]");
+  }
+
+  #[test]
+  fn string_find() {
+    let result = eval("var result = [\"héllo\".find(\"l\"), \"héllo\".find(\"z\"), \"abc\".find(\"\")];");
+    assert_eq!(result.unwrap().to_string(), "[2, nil, 0]");
+  }
+
+  #[test]
+  fn string_substring() {
+    let result = eval("var result = [\"日本語テキスト\".substring(1, 3), \"héllo\".substring(2), \"abc\".substring(3)];");
+    assert_eq!(result.unwrap().to_string(), "[\"本語\", \"llo\", \"\"]");
+
+    let err = eval("\"abc\".substring(2, 5);").unwrap_err();
+    assert_eq!(runtime_message(err), "substring() range 2..5 out of bounds for string of length 3.");
+    let err = eval("\"abc\".substring(-1);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of substring() must be a non-negative integer, not -1.");
+  }
+
+  #[test]
+  fn string_chars() {
+    let result = eval("var result = \"añ日\".chars();");
+    assert_eq!(result.unwrap().to_string(), "[\"a\", \"ñ\", \"日\"]");
+  }
+
+  #[test]
+  fn string_repeat() {
+    let result = eval("var result = [\"ab\".repeat(3), \"é\".repeat(0)];");
+    assert_eq!(result.unwrap().to_string(), "[\"ababab\", \"\"]");
+
+    let err = eval("\"a\".repeat(1.5);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of repeat() must be a non-negative integer, not 1.5.");
+    let err = eval("\"ab\".repeat(10000000000000000000);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 1, message: "repeat() result is too large.".to_string() });
+    let result = eval("var result = \"\".repeat(10000000000000000000);");
+    assert_eq!(result.unwrap(), Object::String(String::new()));
+  }
+
+  #[test]
+  fn string_pad_left() {
+    let result = eval("var result = [\"7\".pad_left(3, \"0\"), \"é\".pad_left(3), \"long\".pad_left(2)];");
+    assert_eq!(result.unwrap().to_string(), "[\"007\", \"  é\", \"long\"]");
+
+    let err = eval("\"a\".pad_left(3, \"ab\");").unwrap_err();
+    assert_eq!(runtime_message(err), "pad_left() fill must be a single character, not \"ab\".");
+    let err = eval("\"a\".pad_left(1000000000000000, \"x\");").unwrap_err();
+    assert_eq!(runtime_message(err), "pad_left() result is too large.");
+  }
+
+  #[test]
+  fn string_pad_right() {
+    let result = eval("var result = [\"ab\".pad_right(4, \"·\"), \"ab\".pad_right(3)];");
+    assert_eq!(result.unwrap().to_string(), "[\"ab··\", \"ab \"]");
+  }
+
+  #[test]
+  fn string_to_number() {
+    let result = eval("var result = [\"42\".to_number(), \" -1.5 \".to_number(), \"1e3\".to_number()];");
+    assert_eq!(result.unwrap().to_string(), "[42, -1.5, 1000]");
+
+    let err = eval("\"12abc\".to_number();").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 1, message: "Can't convert \"12abc\" to a number.".to_string() });
+  }
+
+  #[test]
+  fn indexes_strings_by_character() {
+    let result = eval("var s = \"日本語\"; var result = [s[0], s[2]];");
+    assert_eq!(result.unwrap().to_string(), "[\"日\", \"語\"]");
+
+    let err = eval("\"é\"[1];").unwrap_err();
+    assert_eq!(runtime_message(err), "Index 1 out of bounds for string of length 1.");
+    let err = eval("\"abc\".reverse();").unwrap_err();
+    assert_eq!(runtime_message(err), "Undefined method 'reverse' on string.");
+  }
+
+  #[test]
+  fn calls_math_functions() {
+    let result = eval("var result = [math.sqrt(9), math.max(1, math.PI), [1, 4].map(math.sqrt), math.floor];");
+    assert_eq!(result.unwrap().to_string(), "[3, 3.141592653589793, [1, 2], <native fn floor>]");
+
+    // Natives report errors like user functions, on the line of the call
+    let err = eval("\nmath.pow(2);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Expected 2 arguments but got 1.".to_string() });
+    let err = eval("\n\nmath.sqrt(\"4\");").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 3, message: "Argument 1 of sqrt() must be a number, not string.".to_string() });
+    let err = eval("math.cbrt(8);").unwrap_err();
+    assert_eq!(runtime_message(err), "Module 'math' has no export 'cbrt'.");
+  }
+
+  #[test]
+  fn math_sqrt() {
+    let result = eval("var result = [math.sqrt(16), math.sqrt(2.25), math.sqrt(0), math.is_nan(math.sqrt(-1))];");
+    assert_eq!(result.unwrap().to_string(), "[4, 1.5, 0, true]");
+
+    let err = eval("math.sqrt(\"16\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of sqrt() must be a number, not string.");
+    let err = eval("math.sqrt(4, 9);").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 1 arguments but got 2.");
+  }
+
+  #[test]
+  fn math_abs() {
+    let result = eval("var result = [math.abs(-2.5), math.abs(3), math.abs(-math.INF)];");
+    assert_eq!(result.unwrap().to_string(), "[2.5, 3, inf]");
+
+    let err = eval("math.abs(nil);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of abs() must be a number, not nil.");
+  }
+
+  #[test]
+  fn math_floor() {
+    let result = eval("var result = [math.floor(1.7), math.floor(-1.5), math.floor(3)];");
+    assert_eq!(result.unwrap().to_string(), "[1, -2, 3]");
+
+    let err = eval("math.floor(true);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of floor() must be a number, not bool.");
+  }
+
+  #[test]
+  fn math_ceil() {
+    let result = eval("var result = [math.ceil(1.2), math.ceil(-1.5), math.ceil(3)];");
+    assert_eq!(result.unwrap().to_string(), "[2, -1, 3]");
+
+    let err = eval("math.ceil(\"1.2\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of ceil() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_round() {
+    // Halfway cases round away from zero
+    let result = eval("var result = [math.round(2.5), math.round(-2.5), math.round(1.4), math.round(-0.6)];");
+    assert_eq!(result.unwrap().to_string(), "[3, -3, 1, -1]");
+
+    let err = eval("math.round([1.5]);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of round() must be a number, not list.");
+  }
+
+  #[test]
+  fn math_sin() {
+    let result = eval("var result = [math.sin(0), math.sin(math.PI / 2)];");
+    assert_eq!(result.unwrap().to_string(), "[0, 1]");
+
+    let err = eval("math.sin();").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 1 arguments but got 0.");
+  }
+
+  #[test]
+  fn math_cos() {
+    let result = eval("var result = [math.cos(0), math.cos(math.PI)];");
+    assert_eq!(result.unwrap().to_string(), "[1, -1]");
+
+    let err = eval("math.cos(\"0\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of cos() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_tan() {
+    let result = eval("var result = [math.tan(0), math.abs(math.tan(math.PI / 4) - 1) < 0.000000001];");
+    assert_eq!(result.unwrap().to_string(), "[0, true]");
+
+    let err = eval("math.tan(nil);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of tan() must be a number, not nil.");
+  }
+
+  #[test]
+  fn math_log() {
+    let result = eval("var result = [math.log(1), math.log(math.E), math.log(0), math.is_nan(math.log(-1))];");
+    assert_eq!(result.unwrap().to_string(), "[0, 1, -inf, true]");
+
+    let err = eval("math.log(\"e\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of log() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_log2() {
+    let result = eval("var result = [math.log2(8), math.log2(0.5)];");
+    assert_eq!(result.unwrap().to_string(), "[3, -1]");
+
+    let err = eval("math.log2(8, 2);").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 1 arguments but got 2.");
+  }
+
+  #[test]
+  fn math_log10() {
+    let result = eval("var result = [math.log10(1000), math.log10(0.01)];");
+    assert_eq!(result.unwrap().to_string(), "[3, -2]");
+
+    let err = eval("math.log10(false);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of log10() must be a number, not bool.");
+  }
+
+  #[test]
+  fn math_exp() {
+    let result = eval("var result = [math.exp(0), math.exp(1) == math.E, math.exp(-math.INF)];");
+    assert_eq!(result.unwrap().to_string(), "[1, true, 0]");
+
+    let err = eval("math.exp(\"1\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of exp() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_pow() {
+    let result = eval("var result = [math.pow(2, 10), math.pow(4, 0.5), math.pow(2, -1), math.pow(0, 0)];");
+    assert_eq!(result.unwrap().to_string(), "[1024, 2, 0.5, 1]");
+
+    let err = eval("math.pow(2, \"3\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 2 of pow() must be a number, not string.");
+    let err = eval("math.pow(2);").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 2 arguments but got 1.");
+  }
+
+  #[test]
+  fn math_atan2() {
+    let result = eval("var result = [math.atan2(0, 1), math.atan2(1, 0) == math.PI / 2, math.atan2(0, -1) == math.PI];");
+    assert_eq!(result.unwrap().to_string(), "[0, true, true]");
+
+    let err = eval("math.atan2(nil, 1);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of atan2() must be a number, not nil.");
+  }
+
+  #[test]
+  fn math_min() {
+    let result = eval("var result = [math.min(3, -1), math.min(2), math.min(4, 1, 7), math.min(1, -math.INF)];");
+    assert_eq!(result.unwrap().to_string(), "[-1, 2, 1, -inf]");
+
+    let err = eval("math.min();").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected at least 1 arguments but got 0.");
+    let err = eval("math.min(1, \"2\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 2 of min() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_max() {
+    let result = eval("var result = [math.max(3, -1), math.max(2), math.max(4, 1, 7)];");
+    assert_eq!(result.unwrap().to_string(), "[3, 2, 7]");
+
+    let err = eval("math.max([1, 2]);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of max() must be a number, not list.");
+  }
+
+  #[test]
+  fn math_clamp() {
+    let result = eval("var result = [math.clamp(5, 0, 1), math.clamp(-5, 0, 1), math.clamp(0.5, 0, 1), math.clamp(2, 2, 2)];");
+    assert_eq!(result.unwrap().to_string(), "[1, 0, 0.5, 2]");
+
+    let err = eval("\nmath.clamp(1, 2, 0);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 2, message: "clamp() bounds must satisfy low <= high, got 2 and 0.".to_string() });
+    let err = eval("math.clamp(1, math.NAN, 2);").unwrap_err();
+    assert_eq!(runtime_message(err), "clamp() bounds must satisfy low <= high, got NaN and 2.");
+    let err = eval("math.clamp(1, 2);").unwrap_err();
+    assert_eq!(runtime_message(err), "Expected 3 arguments but got 2.");
+  }
+
+  #[test]
+  fn math_is_nan() {
+    let result = eval("var result = [math.is_nan(math.NAN), math.is_nan(0 / 0), math.is_nan(1), math.is_nan(math.INF)];");
+    assert_eq!(result.unwrap().to_string(), "[true, true, false, false]");
+
+    let err = eval("math.is_nan(\"NaN\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of is_nan() must be a number, not string.");
+  }
+
+  #[test]
+  fn math_is_finite() {
+    let result = eval("var result = [math.is_finite(0.5), math.is_finite(math.INF), math.is_finite(-math.INF), math.is_finite(math.NAN)];");
+    assert_eq!(result.unwrap().to_string(), "[true, false, false, false]");
+
+    let err = eval("math.is_finite(nil);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of is_finite() must be a number, not nil.");
+  }
+
+  #[test]
+  fn math_constants() {
+    let result = eval("var result = [math.PI, math.E, math.INF, -math.INF, math.NAN == math.NAN];");
+    assert_eq!(result.unwrap().to_string(), "[3.141592653589793, 2.718281828459045, inf, -inf, false]");
+  }
+
+  #[test]
+  fn stringifies_json() {
+    let result = eval("var text = json.stringify({\"ids\": [1, 2], \"ok\": true}); var result = json.parse(text)[\"ids\"][1] == 2 ? text : nil;");
+    assert_eq!(result, Ok(Object::String("{\"ids\":[1,2],\"ok\":true}".to_string())));
+
+    let result = eval("class Point { init(x, y) { this.x = x; this.y = y; } to_json() { return [this.x, this.y]; } } var result = json.stringify({\"p\": Point(1, 2)}, 1);");
+    assert_eq!(result, Ok(Object::String("{\n \"p\": [\n  1,\n  2\n ]\n}".to_string())));
+  }
+
+  #[test]
+  fn escapes_json_strings() {
+    let source = "var text = \"a \\ 'b'\t\né\"; var json_text = json.stringify(text); var result = json.parse(json_text) == text ? json_text : nil;";
+    assert_eq!(eval(source), Ok(Object::String("\"a \\\\ 'b'\\t\\né\"".to_string())));
+  }
+
+  #[test]
+  fn rejects_unserializable_json() {
+    let err = eval("class Empty {}\njson.stringify([Empty()]);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Can't serialize an instance of Empty to JSON, define a to_json() method.".to_string() });
+    let err = eval("json.stringify({\"f\": fun () {}});").unwrap_err();
+    assert_eq!(runtime_message(err), "Can't serialize a function to JSON.");
+  }
+
+  #[test]
+  fn rejects_cyclic_json() {
+    let err = eval("var list = [1]; list.push(list); json.stringify(list);").unwrap_err();
+    assert_eq!(runtime_message(err), "Can't serialize a value that contains itself to JSON.");
+    let err = eval("class Loop { to_json() { return [this]; } } json.stringify(Loop());").unwrap_err();
+    assert_eq!(runtime_message(err), "Can't serialize a value that contains itself to JSON.");
+  }
+
+  #[test]
+  fn reports_json_parse_errors() {
+    let err = eval("json.parse(\"[1,\n 2,,]\");").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 2, message: "Invalid JSON on line 2, column 4: Unexpected ','.".to_string() });
+    let err = eval("json.parse(\"[1, 2\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid JSON on line 1, column 6: Expected ',' or ']' after the element.");
+    let err = eval("json.parse(\"[1] x\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid JSON on line 1, column 5: Unexpected 'x' after the value.");
+  }
+
+  // Runs `source` with natives registered by `setup` and returns `result`
+  fn eval_with(setup: impl FnOnce(&Interpreter), source: &str) -> Result<Object, RakiError> {
+    let interpreter = Interpreter::new();
+    setup(&interpreter);
+    let mut scanner = Scanner::new(source.to_string());
+    interpreter.interpret(&Parser::new(scanner.scan_tokens()?).parse_program())?;
+    interpreter.globals.borrow().get(&Token { r#type: TokenType::Identifier, lexeme: "result".to_string(), literal: LiteralType::None, line: 0 })
+  }
+
+  #[test]
+  fn calls_registered_natives() {
+    let setup = |interpreter: &Interpreter| {
+      interpreter.register_native("answer", 0, |_, _| Ok(Object::Double(42.0)));
+      interpreter.register_native("sum", Arity::Variadic(0), |_, arguments| {
+        let numbers = (0..arguments.len()).map(|i| number_argument("sum", arguments, i)).collect::<Result<Vec<f64>, RakiError>>()?;
+        Ok(Object::Double(numbers.iter().fold(0.0, |total, n| total + n)))
+      });
+    };
+    let result = eval_with(setup, "var result = [answer(), sum(), sum(1, 2, 3), sum];");
+    assert_eq!(result.unwrap().to_string(), "[42, 0, 6, <native fn sum>]");
+
+    let err = eval_with(setup, "\nanswer(1);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Expected 0 arguments but got 1.".to_string() });
+    let err = eval_with(setup, "\nsum(1, nil);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Argument 2 of sum() must be a number, not nil.".to_string() });
+  }
+
+  #[test]
+  fn natives_call_back_into_raki() {
+    let setup = |interpreter: &Interpreter| {
+      interpreter.register_native("twice", 1, |interpreter, arguments| {
+        interpreter.call_value(arguments[0].clone(), Vec::new())?;
+        interpreter.call_value(arguments[0].clone(), Vec::new())
+      });
+    };
+    let result = eval_with(setup, "var n = 0; fun bump() { n = n + 1; return n; } var result = [twice(bump), n];");
+    assert_eq!(result.unwrap().to_string(), "[2, 2]");
+
+    // Errors inside the callback keep their own line
+    let err = eval_with(setup, "fun fail() {\n  return 1 + nil;\n}\ntwice(fail);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Unsupported operand types for +: number and nil.".to_string() });
+    let err = eval_with(setup, "\ntwice(fun (x) {});").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Expected 1 arguments but got 0.".to_string() });
+  }
+
+  #[test]
+  fn matches_and_searches_regular_expressions() {
+    let result = eval("var b = re.compile(\"b\"); var result = [b.match(\"abc\"), b.match(\"bc\")[\"end\"], b.search(\"abc\")[\"start\"], b.pattern];");
+    assert_eq!(result.unwrap().to_string(), "[nil, 1, 1, \"b\"]");
+    let result = eval("var result = [re.compile(\"^a\").search(\"ba\"), re.compile(\"a$\").search(\"ba\")[\"start\"], re.compile(\"o\").find_all(\"foo\").len()];");
+    assert_eq!(result.unwrap().to_string(), "[nil, 1, 2]");
+  }
+
+  #[test]
+  fn captures_regex_groups() {
+    let result = eval("var found = re.compile(\"(?<year>\\d{4})-(\\d\\d)\").search(\"due 2024-06\"); var result = [found[\"text\"], found[\"groups\"], found[\"named\"]];");
+    assert_eq!(result.unwrap().to_string(), "[\"2024-06\", [\"2024\", \"06\"], {\"year\": \"2024\"}]");
+
+    // Groups that took no part in the match are nil
+    let result = eval("var result = re.compile(\"(a)|(b)\").search(\"b\")[\"groups\"];");
+    assert_eq!(result.unwrap().to_string(), "[nil, \"b\"]");
+  }
+
+  #[test]
+  fn replaces_regex_matches() {
+    let result = eval("var result = [re.compile(\"\\s*,\\s*\").replace(\"a , b\", \"+\"), re.compile(\"(\\w+)@(\\w+)\").replace(\"a@b, c@d\", \"x\"), re.compile(\"z\").replace(\"ab\", \"x\")];");
+    assert_eq!(result.unwrap().to_string(), "[\"a+b\", \"x, x\", \"ab\"]");
+  }
+
+  #[test]
+  fn splits_on_regex_matches() {
+    let result = eval("var result = [re.compile(\"\\s*,\\s*\").split(\"a , b,c\"), re.compile(\",\").split(\"a,,b,\"), re.compile(\"x*\").split(\"abc\")];");
+    assert_eq!(result.unwrap().to_string(), "[[\"a\", \"b\", \"c\"], [\"a\", \"\", \"b\", \"\"], [\"a\", \"b\", \"c\"]]");
+  }
+
+  #[test]
+  fn reports_regex_pattern_errors() {
+    let err = eval("\nre.compile(\"a(b\");").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 2, message: "Invalid pattern 'a(b' at offset 1: Missing ')'.".to_string() });
+    let err = eval("re.compile(\"*a\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid pattern '*a' at offset 0: Nothing to repeat.");
+    let err = eval("re.compile(\"[z-a]\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid pattern '[z-a]' at offset 1: Range 'z-a' is backwards.");
+  }
+
+  fn fixed_clock(interpreter: &Interpreter) {
+    interpreter.set_clock(FixedClock::new(1_700_000_000.0));
+  }
+
+  #[test]
+  fn reads_the_injected_clock() {
+    let result = eval_with(fixed_clock, "var start = time.now(); time.sleep(90); var result = [time.now() - start, clock()];");
+    assert_eq!(result.unwrap().to_string(), "[90, 1700000090]");
+
+    let err = eval_with(fixed_clock, "time.sleep(-1);").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of sleep() must be a non-negative number of seconds, not -1.");
+  }
+
+  #[test]
+  fn formats_and_parses_timestamps() {
+    let result = eval_with(fixed_clock, "var result = [time.format(time.timestamp()), time.parse(time.format(1234.5))];");
+    assert_eq!(result.unwrap().to_string(), "[\"2023-11-14T22:13:20Z\", 1234.5]");
+
+    let err = eval_with(fixed_clock, "time.parse(\"yesterday\");").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid ISO-8601 timestamp 'yesterday'.");
+  }
+
+  #[test]
+  fn seeds_random_numbers() {
+    let draw = "var list = [1, 2, 3, 4, 5]; random.shuffle(list); var result = [random.int(1, 6), random.float(), random.choice(list), list];";
+    let seeded = |seed| move |interpreter: &Interpreter| interpreter.seed(seed);
+    assert_eq!(eval_with(seeded(7), draw), eval_with(seeded(7), draw));
+    assert_ne!(eval_with(seeded(7), draw), eval_with(seeded(8), draw));
+    assert_eq!(eval(&format!("random.seed(7); {}", draw)), eval_with(seeded(7), draw));
+  }
+
+  #[test]
+  fn draws_random_numbers_in_range() {
+    let result = eval("var rolls = []; for (i in 0..200) { rolls.push(random.int(-1, 1)); } var result = [rolls.filter((n) => n == -1).len() > 0, rolls.filter((n) => n == 1).len() > 0, rolls.filter((n) => n * n > 1).len()];");
+    assert_eq!(result.unwrap().to_string(), "[true, true, 0]");
+    let result = eval("var floats = []; for (i in 0..200) { floats.push(random.float()); } var result = floats.filter((x) => x >= 0).filter((x) => x < 1).len();");
+    assert_eq!(result, Ok(Object::Double(200.0)));
+  }
+
+  #[test]
+  fn rejects_bad_random_arguments() {
+    let err = eval("random.int(3, 1);").unwrap_err();
+    assert_eq!(runtime_message(err), "int() needs a lower bound no greater than the upper one, got 3 and 1.");
+    let err = eval("random.choice([]);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::IndexError, line: 1, message: "choice() from an empty list.".to_string() });
+    let err = eval("random.shuffle((1, 2));").unwrap_err();
+    assert_eq!(runtime_message(err), "Argument 1 of shuffle() must be a list, not tuple.");
+  }
+
+  #[test]
+  fn formats_with_specs() {
+    let result = eval("class Money { __str__() { return \"$5\"; } } var result = format(\"{:>6.2}|{:<4}|{:#x}|{}\", 0.1 + 0.2, nil, 255, Money());");
+    assert_eq!(result, Ok(Object::String("  0.30|nil |0xff|$5".to_string())));
+    let result = eval("var result = format(\"{:+} {:b} {:08.3} {:^7}| {{}}\", 5, 5, 3.14159, \"mid\");");
+    assert_eq!(result, Ok(Object::String("+5 101 0003.142   mid  | {}".to_string())));
+  }
+
+  #[test]
+  fn rejects_bad_format_strings() {
+    let err = eval("\nformat(\"{} {}\", 1);").unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::IndexError, line: 2, message: "Format string needs argument 2 but got 1.".to_string() });
+    let err = eval("format(\"{:q}\", 1);").unwrap_err();
+    assert_eq!(runtime_message(err), "Invalid format spec 'q'.");
+    let err = eval("format(\"{\", 1);").unwrap_err();
+    assert_eq!(runtime_message(err), "Unclosed '{' in format string, write '{{' for a brace.");
+  }
+
+  #[test]
+  fn reprs_values() {
+    let result = eval("class Money { __str__() { return \"$5\"; } } var result = [repr(\"it's\"), repr([\"a\", nil]), repr((1, \"a\")), repr({\"k\": 1.5}), repr(Money())];");
+    let reprs = ["\"it's\"", "[\"a\", nil]", "(1, \"a\")", "{\"k\": 1.5}", "$5"];
+    assert_eq!(result, Ok(Object::new_list(reprs.iter().map(|repr| Object::String(repr.to_string())).collect())));
+  }
+
+  #[test]
+  fn checks_print_separators() {
+    assert_eq!(eval("print 1, 2, sep: \"\", end: \"\"; var result = nil;"), Ok(Object::None));
+    let err = eval("print 1, 2, sep: 3;").unwrap_err();
+    assert_eq!(runtime_message(err), "print sep must be a string, not number.");
+    let err = eval("print 1, end: nil;").unwrap_err();
+    assert_eq!(runtime_message(err), "print end must be a string, not nil.");
+  }
+
+  #[test]
+  fn clock_returns_seconds() {
+    let result = eval("var start = clock(); var result = [start > 1000000000, clock() >= start];");
+    assert_eq!(result.unwrap().to_string(), "[true, true]");
+  }
 }
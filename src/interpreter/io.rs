@@ -0,0 +1,208 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Interpreter, Module, Object, argument, native};
+
+// What the host lets scripts touch. Nothing is granted by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+  // Directories whose contents scripts may read, and write
+  pub read: Vec<PathBuf>,
+  pub write: Vec<PathBuf>,
+  pub stdin: bool,
+  pub env: bool,
+  // Returned by `io.args()`
+  pub args: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+  Read,
+  Write,
+}
+
+impl Capabilities {
+  // Whether `path` lies inside a granted directory. Symlinks are resolved
+  // first so they can't point outside of it.
+  fn allows(&self, access: Access, path: &Path) -> bool {
+    let roots = match access {
+      Access::Read => &self.read,
+      Access::Write => &self.write,
+    };
+    roots.iter().filter_map(|root| resolve(root).ok()).any(|root| path.starts_with(root))
+  }
+
+  pub fn allows_read(&self, path: &Path) -> bool {
+    resolve(path).is_ok_and(|path| self.allows(Access::Read, &path))
+  }
+
+  // Whether a module file may be imported. It has to be inside one of the
+  // directories the interpreter imports from, or be readable.
+  pub fn allows_import(&self, path: &Path, directories: &[PathBuf]) -> bool {
+    let Ok(path) = resolve(path) else {
+      return false;
+    };
+    directories.iter().filter_map(|directory| resolve(directory).ok()).any(|directory| path.starts_with(directory)) || self.allows(Access::Read, &path)
+  }
+}
+
+// An absolute path with `.` and `..` removed and its longest existing prefix
+// canonicalized, so it also works for files that don't exist yet
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+  let mut absolute = PathBuf::new();
+  for component in env::current_dir()?.join(path).components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => _ = absolute.pop(),
+      component => absolute.push(component),
+    }
+  }
+
+  let mut missing = Vec::new();
+  let mut existing = absolute.as_path();
+  loop {
+    if let Ok(canonical) = existing.canonicalize() {
+      return Ok(missing.iter().rev().fold(canonical, |path, name| path.join(name)));
+    }
+    match (existing.file_name(), existing.parent()) {
+      (Some(name), Some(parent)) => {
+        missing.push(name);
+        existing = parent;
+      }
+      _ => return Ok(absolute),
+    }
+  }
+}
+
+fn error(kind: ErrorKind, message: String) -> RakiError {
+  RakiError::Runtime { kind, line: 0, message }
+}
+
+// Resolves argument `i` as a path the script is allowed to access
+fn granted_path(interpreter: &Interpreter, function: &str, arguments: &[Object], i: usize, access: Access) -> Result<(String, PathBuf), RakiError> {
+  let path: String = argument(function, arguments, i)?;
+  let resolved = resolve(Path::new(&path)).map_err(|err| error(ErrorKind::IOError, format!("Could not resolve '{}': {}.", path, err)))?;
+  if !interpreter.capabilities().allows(access, &resolved) {
+    let action = match access {
+      Access::Read => "read",
+      Access::Write => "write",
+    };
+    return Err(error(ErrorKind::PermissionError, format!("{}() is not allowed to {} '{}'.", function, action, path)));
+  }
+  Ok((path, resolved))
+}
+
+fn io_error(function: &str, path: &str, err: io::Error) -> RakiError {
+  error(ErrorKind::IOError, format!("{}() failed for '{}': {}.", function, path, err))
+}
+
+fn read_file(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let (path, resolved) = granted_path(interpreter, "read_file", arguments, 0, Access::Read)?;
+  fs::read_to_string(resolved).map(Object::String).map_err(|err| io_error("read_file", &path, err))
+}
+
+fn write_file(interpreter: &Interpreter, arguments: &[Object], append: bool) -> Result<Object, RakiError> {
+  let function = if append { "append" } else { "write_file" };
+  let (path, resolved) = granted_path(interpreter, function, arguments, 0, Access::Write)?;
+  let contents: String = argument(function, arguments, 1)?;
+  let mut file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(resolved).map_err(|err| io_error(function, &path, err))?;
+  file.write_all(contents.as_bytes()).map_err(|err| io_error(function, &path, err))?;
+  Ok(Object::None)
+}
+
+// Entry names, sorted so listings don't depend on the file system
+fn list_dir(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let (path, resolved) = granted_path(interpreter, "list_dir", arguments, 0, Access::Read)?;
+  let entries = fs::read_dir(resolved).map_err(|err| io_error("list_dir", &path, err))?;
+  let mut names = entries.map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string())).collect::<io::Result<Vec<String>>>().map_err(|err| io_error("list_dir", &path, err))?;
+  names.sort();
+  Ok(Object::new_list(names.into_iter().map(Object::String).collect()))
+}
+
+fn exists(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let (_, resolved) = granted_path(interpreter, "exists", arguments, 0, Access::Read)?;
+  Ok(Object::Boolean(resolved.exists()))
+}
+
+// A line without its line ending, or nil at the end of input
+fn read_line(interpreter: &Interpreter) -> Result<Object, RakiError> {
+  if !interpreter.capabilities().stdin {
+    return Err(error(ErrorKind::PermissionError, "read_line() is not allowed to read standard input.".to_string()));
+  }
+  let mut line = String::new();
+  match io::stdin().lock().read_line(&mut line) {
+    Ok(0) => Ok(Object::None),
+    Ok(_) => Ok(Object::String(line.trim_end_matches(['\n', '\r']).to_string())),
+    Err(err) => Err(error(ErrorKind::IOError, format!("read_line() failed: {}.", err))),
+  }
+}
+
+fn env_get(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let name: String = argument("get", arguments, 0)?;
+  if !interpreter.capabilities().env {
+    return Err(error(ErrorKind::PermissionError, format!("get() is not allowed to read the environment variable '{}'.", name)));
+  }
+  Ok(env::var(&name).map(Object::String).unwrap_or(Object::None))
+}
+
+// The built-in `fs` namespace. Every path must be inside a directory the host
+// granted, see `Capabilities`.
+pub fn fs_module() -> Module {
+  Module::builtin(
+    "fs",
+    vec![
+      ("read_file", native("read_file", Arity::Fixed(1), read_file)),
+      ("write_file", native("write_file", Arity::Fixed(2), |interpreter, arguments| write_file(interpreter, arguments, false))),
+      ("append", native("append", Arity::Fixed(2), |interpreter, arguments| write_file(interpreter, arguments, true))),
+      ("list_dir", native("list_dir", Arity::Fixed(1), list_dir)),
+      ("exists", native("exists", Arity::Fixed(1), exists)),
+    ],
+  )
+}
+
+// The built-in `io` namespace, with `io.stdin`, `io.env` and `io.args()`
+pub fn io_module() -> Module {
+  let stdin = Module::builtin("stdin", vec![("read_line", native("read_line", Arity::Fixed(0), |interpreter, _| read_line(interpreter)))]);
+  let environment = Module::builtin("env", vec![("get", native("get", Arity::Fixed(1), env_get))]);
+  let args = native("args", Arity::Fixed(0), |interpreter, _| Ok(Object::new_list(interpreter.capabilities().args.iter().cloned().map(Object::String).collect())));
+  Module::builtin("io", vec![("stdin", Object::Module(Rc::new(stdin))), ("env", Object::Module(Rc::new(environment))), ("args", args)])
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn resolves_paths_that_do_not_exist_yet() {
+    let dir = env::temp_dir().canonicalize().unwrap();
+    assert_eq!(resolve(&dir.join("raki/./missing/../file.txt")).unwrap(), dir.join("raki/file.txt"));
+    assert_eq!(resolve(&dir.join("..")).unwrap(), dir.parent().unwrap());
+  }
+
+  #[test]
+  fn allows_only_granted_directories() {
+    let dir = env::temp_dir().canonicalize().unwrap();
+    let capabilities = Capabilities { read: vec![dir.join("data")], ..Capabilities::default() };
+    assert!(capabilities.allows(Access::Read, &dir.join("data/file.txt")));
+    assert!(!capabilities.allows(Access::Read, &dir.join("database")));
+    assert!(!capabilities.allows(Access::Read, &dir.join("other/file.txt")));
+    assert!(!capabilities.allows(Access::Write, &dir.join("data/file.txt")));
+    assert!(!Capabilities::default().allows(Access::Read, &dir));
+  }
+
+  #[test]
+  fn allows_imports_from_module_directories() {
+    let dir = env::temp_dir().canonicalize().unwrap();
+    let directories = [dir.join("project")];
+    assert!(Capabilities::default().allows_import(&dir.join("project/lib/a.raki"), &directories));
+    assert!(!Capabilities::default().allows_import(&dir.join("project/../secret.txt"), &directories));
+    assert!(!Capabilities::default().allows_import(Path::new("/etc/hostname"), &directories));
+    let capabilities = Capabilities { read: vec![dir.join("shared")], ..Capabilities::default() };
+    assert!(capabilities.allows_import(&dir.join("shared/b.raki"), &directories));
+  }
+}
@@ -0,0 +1,59 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::Object;
+
+// State of a running `for ... in` loop. Built-in iterables are stepped here,
+// user objects are stepped by the interpreter calling their `next()` method.
+pub enum RakiIterator {
+  List { list: Rc<RefCell<Vec<Object>>>, index: usize, len: usize },
+  Values { values: Vec<Object>, index: usize },
+  Range { next: f64, end: f64, inclusive: bool },
+  User { iterator: Object },
+}
+
+impl RakiIterator {
+  pub fn list(list: Rc<RefCell<Vec<Object>>>) -> RakiIterator {
+    let len = list.borrow().len();
+    RakiIterator::List { list, index: 0, len }
+  }
+
+  pub fn values(values: Vec<Object>) -> RakiIterator {
+    RakiIterator::Values { values, index: 0 }
+  }
+
+  // Steps a built-in iterator, user iterators always yield `None` here
+  pub fn next_builtin(&mut self) -> Result<Option<Object>, RakiError> {
+    match self {
+      RakiIterator::List { list, index, len } => {
+        let list = list.borrow();
+        if list.len() != *len {
+          return Err(RakiError::Runtime { kind: ErrorKind::RuntimeError, line: 0, message: "List changed size during iteration.".to_string() });
+        }
+        let value = list.get(*index).cloned();
+        *index += 1;
+        Ok(value)
+      }
+      RakiIterator::Values { values, index } => {
+        let value = values.get(*index).cloned();
+        *index += 1;
+        Ok(value)
+      }
+      RakiIterator::Range { next, end, inclusive } => {
+        let done = match inclusive {
+          true => *next > *end,
+          false => *next >= *end,
+        };
+        if done {
+          return Ok(None);
+        }
+        let value = Object::Double(*next);
+        *next += 1.0;
+        Ok(Some(value))
+      }
+      RakiIterator::User { .. } => Ok(None),
+    }
+  }
+}
@@ -0,0 +1,468 @@
+use std::rc::Rc;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Interpreter, Module, Object, RakiMap, argument, native};
+
+// Deeper input is rejected rather than risking the native stack
+const MAX_DEPTH: usize = 256;
+
+fn error(kind: ErrorKind, message: String) -> RakiError {
+  RakiError::Runtime { kind, line: 0, message }
+}
+
+struct JsonParser {
+  source: Vec<char>,
+  current: usize,
+  line: usize,
+  column: usize,
+}
+
+impl JsonParser {
+  fn new(source: &str) -> JsonParser {
+    JsonParser { source: source.chars().collect(), current: 0, line: 1, column: 1 }
+  }
+
+  fn parse(&mut self) -> Result<Object, RakiError> {
+    let value = self.value(0)?;
+    self.skip_whitespace();
+    match self.peek() {
+      None => Ok(value),
+      Some(c) => Err(self.error(&format!("Unexpected {:?} after the value.", c))),
+    }
+  }
+
+  fn error(&self, message: &str) -> RakiError {
+    error(ErrorKind::ValueError, format!("Invalid JSON on line {}, column {}: {}", self.line, self.column, message))
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.source.get(self.current).copied()
+  }
+
+  fn advance(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.current += 1;
+    match c {
+      '\n' => {
+        self.line += 1;
+        self.column = 1;
+      }
+      _ => self.column += 1,
+    }
+    Some(c)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+      self.advance();
+    }
+  }
+
+  fn expect(&mut self, expected: char, context: &str) -> Result<(), RakiError> {
+    self.skip_whitespace();
+    match self.peek() {
+      Some(c) if c == expected => {
+        self.advance();
+        Ok(())
+      }
+      Some(c) => Err(self.error(&format!("Expected '{}' {}, found {:?}.", expected, context, c))),
+      None => Err(self.error(&format!("Expected '{}' {}, found the end of input.", expected, context))),
+    }
+  }
+
+  fn value(&mut self, depth: usize) -> Result<Object, RakiError> {
+    if depth > MAX_DEPTH {
+      return Err(self.error(&format!("Nested deeper than {} levels.", MAX_DEPTH)));
+    }
+    self.skip_whitespace();
+    match self.peek() {
+      Some('{') => self.object(depth),
+      Some('[') => self.array(depth),
+      Some('"') => self.string().map(Object::String),
+      Some('-' | '0'..='9') => self.number(),
+      Some('t') => self.keyword("true", Object::Boolean(true)),
+      Some('f') => self.keyword("false", Object::Boolean(false)),
+      Some('n') => self.keyword("null", Object::None),
+      Some(c) => Err(self.error(&format!("Unexpected {:?}.", c))),
+      None => Err(self.error("Unexpected end of input.")),
+    }
+  }
+
+  fn keyword(&mut self, word: &str, value: Object) -> Result<Object, RakiError> {
+    let (line, column) = (self.line, self.column);
+    for expected in word.chars() {
+      if self.peek() != Some(expected) {
+        self.line = line;
+        self.column = column;
+        return Err(self.error(&format!("Expected '{}'.", word)));
+      }
+      self.advance();
+    }
+    Ok(value)
+  }
+
+  fn object(&mut self, depth: usize) -> Result<Object, RakiError> {
+    self.advance();
+    let mut map = RakiMap::new();
+    self.skip_whitespace();
+    if self.peek() == Some('}') {
+      self.advance();
+      return Ok(Object::new_map(map));
+    }
+
+    loop {
+      self.skip_whitespace();
+      if self.peek() != Some('"') {
+        return Err(self.error("Expected a string key."));
+      }
+      let key = self.string()?;
+      self.expect(':', "after the key")?;
+      map.insert(Object::String(key), self.value(depth + 1)?);
+      self.skip_whitespace();
+      match self.peek() {
+        Some(',') => _ = self.advance(),
+        Some('}') => {
+          self.advance();
+          return Ok(Object::new_map(map));
+        }
+        _ => return Err(self.error("Expected ',' or '}' after the entry.")),
+      }
+    }
+  }
+
+  fn array(&mut self, depth: usize) -> Result<Object, RakiError> {
+    self.advance();
+    let mut elements = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some(']') {
+      self.advance();
+      return Ok(Object::new_list(elements));
+    }
+
+    loop {
+      elements.push(self.value(depth + 1)?);
+      self.skip_whitespace();
+      match self.peek() {
+        Some(',') => _ = self.advance(),
+        Some(']') => {
+          self.advance();
+          return Ok(Object::new_list(elements));
+        }
+        _ => return Err(self.error("Expected ',' or ']' after the element.")),
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, RakiError> {
+    self.advance();
+    let mut s = String::new();
+    loop {
+      match self.advance() {
+        Some('"') => return Ok(s),
+        Some('\\') => s.push(self.escape()?),
+        Some(c) if (c as u32) < 0x20 => return Err(self.error("Control characters must be escaped in strings.")),
+        Some(c) => s.push(c),
+        None => return Err(self.error("Unterminated string.")),
+      }
+    }
+  }
+
+  fn escape(&mut self) -> Result<char, RakiError> {
+    match self.advance() {
+      Some('"') => Ok('"'),
+      Some('\\') => Ok('\\'),
+      Some('/') => Ok('/'),
+      Some('b') => Ok('\u{8}'),
+      Some('f') => Ok('\u{c}'),
+      Some('n') => Ok('\n'),
+      Some('r') => Ok('\r'),
+      Some('t') => Ok('\t'),
+      Some('u') => {
+        let high = self.hex()?;
+        if !(0xD800..0xDC00).contains(&high) {
+          return char::from_u32(high).ok_or_else(|| self.error("Unpaired surrogate in \\u escape."));
+        }
+        // Characters outside the BMP are written as a surrogate pair
+        if self.advance() != Some('\\') || self.advance() != Some('u') {
+          return Err(self.error("Unpaired surrogate in \\u escape."));
+        }
+        let low = self.hex()?;
+        if !(0xDC00..0xE000).contains(&low) {
+          return Err(self.error("Unpaired surrogate in \\u escape."));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).ok_or_else(|| self.error("Invalid \\u escape."))
+      }
+      _ => Err(self.error("Invalid escape in string.")),
+    }
+  }
+
+  fn hex(&mut self) -> Result<u32, RakiError> {
+    let mut value = 0;
+    for _ in 0..4 {
+      match self.advance().and_then(|c| c.to_digit(16)) {
+        Some(digit) => value = value * 16 + digit,
+        None => return Err(self.error("Expected 4 hex digits in \\u escape.")),
+      }
+    }
+    Ok(value)
+  }
+
+  fn digits(&mut self, text: &mut String) -> usize {
+    let start = text.len();
+    while let Some(c @ '0'..='9') = self.peek() {
+      text.push(c);
+      self.advance();
+    }
+    text.len() - start
+  }
+
+  fn number(&mut self) -> Result<Object, RakiError> {
+    let mut text = String::new();
+    if self.peek() == Some('-') {
+      text.push('-');
+      self.advance();
+    }
+    if self.peek() == Some('0') {
+      text.push('0');
+      self.advance();
+    } else if self.digits(&mut text) == 0 {
+      return Err(self.error("Expected a digit."));
+    }
+    if self.peek() == Some('.') {
+      text.push('.');
+      self.advance();
+      if self.digits(&mut text) == 0 {
+        return Err(self.error("Expected a digit after the decimal point."));
+      }
+    }
+    if let Some(e @ ('e' | 'E')) = self.peek() {
+      text.push(e);
+      self.advance();
+      if let Some(sign @ ('+' | '-')) = self.peek() {
+        text.push(sign);
+        self.advance();
+      }
+      if self.digits(&mut text) == 0 {
+        return Err(self.error("Expected a digit in the exponent."));
+      }
+    }
+    text.parse().map(Object::Double).map_err(|_| self.error("Invalid number."))
+  }
+}
+
+struct Serializer<'a> {
+  interpreter: &'a Interpreter,
+  indent: Option<String>,
+  // Containers being written, to catch cycles
+  parents: Vec<*const ()>,
+  out: String,
+}
+
+impl Serializer<'_> {
+  fn newline(&mut self, depth: usize) {
+    if let Some(indent) = &self.indent {
+      self.out.push('\n');
+      self.out.push_str(&indent.repeat(depth));
+    }
+  }
+
+  fn write(&mut self, value: &Object, depth: usize) -> Result<(), RakiError> {
+    self.interpreter.check_stack()?;
+    match value {
+      Object::None => self.out.push_str("null"),
+      Object::Boolean(b) => self.out.push_str(&b.to_string()),
+      Object::Double(n) if n.is_finite() => self.out.push_str(&n.to_string()),
+      Object::Double(n) => return Err(error(ErrorKind::ValueError, format!("Can't serialize {} to JSON.", n))),
+      Object::String(s) => write_string(&mut self.out, s),
+      Object::List(list) => {
+        let elements = list.borrow().clone();
+        self.nested(Rc::as_ptr(list) as *const (), |serializer| serializer.array(&elements, depth))?;
+      }
+      Object::Tuple(elements) => self.array(elements, depth)?,
+      Object::Map(map) => {
+        let entries: Vec<(Object, Object)> = map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        self.nested(Rc::as_ptr(map) as *const (), |serializer| serializer.object(&entries, depth))?;
+      }
+      Object::Instance(instance) => {
+        let class = instance.borrow().class.clone();
+        let Some(to_json) = class.find_method("to_json") else {
+          return Err(error(ErrorKind::TypeError, format!("Can't serialize an instance of {} to JSON, define a to_json() method.", class.name)));
+        };
+        let converted = self.interpreter.call_value(Object::Function(Rc::new(to_json.bind(value.clone()))), Vec::new())?;
+        self.nested(Rc::as_ptr(instance) as *const (), |serializer| serializer.write(&converted, depth))?;
+      }
+      value => return Err(error(ErrorKind::TypeError, format!("Can't serialize a {} to JSON.", value.type_name()))),
+    }
+    Ok(())
+  }
+
+  fn nested(&mut self, parent: *const (), f: impl FnOnce(&mut Self) -> Result<(), RakiError>) -> Result<(), RakiError> {
+    if self.parents.contains(&parent) {
+      return Err(error(ErrorKind::ValueError, "Can't serialize a value that contains itself to JSON.".to_string()));
+    }
+    self.parents.push(parent);
+    let result = f(self);
+    self.parents.pop();
+    result
+  }
+
+  fn array(&mut self, elements: &[Object], depth: usize) -> Result<(), RakiError> {
+    self.out.push('[');
+    for (i, element) in elements.iter().enumerate() {
+      if i > 0 {
+        self.out.push(',');
+      }
+      self.newline(depth + 1);
+      self.write(element, depth + 1)?;
+    }
+    if !elements.is_empty() {
+      self.newline(depth);
+    }
+    self.out.push(']');
+    Ok(())
+  }
+
+  fn object(&mut self, entries: &[(Object, Object)], depth: usize) -> Result<(), RakiError> {
+    self.out.push('{');
+    for (i, (key, value)) in entries.iter().enumerate() {
+      let Object::String(key) = key else {
+        return Err(error(ErrorKind::TypeError, format!("JSON object keys must be strings, not {}.", key.type_name())));
+      };
+      if i > 0 {
+        self.out.push(',');
+      }
+      self.newline(depth + 1);
+      write_string(&mut self.out, key);
+      self.out.push_str(if self.indent.is_some() { ": " } else { ":" });
+      self.write(value, depth + 1)?;
+    }
+    if !entries.is_empty() {
+      self.newline(depth);
+    }
+    self.out.push('}');
+    Ok(())
+  }
+}
+
+fn write_string(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+pub fn parse(source: &str) -> Result<Object, RakiError> {
+  JsonParser::new(source).parse()
+}
+
+// Writes `value` compactly, or pretty printed when `indent` is given.
+// Instances are written through their `to_json()` method.
+pub fn stringify(interpreter: &Interpreter, value: &Object, indent: Option<String>) -> Result<String, RakiError> {
+  let mut serializer = Serializer { interpreter, indent, parents: Vec::new(), out: String::new() };
+  serializer.write(value, 0)?;
+  Ok(serializer.out)
+}
+
+fn indent_argument(arguments: &[Object]) -> Result<Option<String>, RakiError> {
+  match arguments.get(1) {
+    None | Some(Object::None) => Ok(None),
+    Some(Object::String(s)) => Ok(Some(s.clone())),
+    Some(Object::Double(n)) if n.fract() == 0.0 && (0.0..=10.0).contains(n) => Ok(Some(" ".repeat(*n as usize))),
+    Some(Object::Double(n)) => Err(error(ErrorKind::ValueError, format!("Argument 2 of stringify() must be between 0 and 10, not {}.", n))),
+    Some(obj) => Err(error(ErrorKind::TypeError, format!("Argument 2 of stringify() must be a number or a string, not {}.", obj.type_name()))),
+  }
+}
+
+// The built-in `json` namespace
+pub fn json_module() -> Module {
+  Module::builtin(
+    "json",
+    vec![
+      ("parse", native("parse", Arity::Fixed(1), |_, arguments| parse(&argument::<String>("parse", arguments, 0)?))),
+      (
+        "stringify",
+        native("stringify", Arity::Variadic(1), |interpreter, arguments| {
+          if arguments.len() > 2 {
+            return Err(error(ErrorKind::TypeError, format!("Expected at most 2 arguments but got {}.", arguments.len())));
+          }
+          stringify(interpreter, &arguments[0], indent_argument(arguments)?).map(Object::String)
+        }),
+      ),
+    ],
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn message(err: RakiError) -> String {
+    match err {
+      RakiError::Runtime { message, .. } => message,
+      err => panic!("expected a runtime error, got {:?}", err),
+    }
+  }
+
+  fn round_trip(source: &str) -> String {
+    stringify(&Interpreter::new(), &parse(source).unwrap(), None).unwrap()
+  }
+
+  #[test]
+  fn parses_values() {
+    assert_eq!(parse(" 12.5e1 "), Ok(Object::Double(125.0)));
+    assert_eq!(parse("-0.25"), Ok(Object::Double(-0.25)));
+    assert_eq!(parse("true"), Ok(Object::Boolean(true)));
+    assert_eq!(parse("null"), Ok(Object::None));
+    assert_eq!(parse(r#""a\"b\\c\né😀""#), Ok(Object::String("a\"b\\c\né😀".to_string())));
+    assert_eq!(parse(r#"{"a": [1, {"b": false}], "c": "d"}"#).unwrap().to_string(), r#"{"a": [1, {"b": false}], "c": "d"}"#);
+    assert_eq!(parse("[]").unwrap().to_string(), "[]");
+  }
+
+  #[test]
+  fn reports_the_error_position() {
+    assert_eq!(message(parse("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err()), "Invalid JSON on line 3, column 7: Expected ':' after the key, found '2'.");
+    assert_eq!(message(parse("[1, 2").unwrap_err()), "Invalid JSON on line 1, column 6: Expected ',' or ']' after the element.");
+    assert_eq!(message(parse("[01]").unwrap_err()), "Invalid JSON on line 1, column 3: Expected ',' or ']' after the element.");
+    assert_eq!(message(parse("tru").unwrap_err()), "Invalid JSON on line 1, column 1: Expected 'true'.");
+    assert_eq!(message(parse("\"abc").unwrap_err()), "Invalid JSON on line 1, column 5: Unterminated string.");
+    assert_eq!(message(parse("1 2").unwrap_err()), "Invalid JSON on line 1, column 3: Unexpected '2' after the value.");
+    assert_eq!(message(parse("").unwrap_err()), "Invalid JSON on line 1, column 1: Unexpected end of input.");
+    assert!(message(parse(&"[".repeat(1000)).unwrap_err()).ends_with("Nested deeper than 256 levels."));
+  }
+
+  #[test]
+  fn stringifies_values() {
+    assert_eq!(round_trip(r#" {"a" : [1, 2.5, true, null], "b": {}, "c": "\"\n\u0001"} "#), r#"{"a":[1,2.5,true,null],"b":{},"c":"\"\n\u0001"}"#);
+
+    let value = parse(r#"{"a": [1, 2], "b": []}"#).unwrap();
+    let pretty = stringify(&Interpreter::new(), &value, Some("  ".to_string())).unwrap();
+    assert_eq!(pretty, "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": []\n}");
+  }
+
+  #[test]
+  fn rejects_unserializable_values() {
+    let interpreter = Interpreter::new();
+    let err = stringify(&interpreter, &Object::Double(f64::NAN), None).unwrap_err();
+    assert_eq!(message(err), "Can't serialize NaN to JSON.");
+
+    let list = Object::new_list(Vec::new());
+    if let Object::List(elements) = &list {
+      elements.borrow_mut().push(list.clone());
+    }
+    assert_eq!(message(stringify(&interpreter, &list, None).unwrap_err()), "Can't serialize a value that contains itself to JSON.");
+
+    // The same list twice is fine, it is only a cycle when nested in itself
+    let shared = Object::new_list(vec![Object::Double(1.0)]);
+    assert_eq!(stringify(&interpreter, &Object::new_tuple(vec![shared.clone(), shared]), None), Ok("[[1],[1]]".to_string()));
+  }
+}
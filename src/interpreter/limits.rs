@@ -0,0 +1,255 @@
+use std::cell::{Cell, RefCell};
+use std::hint::black_box;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::raki_log::{LimitKind, RakiError};
+
+use super::Object;
+
+// How deep scripts may recurse. Each Raki call takes several Rust frames, up
+// to 30 KiB in debug builds, so the stack limit is usually reached first.
+pub const DEFAULT_CALL_DEPTH: usize = 200;
+
+// Half of the 2 MiB a `std::thread::spawn` thread gets, leaving the rest for
+// frames the checks can't see, like natives and parsing imported modules.
+// Hosts on bigger threads can raise it.
+pub const DEFAULT_STACK: usize = 1024 * 1024;
+
+// Reading the clock on every step would dominate simple loops
+const CLOCK_INTERVAL: u64 = 1024;
+
+// How long a sleeping script goes without checking for cancellation
+pub const SLEEP_SLICE: Duration = Duration::from_millis(20);
+
+// Budgets for untrusted scripts. Steps, time and allocations are counted from the
+// start of each run, see `Interpreter::start_run`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+  // Statements executed plus expressions evaluated
+  pub max_steps: Option<u64>,
+  pub timeout: Option<Duration>,
+  pub max_call_depth: usize,
+  // Bytes of native stack a run may use, so deeply nested expressions and
+  // values fail before the stack overflows
+  pub max_stack: usize,
+  // Approximate bytes allocated by strings, containers and instances over the
+  // whole run. Values that were dropped still count, so this bounds how much
+  // work a run does with memory, not how much it holds at once.
+  pub max_allocation: Option<usize>,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits { max_steps: None, timeout: None, max_call_depth: DEFAULT_CALL_DEPTH, max_stack: DEFAULT_STACK, max_allocation: None }
+  }
+}
+
+// Stops the running script from any thread. The script fails with a
+// `Cancelled` error at its next step, and later runs fail at their first one
+// until the host calls `reset`.
+#[derive(Clone, Debug)]
+pub struct CancelHandle {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn reset(&self) {
+    self.cancelled.store(false, Ordering::Relaxed);
+  }
+}
+
+// What the current run has used so far
+#[derive(Debug, Default)]
+pub struct Budget {
+  pub limits: RefCell<Limits>,
+  steps: Cell<u64>,
+  depth: Cell<usize>,
+  allocated: Cell<usize>,
+  deadline: Cell<Option<Instant>>,
+  cancelled: Arc<AtomicBool>,
+  // Roughly where the stack was when the run started
+  stack_base: Cell<Option<usize>>,
+}
+
+// The address of a local, which moves down as the stack grows on every
+// platform Rust supports
+fn stack_position() -> usize {
+  let marker = 0u8;
+  black_box(&marker) as *const u8 as usize
+}
+
+impl Budget {
+  pub fn start(&self) {
+    self.stack_base.set(Some(stack_position()));
+    self.steps.set(0);
+    self.allocated.set(0);
+    self.deadline.set(self.limits.borrow().timeout.map(|timeout| Instant::now() + timeout));
+  }
+
+  pub fn cancel_handle(&self) -> CancelHandle {
+    CancelHandle { cancelled: self.cancelled.clone() }
+  }
+
+  pub fn step(&self) -> Result<(), RakiError> {
+    let steps = self.steps.get() + 1;
+    self.steps.set(steps);
+
+    self.check_cancelled()?;
+    let limits = self.limits.borrow();
+    if let Some(max) = limits.max_steps
+      && steps > max
+    {
+      return Err(exceeded(LimitKind::Steps, format!("Exceeded the limit of {} steps.", max)));
+    }
+    if let Some(deadline) = self.deadline.get()
+      && steps.is_multiple_of(CLOCK_INTERVAL)
+      && Instant::now() >= deadline
+    {
+      return Err(self.timed_out());
+    }
+    Ok(())
+  }
+
+  pub fn check_cancelled(&self) -> Result<(), RakiError> {
+    match self.cancelled.load(Ordering::Relaxed) {
+      true => Err(exceeded(LimitKind::Cancelled, "Execution was cancelled.".to_string())),
+      false => Ok(()),
+    }
+  }
+
+  // Fails once the run has used more than `max_stack`. Runs the host didn't
+  // start measure from the first check.
+  pub fn check_stack(&self) -> Result<(), RakiError> {
+    let position = stack_position();
+    let base = self.stack_base.get().unwrap_or(position);
+    self.stack_base.set(Some(base));
+    let max = self.limits.borrow().max_stack;
+    if base.saturating_sub(position) > max {
+      return Err(exceeded(LimitKind::Stack, format!("Exceeded the stack limit of {} bytes, the script is nested too deeply.", max)));
+    }
+    Ok(())
+  }
+
+  // Time left before the deadline, if there is one
+  pub fn remaining(&self) -> Option<Duration> {
+    self.deadline.get().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+  }
+
+  pub fn timed_out(&self) -> RakiError {
+    let timeout = self.limits.borrow().timeout.unwrap_or_default();
+    exceeded(LimitKind::Time, format!("Exceeded the time limit of {:?}.", timeout))
+  }
+
+  // Runs `f` one call deeper, failing before the native stack could overflow
+  pub fn nested<T>(&self, f: impl FnOnce() -> Result<T, RakiError>) -> Result<T, RakiError> {
+    let max = self.limits.borrow().max_call_depth;
+    if self.depth.get() >= max {
+      return Err(exceeded(LimitKind::CallDepth, format!("Exceeded the maximum call depth of {}.", max)));
+    }
+    self.check_stack()?;
+    self.depth.set(self.depth.get() + 1);
+    let result = f();
+    self.depth.set(self.depth.get() - 1);
+    result
+  }
+
+  pub fn allocate(&self, bytes: usize) -> Result<(), RakiError> {
+    let allocated = self.allocated.get().saturating_add(bytes);
+    self.allocated.set(allocated);
+    match self.limits.borrow().max_allocation {
+      Some(max) if allocated > max => Err(exceeded(LimitKind::Allocation, format!("Exceeded the allocation limit of {} bytes.", max))),
+      _ => Ok(()),
+    }
+  }
+}
+
+fn exceeded(kind: LimitKind, message: String) -> RakiError {
+  RakiError::Limit { kind, message }
+}
+
+// The value's own allocation, not counting what its elements point to
+pub fn shallow_size(value: &Object) -> usize {
+  let slot = mem::size_of::<Object>();
+  match value {
+    Object::String(s) => slot + s.len(),
+    Object::List(list) => slot + list.borrow().len() * slot,
+    Object::Tuple(elements) => slot + elements.len() * slot,
+    Object::Map(map) => slot + map.borrow().len() * 3 * slot,
+    Object::Instance(instance) => slot + instance.borrow().fields.len() * 2 * slot,
+    _ => slot,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn budget(limits: Limits) -> Budget {
+    let budget = Budget { limits: RefCell::new(limits), ..Budget::default() };
+    budget.start();
+    budget
+  }
+
+  #[test]
+  fn counts_steps() {
+    let budget = budget(Limits { max_steps: Some(2), ..Limits::default() });
+    assert!(budget.step().is_ok() && budget.step().is_ok());
+    assert_eq!(budget.step(), Err(exceeded(LimitKind::Steps, "Exceeded the limit of 2 steps.".to_string())));
+
+    budget.start();
+    assert!(budget.step().is_ok());
+  }
+
+  #[test]
+  fn stops_at_the_deadline() {
+    let budget = budget(Limits { timeout: Some(Duration::ZERO), ..Limits::default() });
+    let err = (0..CLOCK_INTERVAL).map(|_| budget.step()).find_map(Result::err);
+    assert!(matches!(err, Some(RakiError::Limit { kind: LimitKind::Time, .. })));
+  }
+
+  #[test]
+  fn limits_call_depth() {
+    let budget = budget(Limits { max_call_depth: 2, ..Limits::default() });
+    assert_eq!(budget.nested(|| budget.nested(|| Ok(1))), Ok(1));
+    let err = budget.nested(|| budget.nested(|| budget.nested(|| Ok(1)))).unwrap_err();
+    assert_eq!(err.to_string(), "CallDepthLimit: Exceeded the maximum call depth of 2.");
+    assert_eq!(budget.depth.get(), 0);
+  }
+
+  #[test]
+  fn limits_the_stack() {
+    let budget = budget(Limits { max_stack: 64 * 1024, ..Limits::default() });
+    fn recurse(budget: &Budget) -> Result<usize, RakiError> {
+      budget.check_stack()?;
+      // Keeps each frame from being optimized away
+      let frame = black_box([0u8; 1024]);
+      recurse(budget).map(|depth| depth + frame[0] as usize)
+    }
+    let err = recurse(&budget).unwrap_err();
+    assert_eq!(err.to_string(), "StackLimit: Exceeded the stack limit of 65536 bytes, the script is nested too deeply.");
+    assert!(budget.check_stack().is_ok());
+  }
+
+  #[test]
+  fn tracks_allocations() {
+    let budget = budget(Limits { max_allocation: Some(100), ..Limits::default() });
+    assert!(budget.allocate(shallow_size(&Object::String("a".repeat(10)))).is_ok());
+    let err = budget.allocate(shallow_size(&Object::String("a".repeat(100)))).unwrap_err();
+    assert!(matches!(err, RakiError::Limit { kind: LimitKind::Allocation, .. }));
+  }
+
+  #[test]
+  fn cancels_from_another_thread() {
+    let budget = budget(Limits::default());
+    let handle = budget.cancel_handle();
+    std::thread::spawn(move || handle.cancel()).join().unwrap();
+    assert_eq!(budget.step(), Err(exceeded(LimitKind::Cancelled, "Execution was cancelled.".to_string())));
+  }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use super::Object;
+
+// Hash map that remembers insertion order, so iteration and printing are
+// deterministic. Keys are expected to be checked with `Object::is_hashable`
+// before they reach the map.
+#[derive(Debug, Clone, Default)]
+pub struct RakiMap {
+  entries: Vec<(Object, Object)>,
+  indices: HashMap<Object, usize>,
+}
+
+impl RakiMap {
+  pub fn new() -> RakiMap {
+    RakiMap::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn get(&self, key: &Object) -> Option<&Object> {
+    self.indices.get(key).map(|&i| &self.entries[i].1)
+  }
+
+  pub fn contains_key(&self, key: &Object) -> bool {
+    self.indices.contains_key(key)
+  }
+
+  // Overwriting an existing key keeps its original position
+  pub fn insert(&mut self, key: Object, value: Object) {
+    match self.indices.get(&key) {
+      Some(&i) => self.entries[i].1 = value,
+      None => {
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+      }
+    }
+  }
+
+  pub fn remove(&mut self, key: &Object) -> Option<Object> {
+    let i = self.indices.remove(key)?;
+    let (_, value) = self.entries.remove(i);
+    for index in self.indices.values_mut() {
+      if *index > i {
+        *index -= 1;
+      }
+    }
+
+    Some(value)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&Object, &Object)> {
+    self.entries.iter().map(|(key, value)| (key, value))
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = &Object> {
+    self.entries.iter().map(|(key, _)| key)
+  }
+
+  pub fn values(&self) -> impl Iterator<Item = &Object> {
+    self.entries.iter().map(|(_, value)| value)
+  }
+}
+
+impl PartialEq for RakiMap {
+  fn eq(&self, other: &Self) -> bool {
+    self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn string(s: &str) -> Object {
+    Object::String(s.to_string())
+  }
+
+  #[test]
+  fn keeps_insertion_order() {
+    let mut map = RakiMap::new();
+    map.insert(string("b"), Object::Double(1.0));
+    map.insert(string("a"), Object::Double(2.0));
+    map.insert(string("b"), Object::Double(3.0));
+
+    let keys: Vec<&Object> = map.keys().collect();
+    assert_eq!(keys, vec![&string("b"), &string("a")]);
+    assert_eq!(map.get(&string("b")), Some(&Object::Double(3.0)));
+  }
+
+  #[test]
+  fn removes_and_reindexes() {
+    let mut map = RakiMap::new();
+    map.insert(string("a"), Object::Double(1.0));
+    map.insert(string("b"), Object::Double(2.0));
+    map.insert(string("c"), Object::Double(3.0));
+
+    assert_eq!(map.remove(&string("a")), Some(Object::Double(1.0)));
+    assert_eq!(map.remove(&string("a")), None);
+    assert_eq!(map.get(&string("c")), Some(&Object::Double(3.0)));
+    assert_eq!(map.len(), 2);
+  }
+
+  #[test]
+  fn compares_regardless_of_order() {
+    let mut left = RakiMap::new();
+    left.insert(Object::Double(1.0), Object::Boolean(true));
+    left.insert(Object::None, Object::Boolean(false));
+
+    let mut right = RakiMap::new();
+    right.insert(Object::None, Object::Boolean(false));
+    right.insert(Object::Double(1.0), Object::Boolean(true));
+
+    assert!(left == right);
+  }
+}
@@ -0,0 +1,152 @@
+use std::f64::consts;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Module, Object, native, number_argument};
+
+type Unary = fn(f64) -> f64;
+type Binary = fn(f64, f64) -> f64;
+type Predicate = fn(f64) -> bool;
+
+const UNARY: [(&str, Unary); 12] = [
+  ("sqrt", f64::sqrt),
+  ("abs", f64::abs),
+  ("floor", f64::floor),
+  ("ceil", f64::ceil),
+  ("round", f64::round),
+  ("sin", f64::sin),
+  ("cos", f64::cos),
+  ("tan", f64::tan),
+  ("log", f64::ln),
+  ("log2", f64::log2),
+  ("log10", f64::log10),
+  ("exp", f64::exp),
+];
+
+const BINARY: [(&str, Binary); 2] = [("pow", f64::powf), ("atan2", f64::atan2)];
+
+// Take any number of arguments
+const FOLDS: [(&str, Binary); 2] = [("min", f64::min), ("max", f64::max)];
+
+const PREDICATES: [(&str, Predicate); 2] = [("is_nan", f64::is_nan), ("is_finite", f64::is_finite)];
+
+const CONSTANTS: [(&str, f64); 4] = [("PI", consts::PI), ("E", consts::E), ("INF", f64::INFINITY), ("NAN", f64::NAN)];
+
+// The built-in `math` namespace. Results follow IEEE 754, so `sqrt(-1)` is NaN
+// rather than an error.
+pub fn math_module() -> Module {
+  let mut members = Vec::new();
+  for (name, f) in UNARY {
+    members.push((name, native(name, Arity::Fixed(1), move |_, arguments| Ok(Object::Double(f(number_argument(name, arguments, 0)?))))));
+  }
+  for (name, f) in BINARY {
+    members.push((name, native(name, Arity::Fixed(2), move |_, arguments| Ok(Object::Double(f(number_argument(name, arguments, 0)?, number_argument(name, arguments, 1)?))))));
+  }
+  for (name, f) in FOLDS {
+    members.push((
+      name,
+      native(name, Arity::Variadic(1), move |_, arguments| {
+        let numbers = (0..arguments.len()).map(|i| number_argument(name, arguments, i)).collect::<Result<Vec<f64>, RakiError>>()?;
+        Ok(Object::Double(numbers.into_iter().reduce(f).unwrap_or(f64::NAN)))
+      }),
+    ));
+  }
+  for (name, f) in PREDICATES {
+    members.push((name, native(name, Arity::Fixed(1), move |_, arguments| Ok(Object::Boolean(f(number_argument(name, arguments, 0)?))))));
+  }
+  members.push(("clamp", native("clamp", Arity::Fixed(3), |_, arguments| clamp(arguments))));
+  for (name, value) in CONSTANTS {
+    members.push((name, Object::Double(value)));
+  }
+
+  Module::builtin("math", members)
+}
+
+fn clamp(arguments: &[Object]) -> Result<Object, RakiError> {
+  let x = number_argument("clamp", arguments, 0)?;
+  let (low, high) = (number_argument("clamp", arguments, 1)?, number_argument("clamp", arguments, 2)?);
+  // `f64::clamp` panics on these
+  if low.is_nan() || high.is_nan() || low > high {
+    let message = format!("clamp() bounds must satisfy low <= high, got {} and {}.", low, high);
+    return Err(RakiError::Runtime { kind: ErrorKind::ValueError, line: 0, message });
+  }
+  Ok(Object::Double(x.clamp(low, high)))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::interpreter::Interpreter;
+
+  fn call(name: &str, arguments: &[f64]) -> Result<Object, RakiError> {
+    let Some(Object::Native(function)) = math_module().get(name) else { panic!("math.{} is not a native function", name) };
+    assert!(function.arity.accepts(arguments.len()));
+    (function.function)(&Interpreter::new(), &arguments.iter().map(|n| Object::Double(*n)).collect::<Vec<Object>>())
+  }
+
+  fn number(name: &str, arguments: &[f64]) -> f64 {
+    match call(name, arguments) {
+      Ok(Object::Double(n)) => n,
+      result => panic!("math.{} returned {:?}", name, result),
+    }
+  }
+
+  #[test]
+  fn computes_roots_powers_and_logarithms() {
+    assert_eq!(number("sqrt", &[16.0]), 4.0);
+    assert!(number("sqrt", &[-1.0]).is_nan());
+    assert_eq!(number("pow", &[2.0, 10.0]), 1024.0);
+    assert_eq!(number("log", &[consts::E]), 1.0);
+    assert_eq!(number("log2", &[8.0]), 3.0);
+    assert_eq!(number("log10", &[1000.0]), 3.0);
+    assert_eq!(number("exp", &[0.0]), 1.0);
+  }
+
+  #[test]
+  fn rounds_numbers() {
+    assert_eq!(number("abs", &[-2.5]), 2.5);
+    assert_eq!(number("floor", &[-1.5]), -2.0);
+    assert_eq!(number("ceil", &[1.2]), 2.0);
+    assert_eq!(number("round", &[2.5]), 3.0);
+    assert_eq!(number("round", &[-2.5]), -3.0);
+  }
+
+  #[test]
+  fn compares_and_clamps() {
+    assert_eq!(number("min", &[3.0, -1.0]), -1.0);
+    assert_eq!(number("max", &[3.0, -1.0]), 3.0);
+    assert_eq!(number("min", &[2.0]), 2.0);
+    assert_eq!(number("max", &[1.0, 7.0, 3.0]), 7.0);
+    assert_eq!(number("clamp", &[5.0, 0.0, 1.0]), 1.0);
+    assert_eq!(number("clamp", &[-5.0, 0.0, 1.0]), 0.0);
+
+    let err = call("clamp", &[1.0, 2.0, 0.0]).unwrap_err();
+    assert_eq!(err, RakiError::Runtime { kind: ErrorKind::ValueError, line: 0, message: "clamp() bounds must satisfy low <= high, got 2 and 0.".to_string() });
+    assert!(call("clamp", &[1.0, f64::NAN, 0.0]).is_err());
+  }
+
+  #[test]
+  fn computes_trigonometry() {
+    assert_eq!(number("sin", &[0.0]), 0.0);
+    assert_eq!(number("cos", &[0.0]), 1.0);
+    assert!((number("tan", &[consts::FRAC_PI_4]) - 1.0).abs() < 1e-12);
+    assert_eq!(number("atan2", &[1.0, 0.0]), consts::FRAC_PI_2);
+  }
+
+  #[test]
+  fn classifies_numbers() {
+    assert_eq!(call("is_nan", &[f64::NAN]), Ok(Object::Boolean(true)));
+    assert_eq!(call("is_nan", &[1.0]), Ok(Object::Boolean(false)));
+    assert_eq!(call("is_finite", &[f64::INFINITY]), Ok(Object::Boolean(false)));
+    assert_eq!(call("is_finite", &[0.5]), Ok(Object::Boolean(true)));
+  }
+
+  #[test]
+  fn defines_constants() {
+    let math = math_module();
+    assert_eq!(math.get("PI"), Some(Object::Double(consts::PI)));
+    assert_eq!(math.get("E"), Some(Object::Double(consts::E)));
+    assert_eq!(math.get("INF"), Some(Object::Double(f64::INFINITY)));
+    assert!(matches!(math.get("NAN"), Some(Object::Double(n)) if n.is_nan()));
+  }
+}
@@ -1,5 +1,52 @@
 pub mod object;
 pub mod interpreter;
+pub mod environment;
+pub mod map;
+pub mod function;
+pub mod class;
+pub mod enumeration;
+pub mod iterator;
+pub mod module;
+pub mod native;
+pub mod convert;
+pub mod userdata;
+pub mod limits;
+pub mod math;
+pub mod io;
+pub mod json;
+pub mod time;
+pub mod random;
+pub mod regex;
+pub mod format;
+pub mod prelude;
+pub mod resolver;
 
-use object::*;
+pub use object::Object;
+use object::print_container;
+use environment::*;
+use map::*;
+use function::*;
+use class::*;
+use enumeration::*;
+use iterator::*;
+pub use module::{Module, analyze};
+pub use native::{Arity, NativeFunction, number_argument};
+use native::native;
+pub use convert::{ConversionError, FromRaki, IntoNative, NativeResult, ToRaki, argument};
+pub use userdata::{Handle, IntoMethod, UserData};
+use userdata::{Method, UserType, getter, native_method, setter};
+pub use limits::{CancelHandle, Limits};
+use limits::{Budget, SLEEP_SLICE, shallow_size};
+use math::*;
+pub use io::Capabilities;
+use io::{fs_module, io_module};
+use json::json_module;
+pub use time::{Clock, FixedClock, SystemClock};
+use time::time_module;
+pub use random::Rng;
+use random::random_module;
+use regex::{re_module, register_regex};
+use format::format;
+use prelude::*;
+pub use resolver::Resolver;
 pub use interpreter::*;
\ No newline at end of file
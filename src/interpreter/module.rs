@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::{
+  checker::Checker,
+  lexer::Scanner,
+  parser::{Expr, Parser, Stmt},
+  raki_log::{RakiError, raki_warn},
+};
+
+use super::{Environment, Object, Resolver};
+
+// A loaded file, with its own global environment
+#[derive(Debug)]
+pub struct Module {
+  pub name: String,
+  pub environment: Rc<RefCell<Environment>>,
+  pub exports: HashSet<String>,
+}
+
+impl Module {
+  pub fn new(name: &str, environment: Environment, statements: &[Stmt]) -> Module {
+    let exports = statements
+      .iter()
+      .filter_map(|stmt| match stmt {
+        Stmt::Export { declaration, .. } => Some(declared_names(declaration)),
+        _ => None,
+      })
+      .flatten()
+      .collect();
+    Module { name: name.to_string(), environment: Rc::new(RefCell::new(environment)), exports }
+  }
+
+  // A module implemented in Rust, exporting all of `members`
+  pub fn builtin(name: &str, members: Vec<(&str, Object)>) -> Module {
+    let mut environment = Environment::new();
+    for (member, value) in &members {
+      environment.define(member, value.clone());
+    }
+    let exports = members.into_iter().map(|(member, _)| member.to_string()).collect();
+    Module { name: name.to_string(), environment: Rc::new(RefCell::new(environment)), exports }
+  }
+
+  // Only exported names are visible from other modules
+  pub fn get(&self, name: &str) -> Option<Object> {
+    match self.exports.contains(name) {
+      true => self.environment.borrow().lookup(name),
+      false => None,
+    }
+  }
+}
+
+fn declared_names(declaration: &Stmt) -> Vec<String> {
+  fn targets(target: &Expr, names: &mut Vec<String>) {
+    match target {
+      Expr::Variable { name } => names.push(name.lexeme.clone()),
+      Expr::Tuple { elements, .. } => elements.iter().for_each(|element| targets(element, names)),
+      _ => {}
+    }
+  }
+
+  match declaration {
+    Stmt::Var { name, .. } | Stmt::Const { name, .. } | Stmt::Function { name, .. } | Stmt::Class { name, .. } | Stmt::Trait { name, .. } | Stmt::Enum { name, .. } => {
+      vec![name.lexeme.clone()]
+    }
+    Stmt::Destructure { target, .. } => {
+      let mut names = Vec::new();
+      targets(target, &mut names);
+      names
+    }
+    _ => Vec::new(),
+  }
+}
+
+// Parses, resolves and type checks `source`, returning every error found
+pub fn analyze(source: String) -> Result<Vec<Stmt>, Vec<RakiError>> {
+  let mut scanner = Scanner::new(source);
+  let mut parser = Parser::new(scanner.scan_tokens().map_err(|err| vec![err])?);
+  let statements = parser.parse_program();
+  if !parser.errors().is_empty() {
+    return Err(parser.errors().to_vec());
+  }
+
+  let resolver = Resolver::new();
+  resolver.resolve(&statements);
+  for warning in resolver.warnings() {
+    raki_warn(&warning);
+  }
+  if !resolver.errors().is_empty() {
+    return Err(resolver.errors());
+  }
+
+  let checker = Checker::new();
+  checker.check(&statements);
+  if !checker.errors().is_empty() {
+    return Err(checker.errors());
+  }
+
+  Ok(statements)
+}
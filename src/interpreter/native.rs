@@ -0,0 +1,83 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::raki_log::RakiError;
+
+use super::{Interpreter, Object, argument};
+
+// Natives get the interpreter so they can call back into Raki functions
+pub type NativeFn = dyn Fn(&Interpreter, &[Object]) -> Result<Object, RakiError>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Arity {
+  Fixed(usize),
+  // At least this many arguments
+  Variadic(usize),
+}
+
+impl Arity {
+  pub fn accepts(&self, count: usize) -> bool {
+    match *self {
+      Arity::Fixed(n) => count == n,
+      Arity::Variadic(min) => count >= min,
+    }
+  }
+}
+
+impl From<usize> for Arity {
+  fn from(n: usize) -> Arity {
+    Arity::Fixed(n)
+  }
+}
+
+impl fmt::Display for Arity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Arity::Fixed(n) => write!(f, "{}", n),
+      Arity::Variadic(min) => write!(f, "at least {}", min),
+    }
+  }
+}
+
+// A built-in function implemented in Rust. Runtime errors it returns without a
+// line are reported on the line of the call.
+pub struct NativeFunction {
+  pub name: String,
+  pub arity: Arity,
+  pub function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+  pub fn new(name: &str, arity: Arity, function: impl Fn(&Interpreter, &[Object]) -> Result<Object, RakiError> + 'static) -> NativeFunction {
+    NativeFunction { name: name.to_string(), arity, function: Box::new(function) }
+  }
+}
+
+impl fmt::Debug for NativeFunction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<native fn {}>", self.name)
+  }
+}
+
+pub fn native(name: &str, arity: Arity, function: impl Fn(&Interpreter, &[Object]) -> Result<Object, RakiError> + 'static) -> Object {
+  Object::Native(Rc::new(NativeFunction::new(name, arity, function)))
+}
+
+pub fn number_argument(function: &str, arguments: &[Object], i: usize) -> Result<f64, RakiError> {
+  argument(function, arguments, i)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn checks_argument_counts() {
+    assert!(Arity::from(2).accepts(2));
+    assert!(!Arity::Fixed(2).accepts(3));
+    assert!(Arity::Variadic(1).accepts(1));
+    assert!(Arity::Variadic(1).accepts(5));
+    assert!(!Arity::Variadic(1).accepts(0));
+    assert_eq!(Arity::Variadic(1).to_string(), "at least 1");
+  }
+}
@@ -1,17 +1,102 @@
+use std::any::Any;
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::ops::{Add, Div, Mul, Sub};
+use std::rc::Rc;
+use std::thread::LocalKey;
 
-use crate::raki_log::RakiError;
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Class, Enum, EnumValue, Function, Instance, Module, NativeFunction, RakiMap, Trait, UserData, Variant};
 
 #[derive(Debug, Clone)]
 pub enum Object {
   Double(f64),
   String(String),
   Boolean(bool),
+  List(Rc<RefCell<Vec<Object>>>),
+  Map(Rc<RefCell<RakiMap>>),
+  Tuple(Rc<Vec<Object>>),
+  Function(Rc<Function>),
+  Native(Rc<NativeFunction>),
+  Class(Rc<Class>),
+  Instance(Rc<RefCell<Instance>>),
+  Trait(Rc<Trait>),
+  Enum(Rc<Enum>),
+  // Calling a variant with fields builds an `EnumValue`
+  Constructor(Rc<Variant>),
+  EnumValue(Rc<EnumValue>),
+  Module(Rc<Module>),
+  UserData(UserData),
+  Range { start: f64, end: f64, inclusive: bool },
   None,
 }
 
+impl Object {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Object::Double(_) => "number",
+      Object::String(_) => "string",
+      Object::Boolean(_) => "bool",
+      Object::List(_) => "list",
+      Object::Map(_) => "map",
+      Object::Tuple(_) => "tuple",
+      Object::Function(_) | Object::Native(_) => "function",
+      Object::Class(_) => "class",
+      Object::Instance(_) => "instance",
+      Object::Trait(_) => "trait",
+      Object::Enum(_) => "enum",
+      Object::Constructor(_) => "function",
+      Object::EnumValue(_) => "enum value",
+      Object::Module(_) => "module",
+      Object::UserData(data) => data.name,
+      Object::Range { .. } => "range",
+      Object::None => "nil",
+    }
+  }
+
+  // Mutable containers and instances can change after insertion and NaN is
+  // never equal to itself, so none of them can be used as a map key
+  pub fn is_hashable(&self) -> bool {
+    match self {
+      Object::Double(d) => !d.is_nan(),
+      Object::String(_) | Object::Boolean(_) | Object::Function(_) | Object::Class(_) | Object::Trait(_) | Object::Enum(_) | Object::Constructor(_) | Object::Module(_) | Object::Native(_) | Object::None => true,
+      Object::EnumValue(value) => value.values.iter().all(Object::is_hashable),
+      Object::Range { start, end, .. } => !start.is_nan() && !end.is_nan(),
+      Object::Tuple(elements) => elements.iter().all(Object::is_hashable),
+      Object::List(_) | Object::Map(_) | Object::Instance(_) | Object::UserData(_) => false,
+    }
+  }
+
+  // How the value is written inside containers, with strings quoted
+  pub fn repr(&self) -> String {
+    Nested(self).to_string()
+  }
+
+  pub fn new_list(elements: Vec<Object>) -> Object {
+    Object::List(Rc::new(RefCell::new(elements)))
+  }
+
+  pub fn new_map(map: RakiMap) -> Object {
+    Object::Map(Rc::new(RefCell::new(map)))
+  }
+
+  pub fn new_tuple(elements: Vec<Object>) -> Object {
+    Object::Tuple(Rc::new(elements))
+  }
+
+  pub fn new_userdata<T: Any>(value: T) -> Object {
+    Object::UserData(UserData::new(value))
+  }
+}
+
+fn operand_error(operator: &str, left: &Object, right: &Object) -> RakiError {
+  RakiError::Runtime { kind: ErrorKind::TypeError, line: 0, message: format!("Unsupported operand types for {}: {} and {}.", operator, left.type_name(), right.type_name()) }
+}
+
 impl Add for Object {
   type Output = Result<Object, RakiError>;
 
@@ -19,7 +104,7 @@ impl Add for Object {
     match (self, rhs) {
       (Object::Double(a), Object::Double(b)) => Ok(Object::Double(a + b)),
       (Object::String(a), Object::String(b)) => Ok(Object::String(a + &b)),
-      _ => Err(RakiError::Runtime {}),
+      (a, b) => Err(operand_error("+", &a, &b)),
     }
   }
 }
@@ -30,7 +115,7 @@ impl Sub for Object {
   fn sub(self, rhs: Self) -> Self::Output {
     match (self, rhs) {
       (Object::Double(a), Object::Double(b)) => Ok(Object::Double(a - b)),
-      _ => Err(RakiError::Runtime {}),
+      (a, b) => Err(operand_error("-", &a, &b)),
     }
   }
 }
@@ -41,7 +126,7 @@ impl Mul for Object {
   fn mul(self, rhs: Self) -> Self::Output {
     match (self, rhs) {
       (Object::Double(a), Object::Double(b)) => Ok(Object::Double(a * b)),
-      _ => Err(RakiError::Runtime {}),
+      (a, b) => Err(operand_error("*", &a, &b)),
     }
   }
 }
@@ -52,23 +137,98 @@ impl Div for Object {
   fn div(self, rhs: Self) -> Self::Output {
     match (self, rhs) {
       (Object::Double(a), Object::Double(b)) => Ok(Object::Double(a / b)),
-      _ => Err(RakiError::Runtime {}),
+      (a, b) => Err(operand_error("/", &a, &b)),
     }
   }
 }
 
+thread_local! {
+  // Containers being printed, and pairs being compared, further up the stack.
+  // Meeting one again means a container holds itself.
+  static PRINTING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+  static COMPARING: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Runs `f` with `key` pushed on `active`, or returns `None` when it's
+// already there
+fn reentrant<K: PartialEq, T>(active: &'static LocalKey<RefCell<Vec<K>>>, key: K, f: impl FnOnce() -> T) -> Option<T> {
+  if active.with_borrow(|keys| keys.contains(&key)) {
+    return None;
+  }
+  active.with_borrow_mut(|keys| keys.push(key));
+  let result = f();
+  active.with_borrow_mut(|keys| keys.pop());
+  Some(result)
+}
+
+// Prints a container unless it's already being printed, in which case it's
+// written as `[...]` or `{...}` like Python does
+pub fn print_container<T>(container: *const (), f: impl FnOnce() -> T) -> Option<T> {
+  reentrant(&PRINTING, container as usize, f)
+}
+
+// A pair met again while comparing is equal unless something else differs
+fn compare_containers<T: PartialEq>(a: &Rc<RefCell<T>>, b: &Rc<RefCell<T>>) -> bool {
+  let pair = (Rc::as_ptr(a) as usize, Rc::as_ptr(b) as usize);
+  Rc::ptr_eq(a, b) || reentrant(&COMPARING, pair, || *a.borrow() == *b.borrow()).unwrap_or(true)
+}
+
 impl PartialEq for Object {
   fn eq(&self, other: &Self) -> bool {
     match (self, other) {
       (Object::Double(a), Object::Double(b)) => a == b,
       (Object::String(a), Object::String(b)) => a == b,
       (Object::Boolean(a), Object::Boolean(b)) => a == b,
+      (Object::List(a), Object::List(b)) => compare_containers(a, b),
+      (Object::Map(a), Object::Map(b)) => compare_containers(a, b),
+      (Object::Tuple(a), Object::Tuple(b)) => a == b,
+      (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+      (Object::Native(a), Object::Native(b)) => Rc::ptr_eq(a, b),
+      (Object::Class(a), Object::Class(b)) => Rc::ptr_eq(a, b),
+      (Object::Instance(a), Object::Instance(b)) => Rc::ptr_eq(a, b),
+      (Object::Trait(a), Object::Trait(b)) => Rc::ptr_eq(a, b),
+      (Object::Enum(a), Object::Enum(b)) => Rc::ptr_eq(a, b),
+      (Object::Module(a), Object::Module(b)) => Rc::ptr_eq(a, b),
+      (Object::UserData(a), Object::UserData(b)) => Rc::ptr_eq(&a.value, &b.value),
+      (Object::Constructor(a), Object::Constructor(b)) => Rc::ptr_eq(a, b),
+      (Object::EnumValue(a), Object::EnumValue(b)) => Rc::ptr_eq(&a.variant, &b.variant) && a.values == b.values,
+      (Object::Range { start: a, end: b, inclusive: c }, Object::Range { start: x, end: y, inclusive: z }) => a == x && b == y && c == z,
       (Object::None, Object::None) => true,
       _ => false,
     }
   }
 }
 
+// Only holds for hashable objects, see `Object::is_hashable`
+impl Eq for Object {}
+
+impl Hash for Object {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    mem::discriminant(self).hash(state);
+    match self {
+      // 0.0 == -0.0, so both have to hash the same
+      Object::Double(d) if *d == 0.0 => 0u64.hash(state),
+      Object::Double(d) => d.to_bits().hash(state),
+      Object::String(s) => s.hash(state),
+      Object::Boolean(b) => b.hash(state),
+      Object::Tuple(elements) => elements.hash(state),
+      Object::Function(function) => Rc::as_ptr(function).hash(state),
+      Object::Native(function) => Rc::as_ptr(function).hash(state),
+      Object::Class(class) => Rc::as_ptr(class).hash(state),
+      Object::Trait(t) => Rc::as_ptr(t).hash(state),
+      Object::Enum(enumeration) => Rc::as_ptr(enumeration).hash(state),
+      Object::Constructor(variant) => Rc::as_ptr(variant).hash(state),
+      Object::Module(module) => Rc::as_ptr(module).hash(state),
+      Object::EnumValue(value) => {
+        Rc::as_ptr(&value.variant).hash(state);
+        value.values.hash(state);
+      }
+      Object::Range { start, end, inclusive } => ((start + 0.0).to_bits(), (end + 0.0).to_bits(), inclusive).hash(state),
+      Object::List(_) | Object::Map(_) | Object::Instance(_) | Object::UserData(_) | Object::None => {}
+    }
+  }
+}
+
 impl PartialOrd for Object {
   fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
     match (self, other) {
@@ -81,13 +241,109 @@ impl PartialOrd for Object {
   }
 }
 
+// Strings nested inside containers are quoted so `["1"]` and `[1]` print
+// differently
+struct Nested<'a>(&'a Object);
+
+impl fmt::Display for Nested<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.0 {
+      Object::String(s) => write!(f, "{:?}", s),
+      obj => write!(f, "{}", obj),
+    }
+  }
+}
+
 impl fmt::Display for Object {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Object::Double(d) => write!(f, "{}", d),
       Object::String(s) => write!(f, "{}", s),
       Object::Boolean(b) => write!(f, "{}", b),
-      Object::None => write!(f, "None"),
+      Object::List(list) => {
+        let elements = print_container(Rc::as_ptr(list) as *const (), || list.borrow().iter().map(|e| Nested(e).to_string()).collect::<Vec<String>>().join(", "));
+        write!(f, "[{}]", elements.as_deref().unwrap_or("..."))
+      }
+      Object::Map(map) => {
+        let entries = print_container(Rc::as_ptr(map) as *const (), || map.borrow().iter().map(|(k, v)| format!("{}: {}", Nested(k), Nested(v))).collect::<Vec<String>>().join(", "));
+        write!(f, "{{{}}}", entries.as_deref().unwrap_or("..."))
+      }
+      Object::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", Nested(&elements[0])),
+      Object::Tuple(elements) => {
+        let elements: Vec<String> = elements.iter().map(|e| Nested(e).to_string()).collect();
+        write!(f, "({})", elements.join(", "))
+      }
+      Object::Function(function) => write!(f, "<fn {}>", function.name),
+      Object::Native(function) => write!(f, "<native fn {}>", function.name),
+      Object::Class(class) => write!(f, "{}", class.name),
+      Object::Instance(instance) => write!(f, "<{} instance>", instance.borrow().class.name),
+      Object::Trait(t) => write!(f, "{}", t.name),
+      Object::Enum(enumeration) => write!(f, "{}", enumeration.name),
+      Object::Module(module) => write!(f, "<module {}>", module.name),
+      Object::UserData(data) => write!(f, "<{}>", data.name),
+      Object::Constructor(variant) => write!(f, "<fn {}.{}>", variant.enum_name, variant.name),
+      Object::EnumValue(value) if value.variant.fields.is_empty() => write!(f, "{}.{}", value.variant.enum_name, value.variant.name),
+      Object::EnumValue(value) => {
+        let values: Vec<String> = value.values.iter().map(|v| Nested(v).to_string()).collect();
+        write!(f, "{}.{}({})", value.variant.enum_name, value.variant.name, values.join(", "))
+      }
+      Object::Range { start, end, inclusive: false } => write!(f, "{}..{}", start, end),
+      Object::Range { start, end, inclusive: true } => write!(f, "{}..={}", start, end),
+      Object::None => write!(f, "nil"),
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use std::collections::hash_map::DefaultHasher;
+
+  use super::*;
+
+  fn hash_of(obj: &Object) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    obj.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  #[test]
+  fn equal_objects_hash_equally() {
+    assert_eq!(hash_of(&Object::Double(0.0)), hash_of(&Object::Double(-0.0)));
+    assert_eq!(hash_of(&Object::String("a".to_string())), hash_of(&Object::String("a".to_string())));
+    assert_ne!(hash_of(&Object::Double(1.0)), hash_of(&Object::Boolean(true)));
+  }
+
+  #[test]
+  fn containers_are_not_hashable() {
+    assert!(!Object::new_list(vec![]).is_hashable());
+    assert!(!Object::new_map(RakiMap::new()).is_hashable());
+    assert!(!Object::Double(f64::NAN).is_hashable());
+    assert!(!Object::new_tuple(vec![Object::None, Object::new_list(vec![])]).is_hashable());
+    assert!(Object::new_tuple(vec![Object::None, Object::Double(1.0)]).is_hashable());
+  }
+
+  #[test]
+  fn prints_cycles_once() {
+    let list = Object::new_list(vec![Object::Double(1.0)]);
+    let Object::List(elements) = &list else { unreachable!() };
+    let map = Object::new_map(RakiMap::new());
+    let Object::Map(entries) = &map else { unreachable!() };
+    entries.borrow_mut().insert(Object::String("self".to_string()), map.clone());
+    elements.borrow_mut().extend([list.clone(), map.clone()]);
+
+    assert_eq!(list.to_string(), "[1, [...], {\"self\": {...}}]");
+    assert_eq!(map.to_string(), "{\"self\": {...}}");
+  }
+
+  #[test]
+  fn compares_cycles() {
+    let cyclic = |first: f64| {
+      let list = Object::new_list(vec![Object::Double(first)]);
+      let Object::List(elements) = &list else { unreachable!() };
+      elements.borrow_mut().push(list.clone());
+      list
+    };
+    assert!(cyclic(1.0) == cyclic(1.0));
+    assert!(cyclic(1.0) != cyclic(2.0));
+  }
+}
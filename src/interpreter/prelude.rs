@@ -0,0 +1,11 @@
+// Raki source every interpreter runs before any user code. Built-in runtime
+// errors are caught as instances of `Error` too.
+pub const PRELUDE: &str = r#"
+class Error {
+  init(message) {
+    this.message = message;
+    this.kind = "Error";
+    this.line = nil;
+  }
+}
+"#;
@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Interpreter, Module, Object, argument, native};
+
+// SplitMix64. Small and fast, and the same seed gives the same sequence on
+// every platform. Not suitable for anything secret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Default for Rng {
+  // Seeded from the system clock
+  fn default() -> Self {
+    Rng::new(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64)
+  }
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Rng {
+    Rng { state: seed }
+  }
+
+  fn next(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  // Uniform in [0, 1)
+  pub fn float(&mut self) -> f64 {
+    (self.next() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  // Uniform in [0, n), rejecting the values that would favour small results
+  pub fn below(&mut self, n: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+      let value = self.next();
+      if value < zone {
+        return value % n;
+      }
+    }
+  }
+}
+
+fn error(kind: ErrorKind, message: String) -> RakiError {
+  RakiError::Runtime { kind, line: 0, message }
+}
+
+fn int(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let low: i64 = argument("int", arguments, 0)?;
+  let high: i64 = argument("int", arguments, 1)?;
+  if low > high {
+    return Err(error(ErrorKind::ValueError, format!("int() needs a lower bound no greater than the upper one, got {} and {}.", low, high)));
+  }
+  // Both fit in 54 bits, so the span can't overflow
+  let value = low + interpreter.rng().below((high - low) as u64 + 1) as i64;
+  Ok(Object::Double(value as f64))
+}
+
+fn choice(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let elements: Vec<Object> = argument("choice", arguments, 0)?;
+  if elements.is_empty() {
+    return Err(error(ErrorKind::IndexError, "choice() from an empty list.".to_string()));
+  }
+  let i = interpreter.rng().below(elements.len() as u64) as usize;
+  Ok(elements[i].clone())
+}
+
+// Fisher-Yates, in place
+fn shuffle(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let Object::List(list) = &arguments[0] else {
+    return Err(error(ErrorKind::TypeError, format!("Argument 1 of shuffle() must be a list, not {}.", arguments[0].type_name())));
+  };
+  let mut elements = list.borrow_mut();
+  for i in (1..elements.len()).rev() {
+    let j = interpreter.rng().below(i as u64 + 1) as usize;
+    elements.swap(i, j);
+  }
+  Ok(Object::None)
+}
+
+// The built-in `random` namespace. Hosts fix the seed with
+// `Interpreter::seed` to make runs reproducible.
+pub fn random_module() -> Module {
+  Module::builtin(
+    "random",
+    vec![
+      (
+        "seed",
+        native("seed", Arity::Fixed(1), |interpreter, arguments| {
+          interpreter.seed(argument::<i64>("seed", arguments, 0)? as u64);
+          Ok(Object::None)
+        }),
+      ),
+      ("int", native("int", Arity::Fixed(2), int)),
+      ("float", native("float", Arity::Fixed(0), |interpreter, _| Ok(Object::Double(interpreter.rng().float())))),
+      ("choice", native("choice", Arity::Fixed(1), choice)),
+      ("shuffle", native("shuffle", Arity::Fixed(1), shuffle)),
+    ],
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn repeats_sequences_for_a_seed() {
+    let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+    let first: Vec<u64> = (0..5).map(|_| a.next()).collect();
+    assert_eq!(first, (0..5).map(|_| b.next()).collect::<Vec<u64>>());
+    assert_ne!(first, (0..5).map(|_| Rng::new(43).next()).collect::<Vec<u64>>());
+    // Reference output of SplitMix64
+    assert_eq!(Rng::new(0).next(), 0xE220_A839_7B1D_CDAF);
+  }
+
+  #[test]
+  fn stays_in_range() {
+    let mut rng = Rng::new(7);
+    let mut seen = [false; 6];
+    for _ in 0..1000 {
+      seen[rng.below(6) as usize] = true;
+      let x = rng.float();
+      assert!((0.0..1.0).contains(&x));
+    }
+    assert!(seen.iter().all(|&seen| seen));
+    assert_eq!(rng.below(1), 0);
+  }
+}
@@ -0,0 +1,857 @@
+use std::fmt;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Interpreter, Module, NativeResult, Object, RakiMap, argument, native};
+
+// Bounds what `{n,m}` can expand to
+const MAX_REPEAT: usize = 1000;
+const MAX_PROGRAM: usize = 100_000;
+// Deeper groups are rejected rather than risking the native stack
+const MAX_DEPTH: usize = 256;
+// States a search remembers, 8 MiB of bits. Longer texts are matched without
+// remembering, giving up after as many steps.
+const MAX_STATES: usize = 1 << 26;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternError {
+  // In characters from the start of the pattern
+  pub offset: usize,
+  pub message: String,
+}
+
+impl fmt::Display for PatternError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "at offset {}: {}", self.offset, self.message)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Perl {
+  Digit,
+  Word,
+  Space,
+}
+
+impl Perl {
+  fn matches(self, c: char) -> bool {
+    match self {
+      Perl::Digit => c.is_ascii_digit(),
+      Perl::Word => is_word(c),
+      Perl::Space => c.is_whitespace(),
+    }
+  }
+}
+
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+  Range(char, char),
+  // `\d`, `\w` and `\s`, or their negations
+  Perl(Perl, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CharClass {
+  negated: bool,
+  items: Vec<ClassItem>,
+}
+
+impl CharClass {
+  fn matches(&self, c: char) -> bool {
+    let found = self.items.iter().any(|item| match item {
+      ClassItem::Range(low, high) => (*low..=*high).contains(&c),
+      ClassItem::Perl(perl, negated) => perl.matches(c) != *negated,
+    });
+    found != self.negated
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+  Start,
+  End,
+  WordBoundary,
+  NotWordBoundary,
+}
+
+#[derive(Debug)]
+enum Node {
+  Char(char),
+  Any,
+  Class(CharClass),
+  Assert(Assertion),
+  // Capturing groups have an index, counted from 1
+  Group(Box<Node>, Option<usize>),
+  Concat(Vec<Node>),
+  Alternate(Vec<Node>),
+  Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool, offset: usize },
+}
+
+// What an escape like `\n`, `\d` or `\b` stands for
+enum Escape {
+  Char(char),
+  Perl(Perl, bool),
+  Assert(Assertion),
+}
+
+struct PatternParser {
+  pattern: Vec<char>,
+  current: usize,
+  groups: usize,
+  names: Vec<(String, usize)>,
+  // Groups open around the current position
+  depth: usize,
+}
+
+impl PatternParser {
+  fn error<T>(&self, offset: usize, message: &str) -> Result<T, PatternError> {
+    Err(PatternError { offset, message: message.to_string() })
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.pattern.get(self.current).copied()
+  }
+
+  fn eat(&mut self, c: char) -> bool {
+    let found = self.peek() == Some(c);
+    if found {
+      self.current += 1;
+    }
+    found
+  }
+
+  fn parse(&mut self) -> Result<Node, PatternError> {
+    let node = self.alternation()?;
+    match self.peek() {
+      Some(')') => self.error(self.current, "Unmatched ')'."),
+      _ => Ok(node),
+    }
+  }
+
+  fn alternation(&mut self) -> Result<Node, PatternError> {
+    let mut alternatives = vec![self.concat()?];
+    while self.eat('|') {
+      alternatives.push(self.concat()?);
+    }
+    Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Node::Alternate(alternatives) })
+  }
+
+  fn concat(&mut self) -> Result<Node, PatternError> {
+    let mut nodes = Vec::new();
+    while !matches!(self.peek(), None | Some('|' | ')')) {
+      let offset = self.current;
+      let atom = self.atom()?;
+      nodes.push(self.quantified(atom, offset)?);
+    }
+    Ok(if nodes.len() == 1 { nodes.remove(0) } else { Node::Concat(nodes) })
+  }
+
+  fn quantified(&mut self, node: Node, offset: usize) -> Result<Node, PatternError> {
+    let quantifier = self.current;
+    let (min, max) = match self.peek() {
+      Some('{') => match self.counts()? {
+        Some(counts) => counts,
+        None => return Ok(node),
+      },
+      Some(c @ ('*' | '+' | '?')) => {
+        self.current += 1;
+        (if c == '+' { 1 } else { 0 }, if c == '?' { Some(1) } else { None })
+      }
+      _ => return Ok(node),
+    };
+    let greedy = !self.eat('?');
+    if matches!(node, Node::Assert(_)) {
+      return self.error(quantifier, "Nothing to repeat.");
+    }
+    Ok(Node::Repeat { node: Box::new(node), min, max, greedy, offset })
+  }
+
+  // `{n}`, `{n,}` or `{n,m}`. Anything else is a literal '{'.
+  fn counts(&mut self) -> Result<Option<(usize, Option<usize>)>, PatternError> {
+    let start = self.current;
+    self.current += 1;
+    let min = self.count();
+    let max = if self.eat(',') { self.count() } else { min };
+    let (Some(min), true) = (min, self.eat('}')) else {
+      self.current = start;
+      return Ok(None);
+    };
+    if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+      return self.error(start, &format!("Repeat counts can't be more than {}.", MAX_REPEAT));
+    }
+    if max.is_some_and(|max| max < min) {
+      return self.error(start, "Repeat range is backwards.");
+    }
+    Ok(Some((min, max)))
+  }
+
+  fn count(&mut self) -> Option<usize> {
+    let start = self.current;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.current += 1;
+    }
+    let digits: String = self.pattern[start..self.current].iter().collect();
+    (!digits.is_empty()).then(|| digits.parse().unwrap_or(usize::MAX))
+  }
+
+  fn atom(&mut self) -> Result<Node, PatternError> {
+    let offset = self.current;
+    let c = self.peek().expect("concat stops at the end");
+    self.current += 1;
+    match c {
+      '(' => self.group(offset),
+      '[' => self.class(offset).map(Node::Class),
+      '.' => Ok(Node::Any),
+      '^' => Ok(Node::Assert(Assertion::Start)),
+      '$' => Ok(Node::Assert(Assertion::End)),
+      '*' | '+' | '?' => self.error(offset, "Nothing to repeat."),
+      '\\' => match self.escape(offset)? {
+        Escape::Char(c) => Ok(Node::Char(c)),
+        Escape::Perl(perl, negated) => Ok(Node::Class(CharClass { negated: false, items: vec![ClassItem::Perl(perl, negated)] })),
+        Escape::Assert(assertion) => Ok(Node::Assert(assertion)),
+      },
+      c => Ok(Node::Char(c)),
+    }
+  }
+
+  fn group(&mut self, offset: usize) -> Result<Node, PatternError> {
+    if self.depth == MAX_DEPTH {
+      return self.error(offset, &format!("Groups are nested deeper than {} levels.", MAX_DEPTH));
+    }
+    let index = if self.eat('?') {
+      if self.eat(':') {
+        None
+      } else if self.eat('<') || (self.eat('P') && self.eat('<')) {
+        let name = self.group_name()?;
+        if self.names.iter().any(|(other, _)| *other == name) {
+          return self.error(offset, &format!("Duplicate group name '{}'.", name));
+        }
+        self.groups += 1;
+        self.names.push((name, self.groups));
+        Some(self.groups)
+      } else {
+        return self.error(self.current, "Unknown group syntax after '(?'.");
+      }
+    } else {
+      self.groups += 1;
+      Some(self.groups)
+    };
+
+    self.depth += 1;
+    let node = self.alternation()?;
+    self.depth -= 1;
+    if !self.eat(')') {
+      return self.error(offset, "Missing ')'.");
+    }
+    Ok(Node::Group(Box::new(node), index))
+  }
+
+  fn group_name(&mut self) -> Result<String, PatternError> {
+    let start = self.current;
+    while self.peek().is_some_and(is_word) {
+      self.current += 1;
+    }
+    let name: String = self.pattern[start..self.current].iter().collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || !self.eat('>') {
+      return self.error(start, "Invalid group name.");
+    }
+    Ok(name)
+  }
+
+  fn escape(&mut self, offset: usize) -> Result<Escape, PatternError> {
+    let Some(c) = self.peek() else {
+      return self.error(offset, "Pattern ends with a lone '\\'.");
+    };
+    self.current += 1;
+    Ok(match c {
+      'd' | 'D' => Escape::Perl(Perl::Digit, c == 'D'),
+      'w' | 'W' => Escape::Perl(Perl::Word, c == 'W'),
+      's' | 'S' => Escape::Perl(Perl::Space, c == 'S'),
+      'b' => Escape::Assert(Assertion::WordBoundary),
+      'B' => Escape::Assert(Assertion::NotWordBoundary),
+      'n' => Escape::Char('\n'),
+      'r' => Escape::Char('\r'),
+      't' => Escape::Char('\t'),
+      'f' => Escape::Char('\u{c}'),
+      'v' => Escape::Char('\u{b}'),
+      '0' => Escape::Char('\0'),
+      c if c.is_alphanumeric() => return self.error(offset, &format!("Unknown escape '\\{}'.", c)),
+      c => Escape::Char(c),
+    })
+  }
+
+  fn class(&mut self, offset: usize) -> Result<CharClass, PatternError> {
+    let negated = self.eat('^');
+    let mut items = Vec::new();
+    // A ']' right after the opening bracket is literal
+    let mut first = true;
+    loop {
+      let start = self.current;
+      let low = match self.peek() {
+        None => return self.error(offset, "Missing ']'."),
+        Some(']') if !first => {
+          self.current += 1;
+          return Ok(CharClass { negated, items });
+        }
+        Some(_) => match self.class_char()? {
+          Escape::Char(c) => c,
+          Escape::Perl(perl, negated) => {
+            items.push(ClassItem::Perl(perl, negated));
+            first = false;
+            continue;
+          }
+          Escape::Assert(_) => unreachable!("class_char has no assertions"),
+        },
+      };
+      first = false;
+
+      // A '-' before the closing bracket is literal
+      if self.peek() == Some('-') && !matches!(self.pattern.get(self.current + 1), None | Some(']')) {
+        self.current += 1;
+        let high = match self.class_char()? {
+          Escape::Char(c) => c,
+          _ => return self.error(start, "Ranges must be between characters."),
+        };
+        if high < low {
+          return self.error(start, &format!("Range '{}-{}' is backwards.", low, high));
+        }
+        items.push(ClassItem::Range(low, high));
+      } else {
+        items.push(ClassItem::Range(low, low));
+      }
+    }
+  }
+
+  fn class_char(&mut self) -> Result<Escape, PatternError> {
+    let offset = self.current;
+    let c = self.peek().expect("class checks for the end");
+    self.current += 1;
+    if c != '\\' {
+      return Ok(Escape::Char(c));
+    }
+    match self.escape(offset)? {
+      // `\b` is a backspace inside a class
+      Escape::Assert(_) if self.pattern[self.current - 1] == 'b' => Ok(Escape::Char('\u{8}')),
+      Escape::Assert(_) => self.error(offset, "Unknown escape '\\B' in a character class."),
+      escape => Ok(escape),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+  Char(char),
+  Any,
+  Class(CharClass),
+  Assert(Assertion),
+  // Tries the first target, backtracking to the second
+  Split(usize, usize),
+  Jump(usize),
+  // Records the position in a capture slot
+  Save(usize),
+  Match,
+}
+
+struct Compiler {
+  program: Vec<Inst>,
+}
+
+impl Compiler {
+  fn emit(&mut self, inst: Inst) -> usize {
+    self.program.push(inst);
+    self.program.len() - 1
+  }
+
+  fn compile(&mut self, node: &Node) -> Result<(), PatternError> {
+    match node {
+      Node::Char(c) => _ = self.emit(Inst::Char(*c)),
+      Node::Any => _ = self.emit(Inst::Any),
+      Node::Class(class) => _ = self.emit(Inst::Class(class.clone())),
+      Node::Assert(assertion) => _ = self.emit(Inst::Assert(*assertion)),
+      Node::Group(node, None) => self.compile(node)?,
+      Node::Group(node, Some(index)) => {
+        self.emit(Inst::Save(index * 2));
+        self.compile(node)?;
+        self.emit(Inst::Save(index * 2 + 1));
+      }
+      Node::Concat(nodes) => {
+        for node in nodes {
+          self.compile(node)?;
+        }
+      }
+      Node::Alternate(alternatives) => {
+        let mut jumps = Vec::new();
+        for (i, alternative) in alternatives.iter().enumerate() {
+          if i == alternatives.len() - 1 {
+            self.compile(alternative)?;
+            break;
+          }
+          let split = self.emit(Inst::Split(0, 0));
+          self.compile(alternative)?;
+          jumps.push(self.emit(Inst::Jump(0)));
+          self.program[split] = Inst::Split(split + 1, self.program.len());
+        }
+        let end = self.program.len();
+        for jump in jumps {
+          self.program[jump] = Inst::Jump(end);
+        }
+      }
+      Node::Repeat { node, min, max, greedy, offset } => {
+        for _ in 0..*min {
+          self.compile(node)?;
+          self.check_size(*offset)?;
+        }
+        let order = |next: usize, end: usize| if *greedy { Inst::Split(next, end) } else { Inst::Split(end, next) };
+        match max {
+          None => {
+            let split = self.emit(Inst::Split(0, 0));
+            self.compile(node)?;
+            self.emit(Inst::Jump(split));
+            self.program[split] = order(split + 1, self.program.len());
+          }
+          Some(max) => {
+            let mut splits = Vec::new();
+            for _ in *min..*max {
+              splits.push(self.emit(Inst::Split(0, 0)));
+              self.compile(node)?;
+              self.check_size(*offset)?;
+            }
+            let end = self.program.len();
+            for split in splits {
+              self.program[split] = order(split + 1, end);
+            }
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn check_size(&self, offset: usize) -> Result<(), PatternError> {
+    match self.program.len() > MAX_PROGRAM {
+      true => Err(PatternError { offset, message: "Pattern is too large once repeats are expanded.".to_string() }),
+      false => Ok(()),
+    }
+  }
+}
+
+// Start and end of each group, the whole match first, in characters
+type Slots = Vec<Option<usize>>;
+
+// The (instruction, position) states a search has tried. Only the words that
+// were written are cleared, so searching again costs what the last search did
+// rather than the size of the whole set. With more than `MAX_STATES` states
+// the set stays empty and only counts steps.
+struct Visited {
+  bits: Vec<u64>,
+  dirty: Vec<usize>,
+  steps: usize,
+}
+
+impl Visited {
+  fn new(states: usize) -> Visited {
+    Visited { bits: vec![0; Visited::words(states)], dirty: Vec::new(), steps: 0 }
+  }
+
+  fn words(states: usize) -> usize {
+    if states <= MAX_STATES { states.div_ceil(64) } else { 0 }
+  }
+
+  // Whether `state` is new. Without a set every state is, until the search
+  // has taken too many steps.
+  fn insert(&mut self, state: usize) -> Result<bool, RakiError> {
+    if self.bits.is_empty() {
+      self.steps += 1;
+      if self.steps > MAX_STATES {
+        return Err(RakiError::Runtime { kind: ErrorKind::ValueError, line: 0, message: format!("Gave up matching after {} steps, the text is too long.", MAX_STATES) });
+      }
+      return Ok(true);
+    }
+    let (word, bit) = (state / 64, 1 << (state % 64));
+    if self.bits[word] & bit != 0 {
+      return Ok(false);
+    }
+    if self.bits[word] == 0 {
+      self.dirty.push(word);
+    }
+    self.bits[word] |= bit;
+    Ok(true)
+  }
+
+  fn clear(&mut self) {
+    for word in self.dirty.drain(..) {
+      self.bits[word] = 0;
+    }
+  }
+}
+
+enum Job {
+  Thread(usize, usize),
+  Restore(usize, Option<usize>),
+}
+
+// A compiled pattern. Matching backtracks in priority order like Perl, but
+// never tries an instruction at the same position twice, so it takes time
+// proportional to the pattern times the text. Searches too big to remember
+// every state give up after `MAX_STATES` steps instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+  pattern: String,
+  program: Vec<Inst>,
+  // Capture groups, not counting the whole match
+  groups: usize,
+  names: Vec<(String, usize)>,
+}
+
+impl Regex {
+  pub fn new(pattern: &str) -> Result<Regex, PatternError> {
+    let mut parser = PatternParser { pattern: pattern.chars().collect(), current: 0, groups: 0, names: Vec::new(), depth: 0 };
+    let node = parser.parse()?;
+    let mut compiler = Compiler { program: vec![Inst::Save(0)] };
+    compiler.compile(&node)?;
+    compiler.emit(Inst::Save(1));
+    compiler.emit(Inst::Match);
+    Ok(Regex { pattern: pattern.to_string(), program: compiler.program, groups: parser.groups, names: parser.names })
+  }
+
+  fn assert(assertion: Assertion, text: &[char], pos: usize) -> bool {
+    let word_before = pos > 0 && is_word(text[pos - 1]);
+    let word_after = pos < text.len() && is_word(text[pos]);
+    match assertion {
+      Assertion::Start => pos == 0,
+      Assertion::End => pos == text.len(),
+      Assertion::WordBoundary => word_before != word_after,
+      Assertion::NotWordBoundary => word_before == word_after,
+    }
+  }
+
+  fn states(&self, text: &[char]) -> usize {
+    self.program.len().saturating_mul(text.len() + 1)
+  }
+
+  // The set for searching `text`, charged to the allocation budget
+  fn visited(&self, interpreter: &Interpreter, text: &[char]) -> Result<Visited, RakiError> {
+    interpreter.charge(Visited::words(self.states(text)) * 8)?;
+    Ok(Visited::new(self.states(text)))
+  }
+
+  // The first match starting at `from` or, unless `anchored`, anywhere after.
+  // `visited` has to be clear.
+  fn find_at(&self, text: &[char], from: usize, anchored: bool, visited: &mut Visited) -> Result<Option<Slots>, RakiError> {
+    // A state that failed once fails again whatever the captures, even from a
+    // later start
+    let width = text.len() + 1;
+    let mut slots: Slots = vec![None; (self.groups + 1) * 2];
+    let mut stack = Vec::new();
+    let last = if anchored { from } else { text.len() };
+
+    for start in from..=last {
+      stack.push(Job::Thread(0, start));
+      while let Some(job) = stack.pop() {
+        let (mut pc, mut pos) = match job {
+          Job::Thread(pc, pos) => (pc, pos),
+          Job::Restore(slot, value) => {
+            slots[slot] = value;
+            continue;
+          }
+        };
+        loop {
+          if !visited.insert(pc * width + pos)? {
+            break;
+          }
+
+          match &self.program[pc] {
+            Inst::Char(c) if text.get(pos) == Some(c) => pos += 1,
+            Inst::Any if text.get(pos).is_some_and(|c| *c != '\n') => pos += 1,
+            Inst::Class(class) if text.get(pos).is_some_and(|c| class.matches(*c)) => pos += 1,
+            Inst::Assert(assertion) if Regex::assert(*assertion, text, pos) => {}
+            Inst::Char(_) | Inst::Any | Inst::Class(_) | Inst::Assert(_) => break,
+            Inst::Split(first, second) => {
+              stack.push(Job::Thread(*second, pos));
+              pc = *first;
+              continue;
+            }
+            Inst::Jump(target) => {
+              pc = *target;
+              continue;
+            }
+            Inst::Save(slot) => {
+              stack.push(Job::Restore(*slot, slots[*slot]));
+              slots[*slot] = Some(pos);
+            }
+            Inst::Match => return Ok(Some(slots)),
+          }
+          pc += 1;
+        }
+      }
+    }
+    Ok(None)
+  }
+
+  // Matches that don't overlap, left to right. An empty match moves the
+  // search on by one character.
+  fn find_all(&self, interpreter: &Interpreter, text: &[char]) -> Result<Vec<Slots>, RakiError> {
+    let mut matches = Vec::new();
+    let mut visited = self.visited(interpreter, text)?;
+    let mut from = 0;
+    while from <= text.len() {
+      // States seen before the last match may still succeed from here
+      visited.clear();
+      let Some(slots) = self.find_at(text, from, false, &mut visited)? else {
+        break;
+      };
+      let (start, end) = (slots[0].expect("matches have a start"), slots[1].expect("matches have an end"));
+      from = if end == start { end + 1 } else { end };
+      matches.push(slots);
+    }
+    Ok(matches)
+  }
+
+  // A map with the matched `text`, its `start` and `end`, the `groups` as a
+  // list and the named groups in `named`. Groups that took no part are nil.
+  fn match_object(&self, text: &[char], slots: &[Option<usize>]) -> Object {
+    let group = |i: usize| match (slots[i * 2], slots[i * 2 + 1]) {
+      (Some(start), Some(end)) => Object::String(text[start..end].iter().collect()),
+      _ => Object::None,
+    };
+    let mut named = RakiMap::new();
+    for (name, index) in &self.names {
+      named.insert(Object::String(name.clone()), group(*index));
+    }
+
+    let mut map = RakiMap::new();
+    map.insert(Object::String("text".to_string()), group(0));
+    map.insert(Object::String("start".to_string()), Object::Double(slots[0].unwrap_or_default() as f64));
+    map.insert(Object::String("end".to_string()), Object::Double(slots[1].unwrap_or_default() as f64));
+    map.insert(Object::String("groups".to_string()), Object::new_list((1..=self.groups).map(group).collect()));
+    map.insert(Object::String("named".to_string()), Object::new_map(named));
+    Object::new_map(map)
+  }
+
+  // Python's `match`: only at the start of the text
+  pub fn match_start(&self, interpreter: &Interpreter, text: &str) -> Result<Option<Object>, RakiError> {
+    let text: Vec<char> = text.chars().collect();
+    Ok(self.find_at(&text, 0, true, &mut self.visited(interpreter, &text)?)?.map(|slots| self.match_object(&text, &slots)))
+  }
+
+  pub fn search(&self, interpreter: &Interpreter, text: &str) -> Result<Option<Object>, RakiError> {
+    let text: Vec<char> = text.chars().collect();
+    Ok(self.find_at(&text, 0, false, &mut self.visited(interpreter, &text)?)?.map(|slots| self.match_object(&text, &slots)))
+  }
+
+  pub fn find_all_objects(&self, interpreter: &Interpreter, text: &str) -> Result<Vec<Object>, RakiError> {
+    let text: Vec<char> = text.chars().collect();
+    Ok(self.find_all(interpreter, &text)?.iter().map(|slots| self.match_object(&text, slots)).collect())
+  }
+
+  // Replaces every match. `$1` or `${1}` inserts a group, `${name}` a named
+  // group and `$$` a dollar sign. Groups that don't exist or took no part
+  // insert nothing.
+  pub fn replace(&self, interpreter: &Interpreter, text: &str, replacement: &str) -> Result<String, RakiError> {
+    let text: Vec<char> = text.chars().collect();
+    let mut result = String::new();
+    let mut last = 0;
+    for slots in self.find_all(interpreter, &text)? {
+      let (start, end) = (slots[0].unwrap_or_default(), slots[1].unwrap_or_default());
+      result.extend(&text[last..start]);
+      self.expand(replacement, &text, &slots, &mut result);
+      last = end;
+    }
+    result.extend(&text[last..]);
+    Ok(result)
+  }
+
+  fn expand(&self, replacement: &str, text: &[char], slots: &[Option<usize>], result: &mut String) {
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+      if c != '$' {
+        result.push(c);
+        continue;
+      }
+      let reference = match chars.peek() {
+        Some('$') => {
+          chars.next();
+          result.push('$');
+          continue;
+        }
+        Some('{') => {
+          chars.next();
+          let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+          name
+        }
+        Some(c) if c.is_ascii_digit() => {
+          let mut digits = String::new();
+          while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+          }
+          digits
+        }
+        _ => {
+          result.push('$');
+          continue;
+        }
+      };
+      let index = reference.parse().ok().or_else(|| self.names.iter().find(|(name, _)| *name == reference).map(|(_, index)| *index));
+      if let Some(index) = index.filter(|index| *index <= self.groups)
+        && let (Some(start), Some(end)) = (slots[index * 2], slots[index * 2 + 1])
+      {
+        result.extend(&text[start..end]);
+      }
+    }
+  }
+
+  pub fn split(&self, interpreter: &Interpreter, text: &str) -> Result<Vec<String>, RakiError> {
+    let text: Vec<char> = text.chars().collect();
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for slots in self.find_all(interpreter, &text)? {
+      let (start, end) = (slots[0].unwrap_or_default(), slots[1].unwrap_or_default());
+      // Empty matches at the ends would only add empty pieces
+      if start == end && (start == 0 || start == text.len()) {
+        continue;
+      }
+      pieces.push(text[last..start].iter().collect());
+      last = end;
+    }
+    pieces.push(text[last..].iter().collect());
+    Ok(pieces)
+  }
+}
+
+fn compile(_: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let pattern: String = argument("compile", arguments, 0)?;
+  let regex = Regex::new(&pattern).map_err(|err| RakiError::Runtime { kind: ErrorKind::ValueError, line: 0, message: format!("Invalid pattern '{}' {}", pattern, err) })?;
+  Ok(Object::new_userdata(regex))
+}
+
+// The built-in `re` namespace. `re.compile` returns `Regex` userdata, whose
+// methods are registered by `register_regex`.
+pub fn re_module() -> Module {
+  Module::builtin("re", vec![("compile", native("compile", Arity::Fixed(1), compile))])
+}
+
+// Searches charge the interpreter's budget, so they are native methods
+pub fn register_regex(interpreter: &Interpreter) {
+  interpreter.register_native_method("match", 1, |interpreter, regex: &mut Regex, arguments| regex.match_start(interpreter, &argument::<String>("match", arguments, 0)?).into_result());
+  interpreter.register_native_method("search", 1, |interpreter, regex: &mut Regex, arguments| regex.search(interpreter, &argument::<String>("search", arguments, 0)?).into_result());
+  interpreter.register_native_method("find_all", 1, |interpreter, regex: &mut Regex, arguments| regex.find_all_objects(interpreter, &argument::<String>("find_all", arguments, 0)?).into_result());
+  interpreter.register_native_method("replace", 2, |interpreter, regex: &mut Regex, arguments| {
+    let (text, replacement): (String, String) = (argument("replace", arguments, 0)?, argument("replace", arguments, 1)?);
+    regex.replace(interpreter, &text, &replacement).into_result()
+  });
+  interpreter.register_native_method("split", 1, |interpreter, regex: &mut Regex, arguments| regex.split(interpreter, &argument::<String>("split", arguments, 0)?).into_result());
+  interpreter.register_getter("pattern", |regex: &Regex| regex.pattern.clone());
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // The text of the first match, or None
+  fn search(pattern: &str, text: &str) -> Option<String> {
+    let regex = Regex::new(pattern).unwrap();
+    let text: Vec<char> = text.chars().collect();
+    regex.find_at(&text, 0, false, &mut Visited::new(regex.states(&text))).unwrap().map(|slots| text[slots[0].unwrap()..slots[1].unwrap()].iter().collect())
+  }
+
+  fn error(pattern: &str) -> String {
+    Regex::new(pattern).unwrap_err().to_string()
+  }
+
+  #[test]
+  fn matches_literals_classes_and_anchors() {
+    assert_eq!(search("b+c", "abbbcd"), Some("bbbc".to_string()));
+    assert_eq!(search("^b", "abc"), None);
+    assert_eq!(search("c$", "abc"), Some("c".to_string()));
+    assert_eq!(search("[a-c]{2}\\d", "xab1"), Some("ab1".to_string()));
+    assert_eq!(search("[^a-z ]+", "abc DEF1 gh"), Some("DEF1".to_string()));
+    assert_eq!(search("[]x-]+", "a]-x"), Some("]-x".to_string()));
+    assert_eq!(search("\\bcat\\b", "concat cat"), Some("cat".to_string()));
+    assert_eq!(search("a.c", "a\nc abc"), Some("abc".to_string()));
+    assert_eq!(search("\\w+@\\w+\\.com", "mail ann@raki.com now"), Some("ann@raki.com".to_string()));
+    assert_eq!(search("x{2,3}", "xxxxx"), Some("xxx".to_string()));
+    assert_eq!(search("x{2,}?", "xxxxx"), Some("xx".to_string()));
+    assert_eq!(search("a{,2}", "a{,2}"), Some("a{,2}".to_string()));
+  }
+
+  #[test]
+  fn prefers_earlier_alternatives() {
+    assert_eq!(search("ab|abc", "abc"), Some("ab".to_string()));
+    assert_eq!(search("(a|ab)(c|bcd)", "abcd"), Some("abcd".to_string()));
+    assert_eq!(search("<.+?>", "<a><b>"), Some("<a>".to_string()));
+    assert_eq!(search("(a*)*b", "aaab"), Some("aaab".to_string()));
+    // Would take exponential time without remembering failed states
+    assert_eq!(search("(a|aa)+$", &format!("{}b", "a".repeat(5000))), None);
+  }
+
+  #[test]
+  fn captures_groups() {
+    let interpreter = Interpreter::new();
+    let regex = Regex::new("(?<year>\\d{4})-(\\d\\d)(x)?").unwrap();
+    let found = regex.search(&interpreter, "on 2024-06!").unwrap().unwrap();
+    assert_eq!(found.to_string(), "{\"text\": \"2024-06\", \"start\": 3, \"end\": 10, \"groups\": [\"2024\", \"06\", nil], \"named\": {\"year\": \"2024\"}}");
+    assert_eq!(regex.replace(&interpreter, "2024-06 and 1999-12", "$2/${year}$$"), Ok("06/2024$ and 12/1999$".to_string()));
+    assert_eq!(regex.match_start(&interpreter, "x 2024-06"), Ok(None));
+  }
+
+  #[test]
+  fn finds_and_splits() {
+    let interpreter = Interpreter::new();
+    let regex = Regex::new("x*").unwrap();
+    assert_eq!(regex.replace(&interpreter, "abxd", "-"), Ok("-a-b--d-".to_string()));
+    assert_eq!(Regex::new(",\\s*").unwrap().split(&interpreter, "a, b,c"), Ok(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+    assert_eq!(Regex::new("").unwrap().split(&interpreter, "abc"), Ok(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+    assert_eq!(Regex::new("\\d").unwrap().find_all_objects(&interpreter, "a1b22").unwrap().len(), 3);
+  }
+
+  #[test]
+  fn reports_the_error_offset() {
+    assert_eq!(error("ab(c"), "at offset 2: Missing ')'.");
+    assert_eq!(error("ab)c"), "at offset 2: Unmatched ')'.");
+    assert_eq!(error("a**"), "at offset 2: Nothing to repeat.");
+    assert_eq!(error("x[a-"), "at offset 1: Missing ']'.");
+    assert_eq!(error("[z-a]"), "at offset 1: Range 'z-a' is backwards.");
+    assert_eq!(error("a{3,1}"), "at offset 1: Repeat range is backwards.");
+    assert_eq!(error("\\q"), "at offset 0: Unknown escape '\\q'.");
+    assert_eq!(error("(?<a>x)(?<a>y)"), "at offset 7: Duplicate group name 'a'.");
+    assert_eq!(error("ab\\"), "at offset 2: Pattern ends with a lone '\\'.");
+    assert_eq!(error("(a{1000}){1000}"), "at offset 1: Pattern is too large once repeats are expanded.");
+  }
+
+  #[test]
+  fn limits_group_nesting() {
+    let nested = |depth: usize| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(search(&nested(MAX_DEPTH), "a"), Some("a".to_string()));
+    assert_eq!(error(&nested(MAX_DEPTH + 1)), "at offset 256: Groups are nested deeper than 256 levels.");
+    assert_eq!(error(&"(".repeat(100_000)), "at offset 256: Groups are nested deeper than 256 levels.");
+  }
+
+  #[test]
+  fn reuses_visited_states_between_matches() {
+    let mut visited = Visited::new(200);
+    assert_eq!((visited.insert(3), visited.insert(130), visited.insert(3)), (Ok(true), Ok(true), Ok(false)));
+    visited.clear();
+    assert!(visited.bits.iter().all(|word| *word == 0) && visited.insert(3) == Ok(true));
+
+    // The first match ends where the second, empty one does
+    assert_eq!(Regex::new("a|").unwrap().replace(&Interpreter::new(), "ab", "-"), Ok("--b-".to_string()));
+  }
+
+  #[test]
+  fn bounds_searches_too_big_to_remember() {
+    // Too many states to remember, but the match is found right away
+    let (regex, text) = (Regex::new("x{1000}").unwrap(), "x".repeat(100_000));
+    assert!(Visited::new(regex.states(&text.chars().collect::<Vec<char>>())).bits.is_empty());
+    assert_eq!(search("x{1000}", &text).map(|found| found.len()), Some(1000));
+
+    let mut visited = Visited::new(usize::MAX);
+    visited.steps = MAX_STATES - 1;
+    assert_eq!(visited.insert(0), Ok(true));
+    let err = visited.insert(0).unwrap_err();
+    assert!(matches!(err, RakiError::Runtime { kind: ErrorKind::ValueError, message, .. } if message == "Gave up matching after 67108864 steps, the text is too long."));
+  }
+}
@@ -0,0 +1,402 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::lexer::{LiteralType, Token};
+use crate::parser::{Expr, MatchArm, Pattern, Stmt, StmtVisitor, Visitor};
+use crate::raki_log::{RakiError, RakiWarning};
+
+struct Binding {
+  line: u32,
+  constant: bool,
+}
+
+// Static pass over a parsed program, run before it is interpreted. Scopes
+// mirror the environments the interpreter creates, starting with globals.
+pub struct Resolver {
+  scopes: RefCell<Vec<HashMap<String, Binding>>>,
+  // Whether each enclosing class has a superclass, for checking `super`
+  classes: RefCell<Vec<bool>>,
+  warnings: RefCell<Vec<RakiWarning>>,
+  errors: RefCell<Vec<RakiError>>,
+}
+
+impl Default for Resolver {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Resolver {
+  pub fn new() -> Resolver {
+    Resolver { scopes: RefCell::new(vec![HashMap::new()]), classes: RefCell::new(Vec::new()), warnings: RefCell::new(Vec::new()), errors: RefCell::new(Vec::new()) }
+  }
+
+  pub fn resolve(&self, statements: &[Stmt]) {
+    for stmt in statements {
+      self.visit_stmt(stmt);
+    }
+  }
+
+  pub fn warnings(&self) -> Vec<RakiWarning> {
+    self.warnings.borrow().clone()
+  }
+
+  pub fn errors(&self) -> Vec<RakiError> {
+    self.errors.borrow().clone()
+  }
+
+  fn error(&self, token: &Token, message: &str) {
+    self.errors.borrow_mut().push(RakiError::Syntax { line: token.line, at: format!(" at {}", token.lexeme), message: message.to_string() });
+  }
+
+  fn warn(&self, line: u32, message: &str) {
+    self.warnings.borrow_mut().push(RakiWarning { line, message: message.to_string() });
+  }
+
+  fn scoped(&self, f: impl FnOnce()) {
+    self.scopes.borrow_mut().push(HashMap::new());
+    f();
+    self.scopes.borrow_mut().pop();
+  }
+
+  fn declare(&self, name: &Token, constant: bool) {
+    let mut scopes = self.scopes.borrow_mut();
+    let scope = scopes.last_mut().expect("globals scope");
+    scope.insert(name.lexeme.clone(), Binding { line: name.line, constant });
+  }
+
+  fn declare_target(&self, target: &Expr) {
+    match target {
+      Expr::Variable { name } => self.declare(name, false),
+      Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.declare_target(element)),
+      expr => self.visit_expr(expr),
+    }
+  }
+
+  fn declare_pattern(&self, pattern: &Pattern) {
+    match pattern {
+      Pattern::Binding { name } => self.declare(name, false),
+      Pattern::Wildcard | Pattern::Literal { .. } | Pattern::Range { .. } => {}
+      Pattern::Value { value } => self.visit_expr(value),
+      Pattern::List { elements, rest } => {
+        elements.iter().for_each(|element| self.declare_pattern(element));
+        if let Some(rest) = rest {
+          self.declare_pattern(rest);
+        }
+      }
+      Pattern::Tuple { elements } => elements.iter().for_each(|element| self.declare_pattern(element)),
+      Pattern::Map { entries } => entries.iter().for_each(|(_, pattern)| self.declare_pattern(pattern)),
+      Pattern::Class { path, fields, .. } => {
+        self.visit_expr(path);
+        fields.iter().for_each(|field| self.declare_pattern(field));
+      }
+    }
+  }
+
+  // Names the resolver never saw declared are globals from the prelude or
+  // earlier REPL lines, the interpreter checks those
+  fn check_assign(&self, name: &Token) {
+    let scopes = self.scopes.borrow();
+    let Some(binding) = scopes.iter().rev().find_map(|scope| scope.get(&name.lexeme)) else {
+      return;
+    };
+
+    if binding.constant {
+      self.error(name, &format!("Cannot assign to constant '{}' declared on line {}.", name.lexeme, binding.line));
+    }
+  }
+
+  fn check_target(&self, target: &Expr) {
+    match target {
+      Expr::Variable { name } => self.check_assign(name),
+      Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.check_target(element)),
+      expr => self.visit_expr(expr),
+    }
+  }
+
+  fn resolve_methods(&self, methods: &[Stmt], has_superclass: bool) {
+    self.classes.borrow_mut().push(has_superclass);
+    for method in methods {
+      match method {
+        Stmt::Function { params, body, .. } => self.resolve_function(params, body),
+        Stmt::Var { initializer: Some(initializer), .. } => self.visit_expr(initializer),
+        _ => {}
+      }
+    }
+    self.classes.borrow_mut().pop();
+  }
+
+  fn resolve_function(&self, params: &[Token], body: &[Stmt]) {
+    self.scoped(|| {
+      params.iter().for_each(|param| self.declare(param, false));
+      self.resolve(body);
+    });
+  }
+
+  fn resolve_arms(&self, arms: &[MatchArm]) {
+    for (i, arm) in arms.iter().enumerate() {
+      // Guarded arms may fall through, so they never hide later ones
+      let covering = arms[..i].iter().find(|earlier| earlier.guard.is_none() && covers(&earlier.pattern, &arm.pattern));
+      if let Some(earlier) = covering {
+        self.warn(arm.arrow.line, &format!("Unreachable match arm, already covered by the arm on line {}.", earlier.arrow.line));
+      }
+
+      self.scoped(|| {
+        self.declare_pattern(&arm.pattern);
+        if let Some(guard) = &arm.guard {
+          self.visit_expr(guard);
+        }
+        self.visit_expr(&arm.body);
+      });
+    }
+  }
+}
+
+// Whether every value matching `later` also matches `earlier`
+fn covers(earlier: &Pattern, later: &Pattern) -> bool {
+  match (earlier, later) {
+    (Pattern::Wildcard | Pattern::Binding { .. }, _) => true,
+    (Pattern::Literal { value: a }, Pattern::Literal { value: b }) => a == b,
+    (Pattern::Range { start, end, inclusive }, Pattern::Literal { value: LiteralType::F64(n) }) => start <= n && (n < end || *inclusive && n == end),
+    (Pattern::Range { start: a_start, end: a_end, inclusive: a_inclusive }, Pattern::Range { start: b_start, end: b_end, inclusive: b_inclusive }) => {
+      a_start <= b_start && (b_end < a_end || b_end == a_end && (*a_inclusive || !*b_inclusive))
+    }
+    (Pattern::Value { value: a }, Pattern::Value { value: b }) => same_path(a, b),
+    (Pattern::List { elements: a, rest: a_rest }, Pattern::List { elements: b, rest: b_rest }) => {
+      let fits = match a_rest {
+        Some(_) => b.len() >= a.len(),
+        None => b_rest.is_none() && b.len() == a.len(),
+      };
+      fits && a.iter().zip(b).all(|(a, b)| covers(a, b))
+    }
+    (Pattern::Tuple { elements: a }, Pattern::Tuple { elements: b }) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| covers(a, b)),
+    (Pattern::Map { entries: a }, Pattern::Map { entries: b }) => {
+      a.iter().all(|(key, a)| b.iter().any(|(other, b)| key == other && covers(a, b)))
+    }
+    (Pattern::Class { path: a_path, fields: a, .. }, Pattern::Class { path: b_path, fields: b, .. }) => {
+      same_path(a_path, b_path) && a.len() <= b.len() && a.iter().zip(b).all(|(a, b)| covers(a, b))
+    }
+    _ => false,
+  }
+}
+
+fn same_path(a: &Expr, b: &Expr) -> bool {
+  match (a, b) {
+    (Expr::Variable { name: a }, Expr::Variable { name: b }) => a.lexeme == b.lexeme,
+    (Expr::Get { object: a_object, name: a }, Expr::Get { object: b_object, name: b }) => a.lexeme == b.lexeme && same_path(a_object, b_object),
+    _ => false,
+  }
+}
+
+impl Visitor<()> for Resolver {
+  fn visit_expr(&self, expr: &Expr) {
+    match expr {
+      Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } => {}
+      Expr::Super { keyword, .. } => {
+        if self.classes.borrow().last() != Some(&true) {
+          self.error(keyword, "Can't use 'super' outside of a subclass.");
+        }
+      }
+      Expr::Grouping { expr } => self.visit_expr(expr),
+      Expr::Unary { right, .. } => self.visit_expr(right),
+      Expr::Assign { name, value } => {
+        self.visit_expr(value);
+        self.check_assign(name);
+      }
+      Expr::Destructure { target, value, .. } => {
+        self.visit_expr(value);
+        self.check_target(target);
+      }
+      Expr::Get { object, .. } => self.visit_expr(object),
+      Expr::Binary { left, right, .. } | Expr::Range { start: left, end: right, .. } | Expr::Index { object: left, index: right, .. } | Expr::Set { object: left, value: right, .. } => {
+        self.visit_expr(left);
+        self.visit_expr(right);
+      }
+      Expr::Ternary { condition, left, right, .. } | Expr::IndexSet { object: condition, index: left, value: right, .. } => {
+        self.visit_expr(condition);
+        self.visit_expr(left);
+        self.visit_expr(right);
+      }
+      Expr::List { elements } | Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.visit_expr(element)),
+      Expr::Map { entries, .. } => {
+        for (key, value) in entries {
+          self.visit_expr(key);
+          self.visit_expr(value);
+        }
+      }
+      Expr::Call { callee, arguments, .. } => {
+        self.visit_expr(callee);
+        arguments.iter().for_each(|argument| self.visit_expr(argument));
+      }
+      Expr::Lambda { params, body, .. } => self.resolve_function(params, body),
+      Expr::Match { value, arms, .. } => {
+        self.visit_expr(value);
+        self.resolve_arms(arms);
+      }
+    }
+  }
+}
+
+impl StmtVisitor<()> for Resolver {
+  fn visit_stmt(&self, stmt: &Stmt) {
+    match stmt {
+      Stmt::Expression { expr } | Stmt::Throw { value: expr, .. } => self.visit_expr(expr),
+      Stmt::Print { values, sep, end, .. } => values.iter().chain(sep).chain(end).for_each(|expr| self.visit_expr(expr)),
+      Stmt::Var { name, initializer, .. } => {
+        initializer.iter().for_each(|initializer| self.visit_expr(initializer));
+        self.declare(name, false);
+      }
+      Stmt::Const { name, initializer, .. } => {
+        self.visit_expr(initializer);
+        self.declare(name, true);
+      }
+      Stmt::Return { value, .. } => value.iter().for_each(|value| self.visit_expr(value)),
+      Stmt::Destructure { target, initializer, .. } => {
+        self.visit_expr(initializer);
+        self.declare_target(target);
+      }
+      Stmt::Block { statements } => self.scoped(|| self.resolve(statements)),
+      Stmt::Function { name, params, body, .. } => {
+        self.declare(name, false);
+        self.resolve_function(params, body);
+      }
+      Stmt::Class { name, superclass, traits, methods, getters, setters, statics } => {
+        if let Some(Expr::Variable { name: superclass }) = superclass
+          && superclass.lexeme == name.lexeme
+        {
+          self.error(superclass, "A class can't inherit from itself.");
+        }
+        self.declare(name, false);
+        traits.iter().for_each(|t| self.visit_expr(t));
+        for members in [methods, getters, setters] {
+          self.resolve_methods(members, superclass.is_some());
+        }
+        // Class methods are bound to the class, which has no `super`
+        self.resolve_methods(statics, false);
+      }
+      Stmt::Trait { name, defaults, .. } => {
+        self.declare(name, false);
+        self.resolve_methods(defaults, false);
+      }
+      Stmt::Enum { name, .. } => self.declare(name, false),
+      Stmt::Import { alias, names, keyword, .. } => {
+        if self.scopes.borrow().len() > 1 {
+          self.error(keyword, "Imports must be at the top level of a module.");
+        }
+        alias.iter().chain(names).for_each(|name| self.declare(name, false));
+      }
+      Stmt::Export { declaration, keyword } => {
+        if self.scopes.borrow().len() > 1 {
+          self.error(keyword, "Exports must be at the top level of a module.");
+        }
+        self.visit_stmt(declaration);
+      }
+      Stmt::If { condition, then_branch, else_branch, .. } => {
+        self.visit_expr(condition);
+        self.visit_stmt(then_branch);
+        if let Some(else_branch) = else_branch {
+          self.visit_stmt(else_branch);
+        }
+      }
+      Stmt::While { condition, body, .. } => {
+        self.visit_expr(condition);
+        self.visit_stmt(body);
+      }
+      Stmt::ForIn { target, iterable, body, .. } => {
+        self.visit_expr(iterable);
+        self.scoped(|| {
+          self.declare_target(target);
+          self.visit_stmt(body);
+        });
+      }
+      Stmt::Try { body, catch, finally } => {
+        self.scoped(|| self.resolve(body));
+        if let Some((name, handler)) = catch {
+          self.scoped(|| {
+            self.declare(name, false);
+            self.resolve(handler);
+          });
+        }
+        if let Some(finally) = finally {
+          self.scoped(|| self.resolve(finally));
+        }
+      }
+      Stmt::Break | Stmt::Continue => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lexer::Scanner;
+  use crate::parser::Parser;
+
+  use super::*;
+
+  fn warnings(source: &str) -> Vec<RakiWarning> {
+    let mut scanner = Scanner::new(source.to_string());
+    let statements = Parser::new(scanner.scan_tokens().unwrap()).parse_program();
+    let resolver = Resolver::new();
+    resolver.resolve(&statements);
+    resolver.warnings()
+  }
+
+  #[test]
+  fn warns_on_unreachable_arms() {
+    let source = "var x = match 3 {\n  n => n,\n  0 => 1,\n};";
+    assert_eq!(warnings(source), vec![RakiWarning { line: 3, message: "Unreachable match arm, already covered by the arm on line 2.".to_string() }]);
+
+    let source = "var x = match [1] {\n  0..=9 => 0,\n  5 => 1,\n  [a, ...rest] => 2,\n  [a, b] => 3,\n};";
+    let lines: Vec<u32> = warnings(source).iter().map(|warning| warning.line).collect();
+    assert_eq!(lines, vec![3, 5]);
+  }
+
+  fn errors(source: &str) -> Vec<RakiError> {
+    let mut scanner = Scanner::new(source.to_string());
+    let statements = Parser::new(scanner.scan_tokens().unwrap()).parse_program();
+    let resolver = Resolver::new();
+    resolver.resolve(&statements);
+    resolver.errors()
+  }
+
+  #[test]
+  fn rejects_assignments_to_constants() {
+    let err = RakiError::Syntax { line: 3, at: " at x".to_string(), message: "Cannot assign to constant 'x' declared on line 1.".to_string() };
+    assert_eq!(errors("const x = 1;\nfun f() {\n  x = 2;\n}"), vec![err]);
+
+    let err = RakiError::Syntax { line: 2, at: " at y".to_string(), message: "Cannot assign to constant 'y' declared on line 1.".to_string() };
+    assert_eq!(errors("let y = 1; var z = 0;\n(z, y) = (1, 2);"), vec![err]);
+  }
+
+  #[test]
+  fn rejects_nested_imports() {
+    let err = RakiError::Syntax { line: 1, at: " at import".to_string(), message: "Imports must be at the top level of a module.".to_string() };
+    assert_eq!(errors("fun f() { import \"m.raki\" as m; }"), vec![err]);
+
+    let err = RakiError::Syntax { line: 1, at: " at export".to_string(), message: "Exports must be at the top level of a module.".to_string() };
+    assert_eq!(errors("{ export var x = 1; }"), vec![err]);
+    assert!(errors("from \"m.raki\" import a; a = 1;").is_empty());
+  }
+
+  #[test]
+  fn rejects_misplaced_super() {
+    let err = RakiError::Syntax { line: 1, at: " at super".to_string(), message: "Can't use 'super' outside of a subclass.".to_string() };
+    assert_eq!(errors("class A { f() { return super.f(); } }"), vec![err]);
+    let err = RakiError::Syntax { line: 1, at: " at super".to_string(), message: "Can't use 'super' outside of a subclass.".to_string() };
+    assert_eq!(errors("class A {} class B < A { class f() { return super.f(); } }"), vec![err]);
+
+    let err = RakiError::Syntax { line: 1, at: " at A".to_string(), message: "A class can't inherit from itself.".to_string() };
+    assert_eq!(errors("class A < A {}"), vec![err]);
+  }
+
+  #[test]
+  fn allows_assigning_shadowed_constants() {
+    assert!(errors("const x = 1; { var x = 2; x = 3; } fun f(x) { x = 4; } for (x in [1]) { x = 5; } var g = (x) => x = 6;").is_empty());
+    assert!(errors("const xs = [1]; xs[0] = 2; var y = match 1 { x => x = 3 };").is_empty());
+  }
+
+  #[test]
+  fn guarded_arms_do_not_hide_later_ones() {
+    assert!(warnings("var x = match 3 { n if n > 1 => n, 0..10 => 1, 10 => 2, _ => 3 };").is_empty());
+  }
+}
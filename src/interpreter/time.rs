@@ -0,0 +1,275 @@
+use std::cell::Cell;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{Arity, Interpreter, Module, Object, argument, native, number_argument};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Where scripts get the time from. Hosts install a `FixedClock` so runs that
+// read the time are reproducible.
+pub trait Clock {
+  // Seconds since an arbitrary start, never going backwards
+  fn monotonic(&self) -> f64;
+  // Seconds since the Unix epoch
+  fn timestamp(&self) -> f64;
+  fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock {
+  start: Instant,
+}
+
+impl Default for SystemClock {
+  fn default() -> Self {
+    SystemClock { start: Instant::now() }
+  }
+}
+
+impl Clock for SystemClock {
+  fn monotonic(&self) -> f64 {
+    self.start.elapsed().as_secs_f64()
+  }
+
+  fn timestamp(&self) -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+  }
+
+  fn sleep(&self, duration: Duration) {
+    thread::sleep(duration);
+  }
+}
+
+// Starts at a given timestamp and only moves when the script sleeps, which
+// returns immediately
+pub struct FixedClock {
+  start: f64,
+  elapsed: Cell<Duration>,
+}
+
+impl FixedClock {
+  pub fn new(timestamp: f64) -> FixedClock {
+    FixedClock { start: timestamp, elapsed: Cell::new(Duration::ZERO) }
+  }
+}
+
+impl Clock for FixedClock {
+  fn monotonic(&self) -> f64 {
+    self.elapsed.get().as_secs_f64()
+  }
+
+  fn timestamp(&self) -> f64 {
+    self.start + self.monotonic()
+  }
+
+  fn sleep(&self, duration: Duration) {
+    self.elapsed.set(self.elapsed.get() + duration);
+  }
+}
+
+fn error(kind: ErrorKind, message: String) -> RakiError {
+  RakiError::Runtime { kind, line: 0, message }
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date, after Howard Hinnant's
+// `days_from_civil`
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days - era * 146_097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+  match month {
+    2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+// Like `2024-03-01T12:30:00Z`, with milliseconds when there are any
+pub fn format_iso(timestamp: f64) -> Result<String, RakiError> {
+  let millis = (timestamp * 1000.0).round();
+  // Years 0 through 9999
+  if !(-62_167_219_200_000.0..253_402_300_800_000.0).contains(&millis) {
+    return Err(error(ErrorKind::ValueError, format!("Timestamp {} is out of range.", timestamp)));
+  }
+  let millis = millis as i64;
+  let seconds = millis.div_euclid(1000);
+  let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+  let time = seconds.rem_euclid(SECONDS_PER_DAY);
+  let mut text = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60);
+  if millis.rem_euclid(1000) != 0 {
+    text.push_str(&format!(".{:03}", millis.rem_euclid(1000)));
+  }
+  text.push('Z');
+  Ok(text)
+}
+
+struct IsoParser<'a> {
+  text: &'a [u8],
+  current: usize,
+}
+
+impl IsoParser<'_> {
+  fn eat(&mut self, c: u8) -> bool {
+    let found = self.text.get(self.current) == Some(&c);
+    if found {
+      self.current += 1;
+    }
+    found
+  }
+
+  fn number(&mut self, digits: usize) -> Option<i64> {
+    let slice = self.text.get(self.current..self.current + digits)?;
+    if !slice.iter().all(u8::is_ascii_digit) {
+      return None;
+    }
+    self.current += digits;
+    Some(slice.iter().fold(0, |n, digit| n * 10 + (digit - b'0') as i64))
+  }
+
+  fn fraction(&mut self) -> Option<f64> {
+    let start = self.current;
+    while self.text.get(self.current).is_some_and(u8::is_ascii_digit) {
+      self.current += 1;
+    }
+    let digits = std::str::from_utf8(&self.text[start..self.current]).ok()?;
+    if digits.is_empty() {
+      return None;
+    }
+    format!("0.{}", digits).parse().ok()
+  }
+
+  // Offset from UTC in seconds. Times without one are taken as UTC.
+  fn offset(&mut self) -> Option<i64> {
+    if self.eat(b'Z') || self.current == self.text.len() {
+      return Some(0);
+    }
+    let sign = if self.eat(b'+') {
+      1
+    } else if self.eat(b'-') {
+      -1
+    } else {
+      return None;
+    };
+    let hours = self.number(2)?;
+    self.eat(b':');
+    let minutes = self.number(2)?;
+    (hours < 24 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+  }
+
+  fn timestamp(&mut self) -> Option<f64> {
+    let year = self.number(4)?;
+    let month = self.eat(b'-').then(|| self.number(2))??;
+    let day = self.eat(b'-').then(|| self.number(2))??;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+      return None;
+    }
+    let mut seconds = (days_from_civil(year, month, day) * SECONDS_PER_DAY) as f64;
+
+    if self.eat(b'T') {
+      let hour = self.number(2)?;
+      let minute = self.eat(b':').then(|| self.number(2))??;
+      let second = if self.eat(b':') { self.number(2)? } else { 0 };
+      if hour > 23 || minute > 59 || second > 59 {
+        return None;
+      }
+      seconds += (hour * 3600 + minute * 60 + second) as f64;
+      if self.eat(b'.') {
+        seconds += self.fraction()?;
+      }
+      seconds -= self.offset()? as f64;
+    }
+    (self.current == self.text.len()).then_some(seconds)
+  }
+}
+
+// Seconds since the Unix epoch of a date like `2024-03-01` or a date and time
+// like `2024-03-01T12:30:00.5+02:00`
+pub fn parse_iso(text: &str) -> Result<f64, RakiError> {
+  IsoParser { text: text.as_bytes(), current: 0 }.timestamp().ok_or_else(|| error(ErrorKind::ValueError, format!("Invalid ISO-8601 timestamp '{}'.", text)))
+}
+
+fn sleep(interpreter: &Interpreter, arguments: &[Object]) -> Result<Object, RakiError> {
+  let seconds = number_argument("sleep", arguments, 0)?;
+  let duration = Duration::try_from_secs_f64(seconds).map_err(|_| error(ErrorKind::ValueError, format!("Argument 1 of sleep() must be a non-negative number of seconds, not {}.", seconds)))?;
+  interpreter.sleep(duration)?;
+  Ok(Object::None)
+}
+
+// The built-in `time` namespace. Everything goes through the interpreter's
+// clock, see `Interpreter::set_clock`.
+pub fn time_module() -> Module {
+  Module::builtin(
+    "time",
+    vec![
+      ("now", native("now", Arity::Fixed(0), |interpreter, _| Ok(Object::Double(interpreter.clock().monotonic())))),
+      ("timestamp", native("timestamp", Arity::Fixed(0), |interpreter, _| Ok(Object::Double(interpreter.clock().timestamp())))),
+      ("sleep", native("sleep", Arity::Fixed(1), sleep)),
+      ("format", native("format", Arity::Fixed(1), |_, arguments| format_iso(number_argument("format", arguments, 0)?).map(Object::String))),
+      ("parse", native("parse", Arity::Fixed(1), |_, arguments| parse_iso(&argument::<String>("parse", arguments, 0)?).map(Object::Double))),
+    ],
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn formats_timestamps() {
+    assert_eq!(format_iso(0.0), Ok("1970-01-01T00:00:00Z".to_string()));
+    assert_eq!(format_iso(951_827_696.25), Ok("2000-02-29T12:34:56.250Z".to_string()));
+    assert_eq!(format_iso(-1.0), Ok("1969-12-31T23:59:59Z".to_string()));
+    assert_eq!(format_iso(253_402_300_799.0), Ok("9999-12-31T23:59:59Z".to_string()));
+    assert!(format_iso(253_402_300_800.0).is_err());
+    assert!(format_iso(f64::NAN).is_err());
+  }
+
+  #[test]
+  fn parses_timestamps() {
+    assert_eq!(parse_iso("1970-01-01"), Ok(0.0));
+    assert_eq!(parse_iso("2000-02-29T12:34:56.25Z"), Ok(951_827_696.25));
+    assert_eq!(parse_iso("2000-02-29T14:34:56.25+02:00"), Ok(951_827_696.25));
+    assert_eq!(parse_iso("2000-02-29T12:04-0030"), Ok(951_827_640.0));
+    assert_eq!(parse_iso("2024-06-01T08:00"), Ok(1_717_228_800.0));
+
+    for invalid in ["2023-02-29", "2024-13-01", "2024-01-01T24:00", "2024-01-01T10", "2024-01-01T10:00Zjunk", "2024-1-01", "01/02/2024", ""] {
+      assert_eq!(parse_iso(invalid), Err(error(ErrorKind::ValueError, format!("Invalid ISO-8601 timestamp '{}'.", invalid))));
+    }
+  }
+
+  #[test]
+  fn round_trips_dates() {
+    for days in (-800_000..800_000).step_by(997) {
+      let (year, month, day) = civil_from_days(days);
+      assert_eq!(days_from_civil(year, month, day), days);
+    }
+  }
+
+  #[test]
+  fn fixed_clocks_advance_when_sleeping() {
+    let clock = FixedClock::new(1_000.0);
+    clock.sleep(Duration::from_millis(1500));
+    assert_eq!((clock.monotonic(), clock.timestamp()), (1.5, 1_001.5));
+  }
+}
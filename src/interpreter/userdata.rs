@@ -0,0 +1,221 @@
+use std::any::{self, Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::raki_log::{ErrorKind, RakiError};
+
+use super::{ConversionError, FromRaki, Interpreter, NativeResult, Object, ToRaki, argument};
+
+// A Rust value handed to scripts by the host. Its methods and properties are
+// registered per Rust type on the interpreter.
+#[derive(Clone)]
+pub struct UserData {
+  pub name: &'static str,
+  pub type_id: TypeId,
+  pub value: Rc<RefCell<dyn Any>>,
+}
+
+impl UserData {
+  pub fn new<T: Any>(value: T) -> UserData {
+    UserData { name: short_name::<T>(), type_id: TypeId::of::<T>(), value: Rc::new(RefCell::new(value)) }
+  }
+
+  pub fn is<T: Any>(&self) -> bool {
+    self.type_id == TypeId::of::<T>()
+  }
+
+  // Methods can reach the same value again through their arguments, so a
+  // value that is already borrowed is an error rather than a panic
+  fn borrow<T: Any>(&self) -> Result<Ref<'_, T>, RakiError> {
+    let value = self.value.try_borrow().map_err(|_| self.in_use())?;
+    Ok(Ref::map(value, |value| value.downcast_ref().expect("userdata has the registered type")))
+  }
+
+  fn borrow_mut<T: Any>(&self) -> Result<RefMut<'_, T>, RakiError> {
+    let value = self.value.try_borrow_mut().map_err(|_| self.in_use())?;
+    Ok(RefMut::map(value, |value| value.downcast_mut().expect("userdata has the registered type")))
+  }
+
+  fn in_use(&self) -> RakiError {
+    RakiError::Runtime { kind: ErrorKind::RuntimeError, line: 0, message: format!("{} is already in use.", self.name) }
+  }
+}
+
+impl fmt::Debug for UserData {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<{}>", self.name)
+  }
+}
+
+// `Connection` rather than `my_crate::db::Connection`
+fn short_name<T: Any>() -> &'static str {
+  let name = any::type_name::<T>();
+  let path = name.split('<').next().unwrap_or(name);
+  path.rsplit("::").next().unwrap_or(path)
+}
+
+pub type Method = dyn Fn(&Interpreter, &UserData, &[Object]) -> Result<Object, RakiError>;
+pub type Getter = dyn Fn(&UserData) -> Result<Object, RakiError>;
+pub type Setter = dyn Fn(&UserData, &Object) -> Result<(), RakiError>;
+
+// Everything registered for one Rust type
+#[derive(Default)]
+pub struct UserType {
+  pub methods: HashMap<String, (usize, Rc<Method>)>,
+  pub getters: HashMap<String, Rc<Getter>>,
+  pub setters: HashMap<String, Rc<Setter>>,
+}
+
+// A userdata argument that must hold a `T`, so natives can take host objects
+pub struct Handle<T> {
+  data: UserData,
+  marker: PhantomData<T>,
+}
+
+// Like `UserData`, a handle to a value that is already borrowed, such as the
+// receiver of the method that was passed it, gives an error
+impl<T: Any> Handle<T> {
+  pub fn borrow(&self) -> Result<Ref<'_, T>, RakiError> {
+    self.data.borrow()
+  }
+
+  pub fn borrow_mut(&self) -> Result<RefMut<'_, T>, RakiError> {
+    self.data.borrow_mut()
+  }
+}
+
+impl<T: Any> FromRaki for Handle<T> {
+  fn from_raki(value: &Object) -> Result<Handle<T>, ConversionError> {
+    match value {
+      Object::UserData(data) if data.is::<T>() => Ok(Handle { data: data.clone(), marker: PhantomData }),
+      _ => Err(ConversionError::new(short_name::<T>(), value)),
+    }
+  }
+}
+
+impl<T> ToRaki for Handle<T> {
+  fn to_raki(self) -> Object {
+    Object::UserData(self.data)
+  }
+}
+
+// A typed Rust method on `T`, like `|conn: &mut Connection, sql: String| ...`
+pub trait IntoMethod<T, Args> {
+  const ARITY: usize;
+
+  fn call_method(&self, name: &str, receiver: &UserData, arguments: &[Object]) -> Result<Object, RakiError>;
+}
+
+macro_rules! into_method {
+  ($len:expr; $($name:ident $i:tt),*) => {
+    impl<F, T, R, $($name),*> IntoMethod<T, ($($name,)*)> for F
+    where
+      F: Fn(&mut T, $($name),*) -> R,
+      T: Any,
+      R: NativeResult,
+      $($name: FromRaki),*
+    {
+      const ARITY: usize = $len;
+
+      #[allow(unused_variables)]
+      fn call_method(&self, name: &str, receiver: &UserData, arguments: &[Object]) -> Result<Object, RakiError> {
+        // Converted before the receiver is borrowed, so a type error in an
+        // argument is reported as one. The receiver passed back in as a
+        // `Handle` is in use while the method runs.
+        let arguments = ($(argument::<$name>(name, arguments, $i)?,)*);
+        self(&mut *receiver.borrow_mut::<T>()?, $(arguments.$i),*).into_result()
+      }
+    }
+  };
+}
+
+into_method!(0;);
+into_method!(1; A 0);
+into_method!(2; A 0, B 1);
+into_method!(3; A 0, B 1, C 2);
+into_method!(4; A 0, B 1, C 2, D 3);
+
+// A method that also gets the interpreter, for built-in types that charge
+// its budget
+pub fn native_method<T: Any>(function: impl Fn(&Interpreter, &mut T, &[Object]) -> Result<Object, RakiError> + 'static) -> Rc<Method> {
+  Rc::new(move |interpreter, receiver, arguments| function(interpreter, &mut *receiver.borrow_mut::<T>()?, arguments))
+}
+
+pub fn getter<T: Any, R: ToRaki>(function: impl Fn(&T) -> R + 'static) -> Rc<Getter> {
+  Rc::new(move |receiver| Ok(function(&*receiver.borrow::<T>()?).to_raki()))
+}
+
+pub fn setter<T: Any, V: FromRaki>(name: &str, function: impl Fn(&mut T, V) + 'static) -> Rc<Setter> {
+  let name = name.to_string();
+  Rc::new(move |receiver, value| {
+    let value = V::from_raki(value).map_err(|err| RakiError::Runtime {
+      kind: err.kind,
+      line: 0,
+      message: format!("Property '{}'{} of {} must be {}, not {}.", name, err.path, receiver.name, err.expected, err.found),
+    })?;
+    function(&mut *receiver.borrow_mut::<T>()?, value);
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  struct Counter {
+    count: f64,
+  }
+
+  #[test]
+  fn calls_methods_on_the_wrapped_value() {
+    let data = UserData::new(Counter { count: 1.0 });
+    assert_eq!(data.name, "Counter");
+    assert!(data.is::<Counter>() && !data.is::<String>());
+
+    let add = |counter: &mut Counter, n: f64| {
+      counter.count += n;
+      counter.count
+    };
+    assert_eq!(add.call_method("add", &data, &[Object::Double(2.0)]), Ok(Object::Double(3.0)));
+    assert_eq!(getter(|counter: &Counter| counter.count)(&data), Ok(Object::Double(3.0)));
+
+    let set = setter("count", |counter: &mut Counter, n: f64| counter.count = n);
+    assert_eq!(set(&data, &Object::Double(7.0)), Ok(()));
+    let err = set(&data, &Object::Boolean(true)).unwrap_err();
+    assert_eq!(err.to_string(), "TypeError on line 0: Property 'count' of Counter must be a number, not bool.");
+  }
+
+  #[test]
+  fn checks_handle_types() {
+    let data = Object::UserData(UserData::new(Counter { count: 0.0 }));
+    assert_eq!(Handle::<Counter>::from_raki(&data).map(|handle| handle.borrow().unwrap().count), Ok(0.0));
+
+    let err = Handle::<String>::from_raki(&data).err().unwrap();
+    assert_eq!(err.to_string(), "Value must be String, not Counter.");
+  }
+
+  #[test]
+  fn reports_reentrant_borrows() {
+    let data = UserData::new(Counter { count: 0.0 });
+    let _held = data.value.borrow_mut();
+    let err = getter(|counter: &Counter| counter.count)(&data).unwrap_err();
+    assert_eq!(err.to_string(), "RuntimeError on line 0: Counter is already in use.");
+  }
+
+  #[test]
+  fn reports_handles_to_the_receiver() {
+    let data = UserData::new(Counter { count: 0.0 });
+    let add = |counter: &mut Counter, other: Handle<Counter>| -> Result<f64, RakiError> {
+      counter.count += other.borrow()?.count;
+      Ok(counter.count)
+    };
+    let err = add.call_method("add", &data, &[Object::UserData(data.clone())]).unwrap_err();
+    assert_eq!(err.to_string(), "RuntimeError on line 0: Counter is already in use.");
+
+    let other = Object::UserData(UserData::new(Counter { count: 2.0 }));
+    assert_eq!(add.call_method("add", &data, &[other]), Ok(Object::Double(2.0)));
+  }
+}
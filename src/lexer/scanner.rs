@@ -14,7 +14,7 @@ pub struct Scanner {
 impl Scanner {
   pub fn new(source: String) -> Scanner {
     Scanner {
-      source: source,
+      source,
       tokens: Vec::new(),
       start: 0,
       current: 0,
@@ -43,6 +43,11 @@ impl Scanner {
   fn scan_token(&mut self) -> Result<(), RakiError> {
     let c = self.advance();
 
+    if c == '\n' {
+      self.line += 1;
+      return Ok(());
+    }
+
     // Scan one char
     let token_type = match TokenType::from_char(c) {
       Some(tty) => tty,
@@ -79,7 +84,7 @@ impl Scanner {
 
     // Try scanning second char and try to extend the first one with it
     let nc = self.source.as_bytes()[self.current] as char;
-    let extended_token_type = match token_type.get_extension(nc) {
+    let mut extended_token_type = match token_type.get_extension(nc) {
       Some(etty) => {
         self.current += 1;
         etty
@@ -89,14 +94,17 @@ impl Scanner {
         return Ok(());
       }
     };
-    
+
+    // Three char tokens like '..=' extend a two char one
+    while let Some(etty) = extended_token_type.get_extension(self.peek()) {
+      self.current += 1;
+      extended_token_type = etty;
+    }
+
     // If the extended_token_type requires additional handling perform it
-    match extended_token_type {
-      TokenType::DoubleSlash => {
-        self.eat_comment();
-        return Ok(());
-      }
-      _ => {}
+    if extended_token_type == TokenType::DoubleSlash {
+      self.eat_comment();
+      return Ok(());
     }
 
     self.add_token(extended_token_type)?;
@@ -124,7 +132,7 @@ impl Scanner {
     self.tokens.push(Token {
       r#type,
       lexeme: String::from(lexeme),
-      literal: literal,
+      literal,
       line: self.line,
     });
 
@@ -169,9 +177,13 @@ impl Scanner {
 
   // Moves current byte pointer to the last digit of the number
   fn eat_number(&mut self) {
-    while self.peek().is_digit(10) {
+    while self.peek().is_ascii_digit() {
       self.advance();
-      if self.peek() == '.' && self.peek_next().is_digit(10) {
+    }
+
+    if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+      self.advance();
+      while self.peek().is_ascii_digit() {
         self.advance();
       }
     }
@@ -185,7 +197,7 @@ impl Scanner {
   }
 
   fn eat_identifier(&mut self) {
-    while self.peek().is_alphabetic() {
+    while self.peek().is_alphanumeric() || self.peek() == '_' {
       self.advance();
     }
   }
@@ -308,6 +320,16 @@ mod test {
     assert_eq!(tokens[2].r#type, TokenType::Eof);
   }
 
+  #[test]
+  fn scans_single_digit_decimals() {
+    let mut scanner = Scanner::new(String::from("1.5 0.25"));
+    let tokens = scanner.scan_tokens().unwrap();
+
+    assert_eq!(tokens[0].literal.to_string(), String::from("1.5"));
+    assert_eq!(tokens[1].literal.to_string(), String::from("0.25"));
+    assert_eq!(tokens[2].r#type, TokenType::Eof);
+  }
+
   #[test]
   fn ignores_bad_decimals() {
     let mut scanner = Scanner::new(String::from("(123. .45"));
@@ -343,4 +365,30 @@ mod test {
 
     assert_eq!(tokens[3].r#type, TokenType::Eof);
   }
+
+  #[test]
+  fn counts_lines() {
+    let mut scanner = Scanner::new(String::from("[\n]"));
+    let tokens = scanner.scan_tokens().unwrap();
+
+    assert_eq!(tokens[0].r#type, TokenType::LeftBracket);
+    assert_eq!(tokens[0].line, 1);
+    assert_eq!(tokens[1].r#type, TokenType::RightBracket);
+    assert_eq!(tokens[1].line, 2);
+  }
+
+  #[test]
+  fn scans_range_operators() {
+    let mut scanner = Scanner::new(String::from("0..10 1..=2 ...rest"));
+    let tokens = scanner.scan_tokens().unwrap();
+
+    assert_eq!(tokens[0].r#type, TokenType::Number);
+    assert_eq!(tokens[1].r#type, TokenType::DotDot);
+    assert_eq!(tokens[2].r#type, TokenType::Number);
+    assert_eq!(tokens[3].r#type, TokenType::Number);
+    assert_eq!(tokens[4].r#type, TokenType::DotDotEqual);
+    assert_eq!(tokens[5].r#type, TokenType::Number);
+    assert_eq!(tokens[6].r#type, TokenType::DotDotDot);
+    assert_eq!(tokens[7].r#type, TokenType::Identifier);
+  }
 }
@@ -28,7 +28,7 @@ pub struct Token {
 impl ::core::fmt::Display for Token {
   #[inline]
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> ::core::fmt::Result {
-    write!(f, "{} {} {}", self.r#type.to_string(), self.lexeme, self.literal)
+    write!(f, "{} {} {}", self.r#type, self.lexeme, self.literal)
   }
 }
 
@@ -1,21 +1,21 @@
 stringify_enum!(TokenType {
   // Single character tokens
-  LeftParen, RightParen, LeftBrace, RightBrace,
+  LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
   Comma, Dot, Minus, Plus, Semicolon, Slash, Star, QuestionMark, DoubleDot,
 
   // One or two characters tokens
   Bang, BangEqual,
-  Equal, EqualEqual,
+  Equal, EqualEqual, Arrow, ThinArrow,
   Greater, GreaterEqual,
   Less, LessEqual,
-  DoubleSlash,
+  DoubleSlash, DotDot, DotDotEqual, DotDotDot,
 
   // Literals.
   Identifier, String, Number,
 
   // Keywords.
-  And, Class, Else, False, Fun, For, If, Nil, Or,
-  Print, Return, Super, This, True, Var, While,
+  And, Break, Catch, Class, Const, Continue, Else, Enum, Export, False, Finally, Fun, For, If, Impl, Import, In, Is, Let,
+  Match, Nil, Or, Print, Return, Super, This, Throw, Trait, True, Try, Var, While,
 
   Eof,
 
@@ -32,6 +32,8 @@ impl TokenType {
       ')' => Some(RightParen),
       '{' => Some(LeftBrace),
       '}' => Some(RightBrace),
+      '[' => Some(LeftBracket),
+      ']' => Some(RightBracket),
       ',' => Some(Comma),
       '.' => Some(Dot),
       '-' => Some(Minus),
@@ -46,8 +48,8 @@ impl TokenType {
       '"' => Some(String),
       '?' => Some(QuestionMark),
       ':' => Some(DoubleDot),
-      c if c.is_digit(10) => Some(Number),
-      c if c.is_alphabetic() => Some(Identifier),
+      c if c.is_ascii_digit() => Some(Number),
+      c if c.is_alphabetic() || c == '_' => Some(Identifier),
       ' ' | '\r' | '\t' => Some(Ignore),
       _ => None,
     }
@@ -56,10 +58,21 @@ impl TokenType {
   pub fn get_extension(&self, c: char) -> Option<TokenType> {
     match *self {
       Bang => (c == '=').then_some(BangEqual),
-      Equal => (c == '=').then_some(EqualEqual),
+      Equal => match c {
+        '=' => Some(EqualEqual),
+        '>' => Some(Arrow),
+        _ => None,
+      },
       Greater => (c == '=').then_some(GreaterEqual),
       Less => (c == '=').then_some(LessEqual),
       Slash => (c == '/').then_some(DoubleSlash),
+      Minus => (c == '>').then_some(ThinArrow),
+      Dot => (c == '.').then_some(DotDot),
+      DotDot => match c {
+        '=' => Some(DotDotEqual),
+        '.' => Some(DotDotDot),
+        _ => None,
+      },
       
       _ => None,
     }
@@ -68,19 +81,35 @@ impl TokenType {
   pub fn get_identifier(&self, s: &str) -> Option<TokenType> {
     match s {
       "and"    => Some(And),
+      "break"  => Some(Break),
+      "catch"  => Some(Catch),
       "class"  => Some(Class),
+      "const"  => Some(Const),
+      "continue" => Some(Continue),
       "else"   => Some(Else),
+      "enum"   => Some(Enum),
+      "export" => Some(Export),
       "false"  => Some(False),
+      "finally" => Some(Finally),
       "fun"    => Some(Fun),
       "for"    => Some(For),
       "if"     => Some(If),
+      "impl"   => Some(Impl),
+      "import" => Some(Import),
+      "in"     => Some(In),
+      "is"     => Some(Is),
+      "let"    => Some(Let),
+      "match"  => Some(Match),
       "nil"    => Some(Nil),
       "or"     => Some(Or),
       "print"  => Some(Print),
       "return" => Some(Return),
       "super"  => Some(Super),
       "this"   => Some(This),
+      "throw"  => Some(Throw),
+      "trait"  => Some(Trait),
       "true"   => Some(True),
+      "try"    => Some(Try),
       "var"    => Some(Var),
       "while"  => Some(While),
       _ => None,
@@ -0,0 +1,23 @@
+#![allow(clippy::needless_return, clippy::module_inception)]
+
+mod raki_log;
+mod lexer;
+mod parser;
+mod checker;
+mod interpreter;
+mod engine;
+
+use lexer::{Token, TokenType};
+
+// The embedding API. Everything else is internal to the interpreter.
+pub use engine::Engine;
+pub use interpreter::{
+  Arity, CancelHandle, Capabilities, Clock, ConversionError, FixedClock, FromRaki, Handle, IntoMethod, IntoNative, Limits, NativeResult, Object as Value, SystemClock, ToRaki, UserData, argument, number_argument,
+};
+pub use raki_log::{ErrorKind, LimitKind, RakiError as Error};
+// Natives are passed the interpreter, and the binary sets up logging, but
+// neither is meant for hosts to use
+#[doc(hidden)]
+pub use interpreter::Interpreter;
+#[doc(hidden)]
+pub use raki_log::init as init_logger;
@@ -1,21 +1,116 @@
-mod raki_log;
-mod lexer;
-mod parser;
-mod interpreter;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::{env, fs, process};
 
-use lexer::{Token, TokenType};
+use raki_lang::{Capabilities, Engine, Error, Limits};
 
-use crate::{interpreter::Interpreter, lexer::Scanner, parser::{Parser, Visitor}};
+fn read_source(path: &str) -> String {
+  match fs::read_to_string(path) {
+    Ok(source) => source,
+    Err(err) => {
+      eprintln!("{}", Error::Io { path: path.to_string(), message: err.to_string() });
+      process::exit(74);
+    }
+  }
+}
+
+fn check_file(path: &str) {
+  if let Err(errors) = Engine::new().check(&read_source(path)) {
+    errors.iter().for_each(|err| eprintln!("{}", err));
+    process::exit(65);
+  }
+}
+
+fn run_file(engine: &Engine, path: &str) {
+  match engine.run_file(path) {
+    Ok(_) => {}
+    Err(err @ Error::Io { .. }) => {
+      eprintln!("{}", err);
+      process::exit(74);
+    }
+    Err(err @ (Error::Runtime { .. } | Error::Limit { .. })) => {
+      eprintln!("{}", err);
+      process::exit(70);
+    }
+    Err(err) => {
+      eprintln!("{}", err);
+      process::exit(65);
+    }
+  }
+}
+
+fn run_prompt(engine: &Engine) {
+  // Not holding the stdin lock between lines, scripts may read from it too
+  let stdin = io::stdin();
+  let mut line = String::new();
+
+  loop {
+    print!("> ");
+    io::stdout().flush().unwrap();
+    line.clear();
+    match stdin.read_line(&mut line) {
+      Ok(0) | Err(_) => break,
+      Ok(_) => {
+        if let Err(err) = engine.eval(&line) {
+          eprintln!("{}", err);
+        }
+      }
+    }
+  }
+}
+
+fn usage() -> ! {
+  eprintln!("Usage: raki_lang [options] [script [args...]]");
+  eprintln!("       raki_lang check <script>");
+  eprintln!();
+  eprintln!("Options:");
+  eprintln!("  --allow-read=<dir>   Let scripts read files under <dir>");
+  eprintln!("  --allow-write=<dir>  Let scripts write files under <dir>");
+  eprintln!("  --allow-stdin        Let scripts read standard input");
+  eprintln!("  --allow-env          Let scripts read environment variables");
+  process::exit(64);
+}
+
+// Leading `--allow-*` flags, followed by the script and its own arguments
+fn parse_capabilities(args: &[String]) -> (Capabilities, &[String]) {
+  let mut capabilities = Capabilities::default();
+  let mut rest = args;
+  while let Some((flag, tail)) = rest.split_first()
+    && flag.starts_with("--")
+  {
+    match flag.split_once('=') {
+      Some(("--allow-read", dir)) if !dir.is_empty() => capabilities.read.push(PathBuf::from(dir)),
+      Some(("--allow-write", dir)) if !dir.is_empty() => capabilities.write.push(PathBuf::from(dir)),
+      None if flag == "--allow-stdin" => capabilities.stdin = true,
+      None if flag == "--allow-env" => capabilities.env = true,
+      _ => {
+        eprintln!("Unknown option '{}'.", flag);
+        usage();
+      }
+    }
+    rest = tail;
+  }
+  capabilities.args = rest.iter().skip(1).cloned().collect();
+  (capabilities, rest)
+}
 
 fn main() {
-  let mut scanner = Scanner::new("1 > 2 ? 3 : 4".to_string());
-  let mut parser = Parser::new(scanner.scan_tokens().unwrap());
-  let exprs = parser.parse();
-
-  let interpreter = Interpreter{};
-  let res = interpreter.visit_expr(&exprs[0]);
-  match res {
-    Ok(obj) => println!("{}", obj),
-    Err(_) => {}
+  raki_lang::init_logger();
+
+  let args: Vec<String> = env::args().skip(1).collect();
+  if let [command, path] = args.as_slice()
+    && command == "check"
+  {
+    return check_file(path);
+  }
+
+  let (capabilities, rest) = parse_capabilities(&args);
+  let engine = Engine::new();
+  engine.grant(capabilities);
+  // Scripts run on the 8 MiB main thread, not a spawned 2 MiB one
+  engine.set_limits(Limits { max_stack: 4 * 1024 * 1024, ..Limits::default() });
+  match rest.first() {
+    None => run_prompt(&engine),
+    Some(path) => run_file(&engine, path),
   }
 }
@@ -6,5 +6,86 @@ pub enum Expr {
   Grouping { expr: Box<Expr> },
   Literal { value: LiteralType },
   Unary { right: Box<Expr>, operator: Token },
-  Ternary { condition: Box<Expr>, left: Box<Expr>, right: Box<Expr>},
-}
\ No newline at end of file
+  Ternary { condition: Box<Expr>, left: Box<Expr>, right: Box<Expr>, operator: Token },
+  Variable { name: Token },
+  Assign { name: Token, value: Box<Expr> },
+  List { elements: Vec<Expr> },
+  Map { entries: Vec<(Expr, Expr)>, brace: Token },
+  Index { object: Box<Expr>, index: Box<Expr>, bracket: Token },
+  IndexSet { object: Box<Expr>, index: Box<Expr>, value: Box<Expr>, bracket: Token },
+  Get { object: Box<Expr>, name: Token },
+  Call { callee: Box<Expr>, arguments: Vec<Expr>, paren: Token },
+  Tuple { elements: Vec<Expr>, paren: Token },
+  Destructure { target: Box<Expr>, value: Box<Expr>, operator: Token },
+  Range { start: Box<Expr>, end: Box<Expr>, operator: Token },
+  Set { object: Box<Expr>, name: Token, value: Box<Expr> },
+  This { keyword: Token },
+  Super { keyword: Token, method: Token },
+  Lambda { params: Vec<Token>, body: Vec<Stmt>, keyword: Token, types: FunctionTypes },
+  Match { value: Box<Expr>, arms: Vec<MatchArm>, keyword: Token },
+}
+
+// A type written in the source like `Int` or `String?`
+#[derive(Clone)]
+pub struct TypeAnnotation {
+  pub name: Token,
+  pub nullable: bool,
+}
+
+// Optional annotations of a function's parameters and result
+#[derive(Clone, Default)]
+pub struct FunctionTypes {
+  pub params: Vec<Option<TypeAnnotation>>,
+  pub result: Option<TypeAnnotation>,
+}
+
+#[derive(Clone)]
+pub struct MatchArm {
+  pub pattern: Pattern,
+  pub guard: Option<Expr>,
+  pub body: Expr,
+  pub arrow: Token,
+}
+
+#[derive(Clone)]
+pub enum Pattern {
+  Wildcard,
+  Binding { name: Token },
+  Literal { value: LiteralType },
+  // A dotted name like `Color.Red`, compared by equality
+  Value { value: Expr },
+  Range { start: f64, end: f64, inclusive: bool },
+  List { elements: Vec<Pattern>, rest: Option<Box<Pattern>> },
+  Tuple { elements: Vec<Pattern> },
+  Map { entries: Vec<(LiteralType, Pattern)> },
+  Class { path: Expr, fields: Vec<Pattern>, paren: Token },
+}
+
+#[derive(Clone)]
+pub enum Stmt {
+  Expression { expr: Expr },
+  // Values joined by `sep` and followed by `end`, a space and a newline by default
+  Print { values: Vec<Expr>, sep: Option<Expr>, end: Option<Expr>, keyword: Token },
+  Var { name: Token, initializer: Option<Expr>, annotation: Option<TypeAnnotation> },
+  Const { name: Token, initializer: Expr, annotation: Option<TypeAnnotation> },
+  Block { statements: Vec<Stmt> },
+  Destructure { target: Expr, initializer: Expr, keyword: Token },
+  Function { name: Token, params: Vec<Token>, body: Vec<Stmt>, types: FunctionTypes },
+  Return { value: Option<Expr>, keyword: Token },
+  If { condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>, keyword: Token },
+  While { condition: Expr, body: Box<Stmt>, keyword: Token },
+  ForIn { target: Expr, iterable: Expr, body: Box<Stmt>, keyword: Token },
+  Break,
+  Continue,
+  // `statics` holds the class methods and the class fields as `Var` statements
+  Class { name: Token, superclass: Option<Expr>, traits: Vec<Expr>, methods: Vec<Stmt>, getters: Vec<Stmt>, setters: Vec<Stmt>, statics: Vec<Stmt> },
+  // Required methods have no body, defaults are `Stmt::Function`s
+  Trait { name: Token, required: Vec<(Token, Vec<Token>)>, defaults: Vec<Stmt> },
+  Enum { name: Token, variants: Vec<(Token, Vec<Token>)> },
+  // `import "path" as alias;` binds the module, `from "path" import a, b;`
+  // binds the listed exports
+  Import { path: String, alias: Option<Token>, names: Vec<Token>, keyword: Token },
+  Export { declaration: Box<Stmt>, keyword: Token },
+  Throw { value: Expr, keyword: Token },
+  Try { body: Vec<Stmt>, catch: Option<(Token, Vec<Stmt>)>, finally: Option<Vec<Stmt>> },
+}
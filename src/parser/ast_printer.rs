@@ -7,11 +7,30 @@ pub struct AstPrinter;
 impl Visitor<String> for AstPrinter {
   fn visit_expr(&self, e: &Expr) -> String {
     match e {
-      Expr::Binary { left, right, operator } => return self.parenthesize(&operator.lexeme, [left, right]),
-      Expr::Grouping { expr } => return self.parenthesize("group", [expr]),
+      Expr::Binary { left, right, operator } => return self.parenthesize(&operator.lexeme, [left.as_ref(), right.as_ref()]),
+      Expr::Grouping { expr } => return self.parenthesize("group", [expr.as_ref()]),
       Expr::Literal { value } => return value.to_string(),
-      Expr::Unary { right, operator } => return self.parenthesize(&operator.lexeme, [right]),
-      Expr::Ternary { condition, left, right } => return self.parenthesize("ternary", [condition, left, right]),
+      Expr::Unary { right, operator } => return self.parenthesize(&operator.lexeme, [right.as_ref()]),
+      Expr::Ternary { condition, left, right, .. } => return self.parenthesize("ternary", [condition.as_ref(), left.as_ref(), right.as_ref()]),
+      Expr::Variable { name } => return name.lexeme.clone(),
+      Expr::Assign { name, value } => return self.parenthesize(&format!("= {}", name.lexeme), [value.as_ref()]),
+      Expr::List { elements, .. } => return self.parenthesize("list", elements),
+      Expr::Map { entries, .. } => return self.parenthesize("map", entries.iter().flat_map(|(key, value)| [key, value])),
+      Expr::Index { object, index, .. } => return self.parenthesize("index", [object.as_ref(), index.as_ref()]),
+      Expr::IndexSet { object, index, value, .. } => return self.parenthesize("index=", [object.as_ref(), index.as_ref(), value.as_ref()]),
+      Expr::Get { object, name } => return self.parenthesize(&format!(". {}", name.lexeme), [object.as_ref()]),
+      Expr::Call { callee, arguments, .. } => return self.parenthesize("call", std::iter::once(callee.as_ref()).chain(arguments)),
+      Expr::Tuple { elements, .. } => return self.parenthesize("tuple", elements),
+      Expr::Destructure { target, value, .. } => return self.parenthesize("=", [target.as_ref(), value.as_ref()]),
+      Expr::Range { start, end, operator } => return self.parenthesize(&operator.lexeme, [start.as_ref(), end.as_ref()]),
+      Expr::Set { object, name, value } => return self.parenthesize(&format!(".{} =", name.lexeme), [object.as_ref(), value.as_ref()]),
+      Expr::This { .. } => return "this".to_string(),
+      Expr::Super { method, .. } => return format!("super.{}", method.lexeme),
+      Expr::Lambda { params, .. } => {
+        let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+        return format!("( fun ({}) )", params.join(" "));
+      }
+      Expr::Match { value, arms, .. } => return self.parenthesize("match", std::iter::once(value.as_ref()).chain(arms.iter().map(|arm| &arm.body))),
     }
   }
 }
@@ -20,7 +39,7 @@ impl AstPrinter {
   #[allow(unused)]
   fn parenthesize<'a, I>(&self, name: &str, exprs: I) -> String
   where
-    I: IntoIterator<Item = &'a Box<Expr>>,
+    I: IntoIterator<Item = &'a Expr>,
   {
     let mut res = format!("( {} ", name);
     for expr in exprs.into_iter() {
@@ -1,36 +1,127 @@
-use super::Expr;
+use super::{Expr, FunctionTypes, MatchArm, Pattern, Stmt, TypeAnnotation};
 use crate::Token;
 use crate::TokenType;
 use crate::lexer::LiteralType;
 use crate::raki_log::{RakiError, raki_log};
 
 /*
+program        → declaration* EOF ;
+declaration    → importDecl
+               | "export" exportable
+               | exportable
+               | statement ;
+importDecl     → "import" STRING "as" IDENTIFIER ";"
+               | "from" STRING "import" IDENTIFIER ( "," IDENTIFIER )* ";" ;
+exportable     → classDecl
+               | traitDecl
+               | enumDecl
+               | funDecl
+               | varDecl
+               | constDecl ;
+classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )? ( "impl" IDENTIFIER ( "," IDENTIFIER )* )?
+                 "{" member* "}" ;
+member         → function
+               | IDENTIFIER ( "->" type )? block
+               | "set" function
+               | "class" ( function | IDENTIFIER ( ":" type )? ( "=" expression )? ";" ) ;
+traitDecl      → "trait" IDENTIFIER "{" ( IDENTIFIER "(" parameters? ")" ( "->" type )? ( ";" | block ) )* "}" ;
+enumDecl       → "enum" IDENTIFIER "{" ( variant ( "," variant )* ","? )? "}" ;
+variant        → IDENTIFIER ( "(" parameters? ")" )? ;
+funDecl        → "fun" function ;
+function       → IDENTIFIER "(" parameters? ")" ( "->" type )? block ;
+parameters     → IDENTIFIER ( ":" type )? ( "," IDENTIFIER ( ":" type )? )* ;
+type           → IDENTIFIER "?"? ;
+varDecl        → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";"
+               | "var" varTuple "=" expression ";" ;
+constDecl      → ( "const" | "let" ) IDENTIFIER ( ":" type )? "=" expression ";" ;
+varTuple       → "(" ( IDENTIFIER | varTuple ) ( "," ( IDENTIFIER | varTuple ) )* ")" ;
+statement      → exprStmt
+               | forStmt
+               | ifStmt
+               | printStmt
+               | returnStmt
+               | throwStmt
+               | tryStmt
+               | whileStmt
+               | breakStmt
+               | continueStmt
+               | block ;
+exprStmt       → expression ";" ;
+forStmt        → "for" "(" forTarget "in" expression ")" statement
+               | "for" forTarget "in" expression statement ;
+forTarget      → IDENTIFIER | varTuple ;
+ifStmt         → "if" "(" expression ")" statement ( "else" statement )? ;
+printStmt      → "print" ( assignment ( "," assignment )* )? ( ","? ( "sep" | "end" ) ":" assignment )* ";" ;
+returnStmt     → "return" expression? ";" ;
+throwStmt      → "throw" expression ";" ;
+tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+whileStmt      → "while" "(" expression ")" statement ;
+breakStmt      → "break" ";" ;
+continueStmt   → "continue" ";" ;
+block          → "{" declaration* "}" ;
+
 expression     → comma ;
-comma          → comma "," ternary
+comma          → comma "," assignment
+               | assignment ;
+assignment     → ( call ( "[" expression "]" | "." IDENTIFIER ) | IDENTIFIER | tuple ) "=" assignment
                | ternary ;
 ternary        → equality ( "?" expression ":" ternary )? ;
 equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+comparison     → range ( ( ">" | ">=" | "<" | "<=" | "is" ) range )* ;
+range          → term ( ( ".." | "..=" ) term )? ;
 term           → factor ( ( "-" | "+" ) factor )* ;
 factor         → unary ( ( "/" | "*" ) unary )* ;
 unary          → ( "!" | "-" ) unary
-               | primary ;
-primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+               | call ;
+call           → primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )* ;
+arguments      → assignment ( "," assignment )* ;
+primary        → NUMBER | STRING | "true" | "false" | "nil" | "this" | IDENTIFIER
+               | "super" "." IDENTIFIER
+               | "fun" "(" parameters? ")" ( "->" type )? block
+               | "match" assignment "{" ( matchArm ( "," matchArm )* ","? )? "}"
+               | "(" parameters? ")" "=>" ( block | assignment )
+               | "(" assignment ")" | tuple
+               | "[" ( assignment ( "," assignment )* )? "]"
+               | "{" ( entry ( "," entry )* )? "}" ;
+entry          → assignment ":" assignment ;
+tuple          → "(" ( assignment "," ( assignment ( "," assignment )* ","? )? )? ")" ;
+matchArm       → pattern ( "if" assignment )? "=>" assignment ;
+pattern        → "_" | IDENTIFIER | literal ( ( ".." | "..=" ) literal )?
+               | IDENTIFIER ( "." IDENTIFIER )+
+               | IDENTIFIER ( "." IDENTIFIER )* "(" patterns? ")"
+               | "[" ( pattern "," )* ( pattern | "..." IDENTIFIER )? "]"
+               | "(" patterns? ")"
+               | "{" ( literal ":" pattern ( "," literal ":" pattern )* )? "}" ;
+patterns       → pattern ( "," pattern )* ;
+literal        → "-"? NUMBER | STRING | "true" | "false" | "nil" ;
 */
 
+// Deeper source is a syntax error, rather than overflowing the native stack in
+// the parser or in the passes that walk the tree after it. Brackets, blocks
+// and other recursion cost far more stack than a chain of operators, which
+// only gets deep once the tree is walked.
+const MAX_NESTING: usize = 64;
+const MAX_DEPTH: usize = 1000;
+
 pub struct Parser {
   tokens: Vec<Token>,
   current: usize,
   exprs: Vec<Expr>,
-  errors: Vec<RakiError>
+  errors: Vec<RakiError>,
+  function_depth: usize,
+  loop_depth: usize,
+  // Recursion around the current token
+  nesting: usize,
+  // How deep the tree is at the current token, counting chained operators
+  depth: usize,
 }
 
 impl Parser {
   pub fn new(tokens: Vec<Token>) -> Parser {
-    Parser { tokens, current: 0, exprs: Vec::new(), errors: Vec::new() }
+    Parser { tokens, current: 0, exprs: Vec::new(), errors: Vec::new(), function_depth: 0, loop_depth: 0, nesting: 0, depth: 0 }
   }
 
+  #[allow(unused)]
   pub fn parse(&mut self) -> Vec<Expr> {
     loop {
       match self.expression() {
@@ -49,31 +140,616 @@ impl Parser {
     self.exprs.clone()
   }
 
+  pub fn parse_program(&mut self) -> Vec<Stmt> {
+    let mut statements = Vec::new();
+    while !self.is_eof() {
+      match self.declaration() {
+        Ok(stmt) => statements.push(stmt),
+        Err(_) => self.synchronize(),
+      }
+    }
+
+    statements
+  }
+
+  pub fn errors(&self) -> &[RakiError] {
+    &self.errors
+  }
+
+  fn declaration(&mut self) -> Result<Stmt, RakiError> {
+    match self.peek().r#type {
+      // `from` and `as` are only keywords inside imports, so they stay usable
+      // as names
+      TokenType::Import => {
+        self.advance();
+        return self.import_declaration();
+      }
+      TokenType::Identifier if self.peek().lexeme == "from" && self.tokens.get(self.current + 1).is_some_and(|next| next.r#type == TokenType::String) => {
+        self.advance();
+        return self.import_declaration();
+      }
+      TokenType::Export => {
+        self.advance();
+        let keyword = self.previous().clone();
+        use TokenType::*;
+        if !matches!(self.peek().r#type, Class | Enum | Trait | Fun | Var | Const | Let) {
+          return Err(self.error(self.peek().clone(), "Expect declaration after 'export'."));
+        }
+        let declaration = Box::new(self.declaration()?);
+        return Ok(Stmt::Export { declaration, keyword });
+      }
+      TokenType::Class => {
+        self.advance();
+        return self.class_declaration();
+      }
+      TokenType::Enum => {
+        self.advance();
+        return self.enum_declaration();
+      }
+      TokenType::Trait => {
+        self.advance();
+        return self.trait_declaration();
+      }
+      // `fun (...)` without a name is a lambda expression statement
+      TokenType::Fun if self.tokens.get(self.current + 1).is_some_and(|next| next.r#type == TokenType::Identifier) => {
+        self.advance();
+        return self.function("function");
+      }
+      TokenType::Var => {
+        self.advance();
+        return self.var_declaration();
+      }
+      TokenType::Const | TokenType::Let => {
+        self.advance();
+        return self.const_declaration();
+      }
+      _ => return self.statement(),
+    }
+  }
+
+  fn class_declaration(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect class name.")?;
+    let name = self.previous().clone();
+
+    let mut superclass = None;
+    if self.peek().r#type == TokenType::Less {
+      self.advance();
+      self.consume(TokenType::Identifier, "Expect superclass name.")?;
+      superclass = Some(Expr::Variable { name: self.previous().clone() });
+    }
+
+    let mut traits = Vec::new();
+    if self.peek().r#type == TokenType::Impl {
+      loop {
+        self.advance();
+        self.consume(TokenType::Identifier, "Expect trait name.")?;
+        traits.push(Expr::Variable { name: self.previous().clone() });
+        if self.peek().r#type != TokenType::Comma {
+          break;
+        }
+      }
+    }
+
+    self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+    let (mut methods, mut getters, mut setters, mut statics) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    while self.peek().r#type != TokenType::RightBrace && !self.is_eof() {
+      let next = self.tokens.get(self.current + 1).map(|token| token.r#type);
+      match (self.peek().r#type, next) {
+        (TokenType::Class, Some(TokenType::Identifier)) => {
+          self.advance();
+          match self.tokens.get(self.current + 1).map(|token| token.r#type) {
+            Some(TokenType::LeftParen) => statics.push(self.function("method")?),
+            _ => statics.push(self.variable()?),
+          }
+        }
+        (TokenType::Identifier, Some(TokenType::Identifier)) if self.peek().lexeme == "set" => {
+          self.advance();
+          let setter = self.function("setter")?;
+          if let Stmt::Function { name, params, .. } = &setter
+            && params.len() != 1
+          {
+            return Err(self.error(name.clone(), "A setter takes exactly one parameter."));
+          }
+          setters.push(setter);
+        }
+        (TokenType::Identifier, Some(TokenType::LeftBrace | TokenType::ThinArrow)) => getters.push(self.getter()?),
+        _ => methods.push(self.function("method")?),
+      }
+    }
+
+    self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+    Ok(Stmt::Class { name, superclass, traits, methods, getters, setters, statics })
+  }
+
+  fn getter(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect getter name.")?;
+    let name = self.previous().clone();
+    let types = FunctionTypes { params: Vec::new(), result: self.result_type()? };
+    self.consume(TokenType::LeftBrace, "Expect '{' before getter body.")?;
+    let body = self.function_body(|parser| parser.block())?;
+    Ok(Stmt::Function { name, params: Vec::new(), body, types })
+  }
+
+  fn trait_declaration(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect trait name.")?;
+    let name = self.previous().clone();
+    self.consume(TokenType::LeftBrace, "Expect '{' before trait body.")?;
+
+    let mut required = Vec::new();
+    let mut defaults = Vec::new();
+    while self.peek().r#type != TokenType::RightBrace && !self.is_eof() {
+      self.consume(TokenType::Identifier, "Expect method name.")?;
+      let method = self.previous().clone();
+      self.consume(TokenType::LeftParen, "Expect '(' after method name.")?;
+      let (params, param_types) = self.parameters()?;
+      let types = FunctionTypes { params: param_types, result: self.result_type()? };
+
+      if self.peek().r#type == TokenType::Semicolon {
+        self.advance();
+        required.push((method, params));
+        continue;
+      }
+      self.consume(TokenType::LeftBrace, "Expect ';' or a default body after trait method.")?;
+      let body = self.function_body(|parser| parser.block())?;
+      defaults.push(Stmt::Function { name: method, params, body, types });
+    }
+
+    self.consume(TokenType::RightBrace, "Expect '}' after trait body.")?;
+    Ok(Stmt::Trait { name, required, defaults })
+  }
+
+  fn enum_declaration(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect enum name.")?;
+    let name = self.previous().clone();
+    self.consume(TokenType::LeftBrace, "Expect '{' before enum body.")?;
+
+    let mut variants = Vec::new();
+    while self.peek().r#type != TokenType::RightBrace && !self.is_eof() {
+      self.consume(TokenType::Identifier, "Expect variant name.")?;
+      let variant = self.previous().clone();
+      let mut fields = Vec::new();
+      if self.peek().r#type == TokenType::LeftParen {
+        self.advance();
+        fields = self.parameters()?.0;
+      }
+      variants.push((variant, fields));
+
+      if self.peek().r#type != TokenType::Comma {
+        break;
+      }
+      self.advance();
+    }
+
+    self.consume(TokenType::RightBrace, "Expect '}' after enum body.")?;
+    Ok(Stmt::Enum { name, variants })
+  }
+
+  fn function(&mut self, kind: &str) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+    let name = self.previous().clone();
+
+    self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+    let (params, param_types) = self.parameters()?;
+    let types = FunctionTypes { params: param_types, result: self.result_type()? };
+    self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+    let body = self.function_body(|parser| parser.block())?;
+
+    Ok(Stmt::Function { name, params, body, types })
+  }
+
+  // Parameter names and their annotations up to and including the closing ')'
+  fn parameters(&mut self) -> Result<(Vec<Token>, Vec<Option<TypeAnnotation>>), RakiError> {
+    let mut params = Vec::new();
+    let mut types = Vec::new();
+    if self.peek().r#type != TokenType::RightParen {
+      loop {
+        self.consume(TokenType::Identifier, "Expect parameter name.")?;
+        params.push(self.previous().clone());
+        types.push(self.annotation()?);
+        if self.peek().r#type != TokenType::Comma {
+          break;
+        }
+        self.advance();
+      }
+    }
+
+    self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+    Ok((params, types))
+  }
+
+  // An optional `: Type` after a name
+  fn annotation(&mut self) -> Result<Option<TypeAnnotation>, RakiError> {
+    if self.peek().r#type != TokenType::DoubleDot {
+      return Ok(None);
+    }
+    self.advance();
+    self.type_annotation().map(Some)
+  }
+
+  // An optional `-> Type` after parameters
+  fn result_type(&mut self) -> Result<Option<TypeAnnotation>, RakiError> {
+    if self.peek().r#type != TokenType::ThinArrow {
+      return Ok(None);
+    }
+    self.advance();
+    self.type_annotation().map(Some)
+  }
+
+  fn type_annotation(&mut self) -> Result<TypeAnnotation, RakiError> {
+    self.consume(TokenType::Identifier, "Expect type name.")?;
+    let name = self.previous().clone();
+    let nullable = self.peek().r#type == TokenType::QuestionMark;
+    if nullable {
+      self.advance();
+    }
+    Ok(TypeAnnotation { name, nullable })
+  }
+
+  fn function_body(&mut self, body: impl FnOnce(&mut Parser) -> Result<Vec<Stmt>, RakiError>) -> Result<Vec<Stmt>, RakiError> {
+    // Loops outside of the function can't be broken out of from inside it
+    let loop_depth = self.loop_depth;
+    self.function_depth += 1;
+    self.loop_depth = 0;
+    let body = body(self);
+    self.function_depth -= 1;
+    self.loop_depth = loop_depth;
+    body
+  }
+
+  // Whether the tokens after an opening '(' are `a, b) =>`
+  fn is_arrow_parameters(&self) -> bool {
+    let mut i = self.current;
+    loop {
+      match self.tokens.get(i).map(|token| token.r#type) {
+        Some(TokenType::RightParen) => return self.tokens.get(i + 1).is_some_and(|token| token.r#type == TokenType::Arrow),
+        Some(TokenType::Identifier) => i += 1,
+        _ => return false,
+      }
+      // Skip a `: Type?` annotation
+      if self.tokens.get(i).is_some_and(|token| token.r#type == TokenType::DoubleDot) {
+        if !self.tokens.get(i + 1).is_some_and(|token| token.r#type == TokenType::Identifier) {
+          return false;
+        }
+        i += 2;
+        if self.tokens.get(i).is_some_and(|token| token.r#type == TokenType::QuestionMark) {
+          i += 1;
+        }
+      }
+      match self.tokens.get(i).map(|token| token.r#type) {
+        Some(TokenType::Comma) => i += 1,
+        Some(TokenType::RightParen) => {}
+        _ => return false,
+      }
+    }
+  }
+
+  fn var_declaration(&mut self) -> Result<Stmt, RakiError> {
+    if self.peek().r#type == TokenType::LeftParen {
+      let keyword = self.previous().clone();
+      let target = self.var_tuple()?;
+      self.consume(TokenType::Equal, "Expect '=' after destructuring declaration.")?;
+      let initializer = self.expression()?;
+      self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
+      return Ok(Stmt::Destructure { target, initializer, keyword });
+    }
+
+    self.variable()
+  }
+
+  fn variable(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect variable name.")?;
+    let name = self.previous().clone();
+    let annotation = self.annotation()?;
+
+    let mut initializer = None;
+    if self.peek().r#type == TokenType::Equal {
+      self.advance();
+      initializer = Some(self.expression()?);
+    }
+
+    self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
+    Ok(Stmt::Var { name, initializer, annotation })
+  }
+
+  fn import_declaration(&mut self) -> Result<Stmt, RakiError> {
+    let keyword = self.previous().clone();
+    self.consume(TokenType::String, "Expect module path.")?;
+    let LiteralType::String(path) = self.previous().literal.clone() else {
+      return Err(self.error(self.previous().clone(), "Expect module path."));
+    };
+
+    let (mut alias, mut names) = (None, Vec::new());
+    if keyword.r#type == TokenType::Import {
+      if self.peek().lexeme != "as" {
+        return Err(self.error(self.peek().clone(), "Expect 'as' after module path."));
+      }
+      self.advance();
+      self.consume(TokenType::Identifier, "Expect module name after 'as'.")?;
+      alias = Some(self.previous().clone());
+    } else {
+      self.consume(TokenType::Import, "Expect 'import' after module path.")?;
+      loop {
+        self.consume(TokenType::Identifier, "Expect name to import.")?;
+        names.push(self.previous().clone());
+        if self.peek().r#type != TokenType::Comma {
+          break;
+        }
+        self.advance();
+      }
+    }
+
+    self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+    Ok(Stmt::Import { path, alias, names, keyword })
+  }
+
+  fn const_declaration(&mut self) -> Result<Stmt, RakiError> {
+    self.consume(TokenType::Identifier, "Expect constant name.")?;
+    let name = self.previous().clone();
+    let annotation = self.annotation()?;
+    self.consume(TokenType::Equal, "Expect '=' after constant name.")?;
+    let initializer = self.expression()?;
+    self.consume(TokenType::Semicolon, "Expect ';' after constant declaration.")?;
+    Ok(Stmt::Const { name, initializer, annotation })
+  }
+
+  // Names bound by `var (a, (b, c)) = ...`, as a tuple of variables
+  fn var_tuple(&mut self) -> Result<Expr, RakiError> {
+    self.consume(TokenType::LeftParen, "Expect '(' before destructuring targets.")?;
+    let paren = self.previous().clone();
+    let mut elements = Vec::new();
+    loop {
+      match self.peek().r#type {
+        TokenType::LeftParen => elements.push(self.var_tuple()?),
+        _ => {
+          self.consume(TokenType::Identifier, "Expect variable name.")?;
+          elements.push(Expr::Variable { name: self.previous().clone() });
+        }
+      }
+      if self.peek().r#type != TokenType::Comma {
+        break;
+      }
+      self.advance();
+    }
+
+    self.consume(TokenType::RightParen, "Expect ')' after destructuring targets.")?;
+    Ok(Expr::Tuple { elements, paren })
+  }
+
+  fn statement(&mut self) -> Result<Stmt, RakiError> {
+    self.nested(Parser::statement_body)
+  }
+
+  fn statement_body(&mut self) -> Result<Stmt, RakiError> {
+    match self.peek().r#type {
+      TokenType::For => {
+        self.advance();
+        return self.for_statement();
+      }
+      TokenType::If => {
+        self.advance();
+        let keyword = self.previous().clone();
+        let condition = self.condition("if")?;
+        let then_branch = Box::new(self.statement()?);
+        let mut else_branch = None;
+        if self.peek().r#type == TokenType::Else {
+          self.advance();
+          else_branch = Some(Box::new(self.statement()?));
+        }
+        return Ok(Stmt::If { condition, then_branch, else_branch, keyword });
+      }
+      TokenType::While => {
+        self.advance();
+        let keyword = self.previous().clone();
+        let condition = self.condition("while")?;
+        let body = Box::new(self.loop_body()?);
+        return Ok(Stmt::While { condition, body, keyword });
+      }
+      TokenType::Throw => {
+        self.advance();
+        let keyword = self.previous().clone();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+        return Ok(Stmt::Throw { value, keyword });
+      }
+      TokenType::Try => {
+        self.advance();
+        return self.try_statement();
+      }
+      TokenType::Break | TokenType::Continue => {
+        self.advance();
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+          return Err(self.error(keyword.clone(), &format!("Can't use '{}' outside of a loop.", keyword.lexeme)));
+        }
+        self.consume(TokenType::Semicolon, &format!("Expect ';' after '{}'.", keyword.lexeme))?;
+        match keyword.r#type {
+          TokenType::Break => return Ok(Stmt::Break),
+          _ => return Ok(Stmt::Continue),
+        }
+      }
+      TokenType::Print => {
+        self.advance();
+        let keyword = self.previous().clone();
+        return self.print_statement(keyword);
+      }
+      TokenType::Return => {
+        self.advance();
+        let keyword = self.previous().clone();
+        if self.function_depth == 0 {
+          return Err(self.error(keyword, "Can't return from top-level code."));
+        }
+
+        let mut value = None;
+        if self.peek().r#type != TokenType::Semicolon {
+          value = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        return Ok(Stmt::Return { value, keyword });
+      }
+      // A map literal in statement position would otherwise be read as a block
+      TokenType::LeftBrace if !self.starts_map_literal() => {
+        self.advance();
+        return Ok(Stmt::Block { statements: self.block()? });
+      }
+      _ => {}
+    }
+
+    let expr = self.expression()?;
+    self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+    Ok(Stmt::Expression { expr })
+  }
+
+  fn try_statement(&mut self) -> Result<Stmt, RakiError> {
+    let keyword = self.previous().clone();
+    self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+    let body = self.block()?;
+
+    let mut catch = None;
+    if self.peek().r#type == TokenType::Catch {
+      self.advance();
+      self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+      self.consume(TokenType::Identifier, "Expect error variable name.")?;
+      let name = self.previous().clone();
+      self.consume(TokenType::RightParen, "Expect ')' after error variable.")?;
+      self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+      catch = Some((name, self.block()?));
+    }
+
+    let mut finally = None;
+    if self.peek().r#type == TokenType::Finally {
+      self.advance();
+      self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+      finally = Some(self.block()?);
+    }
+
+    if catch.is_none() && finally.is_none() {
+      return Err(self.error(keyword, "Expect 'catch' or 'finally' after try block."));
+    }
+    Ok(Stmt::Try { body, catch, finally })
+  }
+
+  fn condition(&mut self, keyword: &str) -> Result<Expr, RakiError> {
+    self.consume(TokenType::LeftParen, &format!("Expect '(' after '{}'.", keyword))?;
+    let condition = self.expression()?;
+    self.consume(TokenType::RightParen, &format!("Expect ')' after {} condition.", keyword))?;
+    Ok(condition)
+  }
+
+  fn loop_body(&mut self) -> Result<Stmt, RakiError> {
+    self.loop_depth += 1;
+    let body = self.statement();
+    self.loop_depth -= 1;
+    body
+  }
+
+  fn for_statement(&mut self) -> Result<Stmt, RakiError> {
+    let keyword = self.previous().clone();
+
+    // `for (x in xs)` and `for ((k, v) in m.entries())` wrap the whole header
+    // in parentheses, while in `for (k, v) in m.entries()` they only belong to
+    // the tuple target
+    let parenthesized = self.peek().r#type == TokenType::LeftParen
+      && match (self.tokens.get(self.current + 1), self.tokens.get(self.current + 2)) {
+        (Some(next), _) if next.r#type == TokenType::LeftParen => true,
+        (_, Some(after)) => after.r#type == TokenType::In,
+        _ => false,
+      };
+    if parenthesized {
+      self.advance();
+    }
+
+    let target = match self.peek().r#type {
+      TokenType::LeftParen => self.var_tuple()?,
+      _ => {
+        self.consume(TokenType::Identifier, "Expect loop variable name.")?;
+        Expr::Variable { name: self.previous().clone() }
+      }
+    };
+    self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+    let iterable = self.expression()?;
+    if parenthesized {
+      self.consume(TokenType::RightParen, "Expect ')' after for clause.")?;
+    }
+
+    let body = Box::new(self.loop_body()?);
+    Ok(Stmt::ForIn { target, iterable, body, keyword })
+  }
+
+  fn block(&mut self) -> Result<Vec<Stmt>, RakiError> {
+    let mut statements = Vec::new();
+    while self.peek().r#type != TokenType::RightBrace && !self.is_eof() {
+      statements.push(self.nested(Parser::declaration)?);
+    }
+
+    self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+    Ok(statements)
+  }
+
+  // A '{' opens a map when its first entry is a single token key followed by ':'
+  fn starts_map_literal(&self) -> bool {
+    match self.tokens.get(self.current + 2) {
+      Some(token) => token.r#type == TokenType::DoubleDot,
+      None => false,
+    }
+  }
+
   fn expression(&mut self) -> Result<Expr, RakiError> {
     return self.comma();
   }
 
   fn comma(&mut self) -> Result<Expr, RakiError> {
-    let mut expr = self.ternary()?;
+    let mut expr = self.assignment()?;
     
     if let TokenType::Comma = self.peek().r#type {
       self.advance();
-      expr = self.expression()?;
+      expr = self.nested(Parser::expression)?;
+    }
+
+    Ok(expr)
+  }
+
+  fn assignment(&mut self) -> Result<Expr, RakiError> {
+    self.nested(Parser::assignment_body)
+  }
+
+  fn assignment_body(&mut self) -> Result<Expr, RakiError> {
+    let expr = self.ternary()?;
+
+    if let TokenType::Equal = self.peek().r#type {
+      self.advance();
+      let equals = self.previous().clone();
+      let value = Box::new(self.assignment()?);
+
+      match expr {
+        Expr::Variable { name } => return Ok(Expr::Assign { name, value }),
+        Expr::Index { object, index, bracket } => return Ok(Expr::IndexSet { object, index, value, bracket }),
+        Expr::Get { object, name } => return Ok(Expr::Set { object, name, value }),
+        target @ Expr::Tuple { .. } if Parser::is_destructure_target(&target) => return Ok(Expr::Destructure { target: Box::new(target), value, operator: equals }),
+        _ => return Err(self.error(equals, "Invalid assignment target.")),
+      }
     }
 
     Ok(expr)
   }
 
+  fn is_destructure_target(expr: &Expr) -> bool {
+    match expr {
+      Expr::Variable { .. } | Expr::Index { .. } => true,
+      Expr::Tuple { elements, .. } => elements.iter().all(Parser::is_destructure_target),
+      _ => false,
+    }
+  }
+
   fn ternary(&mut self) -> Result<Expr, RakiError> {
     let condition = self.equality()?;
 
     if let TokenType::QuestionMark = self.peek().r#type {
-      self.advance();
+      let operator = self.advance_token();
       let left = Box::new(self.expression()?);
       match self.consume(TokenType::DoubleDot, "Expect ':' after ternary operator") {
         Ok(_) => {
-          let right = Box::new(self.ternary()?);
-          return Ok(Expr::Ternary{condition: Box::new(condition), left, right})
+          let right = Box::new(self.nested(Parser::ternary)?);
+          return Ok(Expr::Ternary{condition: Box::new(condition), left, right, operator})
         }
         Err(err) => return Err(err)
       }
@@ -86,6 +762,7 @@ impl Parser {
     let mut expr: Expr = self.comparison()?;
 
     while let TokenType::BangEqual | TokenType::EqualEqual = self.peek().r#type {
+      self.deeper()?;
       self.advance();
       let operator = self.previous().clone();
       let right = self.comparison()?;
@@ -96,22 +773,37 @@ impl Parser {
   }
 
   fn comparison(&mut self) -> Result<Expr, RakiError> {
-    let mut expr: Expr = self.term()?;
+    let mut expr: Expr = self.range()?;
 
-    while let TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual = self.peek().r#type {
+    while let TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual | TokenType::Is = self.peek().r#type {
+      self.deeper()?;
       self.advance();
       let operator = self.previous().clone();
-      let right = self.term()?;
+      let right = self.range()?;
       expr = Expr::Binary { left: Box::new(expr), right: Box::new(right), operator };
     }
 
     Ok(expr)
   }
 
+  fn range(&mut self) -> Result<Expr, RakiError> {
+    let start: Expr = self.term()?;
+
+    if let TokenType::DotDot | TokenType::DotDotEqual = self.peek().r#type {
+      self.advance();
+      let operator = self.previous().clone();
+      let end = self.term()?;
+      return Ok(Expr::Range { start: Box::new(start), end: Box::new(end), operator });
+    }
+
+    Ok(start)
+  }
+
   fn term(&mut self) -> Result<Expr, RakiError> {
     let mut expr: Expr = self.factor()?;
 
     while let TokenType::Plus | TokenType::Minus = self.peek().r#type {
+      self.deeper()?;
       self.advance();
       let operator = self.previous().clone();
       let right = self.factor()?;
@@ -124,7 +816,8 @@ impl Parser {
   fn factor(&mut self) -> Result<Expr, RakiError> {
     let mut expr: Expr = self.unary()?;
 
-    while let TokenType::Plus | TokenType::Minus = self.peek().r#type {
+    while let TokenType::Slash | TokenType::Star = self.peek().r#type {
+      self.deeper()?;
       self.advance();
       let operator = self.previous().clone();
       let right = self.unary()?;
@@ -138,15 +831,65 @@ impl Parser {
     if let TokenType::Bang | TokenType::Minus = self.peek().r#type {
       self.advance();
       let operator = self.previous().clone();
-      let right = self.unary()?;
+      let right = self.nested(Parser::unary)?;
       return Ok(Expr::Unary { right: Box::new(right), operator });
     }
 
-    self.primary()
+    self.call()
+  }
+
+  fn call(&mut self) -> Result<Expr, RakiError> {
+    let mut expr = self.primary()?;
+
+    loop {
+      if matches!(self.peek().r#type, TokenType::LeftParen | TokenType::Dot | TokenType::LeftBracket) {
+        self.deeper()?;
+      }
+      match self.peek().r#type {
+        TokenType::LeftParen => {
+          self.advance();
+          let arguments = self.arguments(TokenType::RightParen)?;
+          self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+          expr = Expr::Call { callee: Box::new(expr), arguments, paren: self.previous().clone() };
+        }
+        TokenType::Dot => {
+          self.advance();
+          let name = self.property_name()?;
+          expr = Expr::Get { object: Box::new(expr), name };
+        }
+        TokenType::LeftBracket => {
+          self.advance();
+          let bracket = self.previous().clone();
+          let index = self.expression()?;
+          self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+          expr = Expr::Index { object: Box::new(expr), index: Box::new(index), bracket };
+        }
+        _ => break,
+      }
+    }
+
+    Ok(expr)
+  }
+
+  // Comma separated assignments up to, but not including, the closing token
+  fn arguments(&mut self, closing: TokenType) -> Result<Vec<Expr>, RakiError> {
+    let mut arguments = Vec::new();
+    if self.peek().r#type == closing {
+      return Ok(arguments);
+    }
+
+    loop {
+      arguments.push(self.assignment()?);
+      if self.peek().r#type != TokenType::Comma {
+        return Ok(arguments);
+      }
+      self.advance();
+    }
   }
 
   fn primary(&mut self) -> Result<Expr, RakiError> {
-    if matches!(self.peek().r#type, TokenType::False | TokenType::True | TokenType::Nil | TokenType::Number | TokenType::LeftParen) {
+    use TokenType::*;
+    if matches!(self.peek().r#type, False | True | Nil | Number | String | This | Super | Identifier | Fun | Match | LeftParen | LeftBracket | LeftBrace) {
       self.advance();
     }
     else {
@@ -154,21 +897,269 @@ impl Parser {
     }
 
     match self.previous().r#type {
-      TokenType::False => return Ok(Expr::Literal { value: LiteralType::Bool(false) }),
-      TokenType::True => return Ok(Expr::Literal { value: LiteralType::Bool(true) }),
-      TokenType::Nil => return Ok(Expr::Literal { value: LiteralType::None }),
-      TokenType::Number | TokenType::String => return Ok(Expr::Literal { value: self.previous().literal.clone() }),
-      TokenType::LeftParen => {
-        let expr: Expr = self.expression()?;
-        match self.consume(TokenType::RightParen, "Expect ')' after expression.") {
-          Ok(_) => return Ok(Expr::Grouping { expr: Box::new(expr) }),
-          Err(err) => return Err(err)
+      False => return Ok(Expr::Literal { value: LiteralType::Bool(false) }),
+      True => return Ok(Expr::Literal { value: LiteralType::Bool(true) }),
+      Nil => return Ok(Expr::Literal { value: LiteralType::None }),
+      Number | String => return Ok(Expr::Literal { value: self.previous().literal.clone() }),
+      This => return Ok(Expr::This { keyword: self.previous().clone() }),
+      Super => {
+        let keyword = self.previous().clone();
+        self.consume(Dot, "Expect '.' after 'super'.")?;
+        self.consume(Identifier, "Expect superclass method name.")?;
+        return Ok(Expr::Super { keyword, method: self.previous().clone() });
+      }
+      Identifier => return Ok(Expr::Variable { name: self.previous().clone() }),
+      Fun => {
+        let keyword = self.previous().clone();
+        self.consume(LeftParen, "Expect '(' after 'fun'.")?;
+        let (params, param_types) = self.parameters()?;
+        let types = FunctionTypes { params: param_types, result: self.result_type()? };
+        self.consume(LeftBrace, "Expect '{' before function body.")?;
+        let body = self.function_body(|parser| parser.block())?;
+        return Ok(Expr::Lambda { params, body, keyword, types });
+      }
+      Match => return self.match_expression(),
+      LeftParen if self.is_arrow_parameters() => {
+        let keyword = self.previous().clone();
+        let (params, param_types) = self.parameters()?;
+        let types = FunctionTypes { params: param_types, result: None };
+        self.consume(Arrow, "Expect '=>' after parameters.")?;
+
+        // The body is a block unless it is a map literal
+        let arrow = self.previous().clone();
+        let body = self.function_body(|parser| match parser.peek().r#type {
+          LeftBrace if !parser.starts_map_literal() => {
+            parser.advance();
+            parser.block()
+          }
+          _ => Ok(vec![Stmt::Return { value: Some(parser.assignment()?), keyword: arrow }]),
+        })?;
+        return Ok(Expr::Lambda { params, body, keyword, types });
+      }
+      LeftParen => {
+        let paren = self.previous().clone();
+        if self.peek().r#type == RightParen {
+          self.advance();
+          return Ok(Expr::Tuple { elements: Vec::new(), paren });
+        }
+
+        let expr: Expr = self.assignment()?;
+        if self.peek().r#type != Comma {
+          match self.consume(RightParen, "Expect ')' after expression.") {
+            Ok(_) => return Ok(Expr::Grouping { expr: Box::new(expr) }),
+            Err(err) => return Err(err)
+          }
+        }
+
+        // A comma inside parentheses builds a tuple, `(a,)` has a single element
+        let mut elements = vec![expr];
+        while self.peek().r#type == Comma {
+          self.advance();
+          if self.peek().r#type == RightParen {
+            break;
+          }
+          elements.push(self.assignment()?);
+        }
+        self.consume(RightParen, "Expect ')' after tuple elements.")?;
+        return Ok(Expr::Tuple { elements, paren });
+      }
+      LeftBracket => {
+        let elements = self.arguments(RightBracket)?;
+        self.consume(RightBracket, "Expect ']' after list elements.")?;
+        return Ok(Expr::List { elements });
+      }
+      LeftBrace => {
+        let brace = self.previous().clone();
+        let mut entries = Vec::new();
+        while self.peek().r#type != RightBrace {
+          let key = self.assignment()?;
+          self.consume(DoubleDot, "Expect ':' after map key.")?;
+          entries.push((key, self.assignment()?));
+          if self.peek().r#type != Comma {
+            break;
+          }
+          self.advance();
         }
+        self.consume(RightBrace, "Expect '}' after map entries.")?;
+        return Ok(Expr::Map { entries, brace });
       }
       _ => return Err(self.error(self.peek().clone(), "Expected expression."))
     }
   }
 
+  fn match_expression(&mut self) -> Result<Expr, RakiError> {
+    let keyword = self.previous().clone();
+    let value = Box::new(self.assignment()?);
+    self.consume(TokenType::LeftBrace, "Expect '{' after match value.")?;
+
+    let mut arms = Vec::new();
+    while self.peek().r#type != TokenType::RightBrace && !self.is_eof() {
+      let pattern = self.pattern()?;
+      let mut guard = None;
+      if self.peek().r#type == TokenType::If {
+        self.advance();
+        guard = Some(self.assignment()?);
+      }
+      self.consume(TokenType::Arrow, "Expect '=>' after match pattern.")?;
+      let arrow = self.previous().clone();
+      let body = self.assignment()?;
+      arms.push(MatchArm { pattern, guard, body, arrow });
+
+      if self.peek().r#type != TokenType::Comma {
+        break;
+      }
+      self.advance();
+    }
+
+    self.consume(TokenType::RightBrace, "Expect '}' after match arms.")?;
+    Ok(Expr::Match { value, arms, keyword })
+  }
+
+  fn pattern(&mut self) -> Result<Pattern, RakiError> {
+    self.nested(Parser::pattern_body)
+  }
+
+  fn pattern_body(&mut self) -> Result<Pattern, RakiError> {
+    use TokenType::*;
+    match self.peek().r#type {
+      Identifier if self.peek().lexeme == "_" => {
+        self.advance();
+        Ok(Pattern::Wildcard)
+      }
+      Identifier => self.name_pattern(),
+      Minus | Number | String | True | False | Nil => {
+        let start = self.literal()?;
+        if !matches!(self.peek().r#type, DotDot | DotDotEqual) {
+          return Ok(Pattern::Literal { value: start });
+        }
+
+        self.advance();
+        let operator = self.previous().clone();
+        let end = self.literal()?;
+        match (start, end) {
+          (LiteralType::F64(start), LiteralType::F64(end)) => Ok(Pattern::Range { start, end, inclusive: operator.r#type == DotDotEqual }),
+          _ => Err(self.error(operator, "Range patterns must have number bounds.")),
+        }
+      }
+      LeftBracket => {
+        self.advance();
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while self.peek().r#type != RightBracket {
+          if rest.is_some() {
+            return Err(self.error(self.peek().clone(), "Rest pattern must come last in a list pattern."));
+          }
+          match self.peek().r#type {
+            DotDotDot => {
+              self.advance();
+              self.consume(Identifier, "Expect name after '...'.")?;
+              rest = Some(Box::new(match self.previous().lexeme.as_str() {
+                "_" => Pattern::Wildcard,
+                _ => Pattern::Binding { name: self.previous().clone() },
+              }));
+            }
+            _ => elements.push(self.pattern()?),
+          }
+          if self.peek().r#type != Comma {
+            break;
+          }
+          self.advance();
+        }
+        self.consume(RightBracket, "Expect ']' after list pattern.")?;
+        Ok(Pattern::List { elements, rest })
+      }
+      LeftParen => {
+        self.advance();
+        let mut elements = self.patterns(RightParen)?;
+        let trailing_comma = self.previous().r#type == Comma;
+        self.consume(RightParen, "Expect ')' after tuple pattern.")?;
+
+        // Like expressions, `(p)` is only a grouping
+        if elements.len() == 1 && !trailing_comma {
+          return Ok(elements.remove(0));
+        }
+        Ok(Pattern::Tuple { elements })
+      }
+      LeftBrace => {
+        self.advance();
+        let mut entries = Vec::new();
+        while self.peek().r#type != RightBrace {
+          let key = self.literal()?;
+          self.consume(DoubleDot, "Expect ':' after map pattern key.")?;
+          entries.push((key, self.pattern()?));
+          if self.peek().r#type != Comma {
+            break;
+          }
+          self.advance();
+        }
+        self.consume(RightBrace, "Expect '}' after map pattern.")?;
+        Ok(Pattern::Map { entries })
+      }
+      _ => Err(self.error(self.peek().clone(), "Expect pattern.")),
+    }
+  }
+
+  // `x` binds, `Color.Red` compares and `Point(x, y)` destructures instances
+  fn name_pattern(&mut self) -> Result<Pattern, RakiError> {
+    let name = self.advance_token();
+    let mut path = Expr::Variable { name: name.clone() };
+    while self.peek().r#type == TokenType::Dot {
+      self.advance();
+      self.consume(TokenType::Identifier, "Expect name after '.' in pattern.")?;
+      path = Expr::Get { object: Box::new(path), name: self.previous().clone() };
+    }
+
+    if self.peek().r#type == TokenType::LeftParen {
+      self.advance();
+      let paren = self.previous().clone();
+      let fields = self.patterns(TokenType::RightParen)?;
+      self.consume(TokenType::RightParen, "Expect ')' after class pattern fields.")?;
+      return Ok(Pattern::Class { path, fields, paren });
+    }
+
+    match path {
+      Expr::Variable { .. } => Ok(Pattern::Binding { name }),
+      path => Ok(Pattern::Value { value: path }),
+    }
+  }
+
+  // Comma separated patterns up to, but not including, the closing token
+  fn patterns(&mut self, closing: TokenType) -> Result<Vec<Pattern>, RakiError> {
+    let mut patterns = Vec::new();
+    while self.peek().r#type != closing {
+      patterns.push(self.pattern()?);
+      if self.peek().r#type != TokenType::Comma {
+        break;
+      }
+      self.advance();
+    }
+
+    Ok(patterns)
+  }
+
+  fn literal(&mut self) -> Result<LiteralType, RakiError> {
+    use TokenType::*;
+    let negative = self.peek().r#type == Minus;
+    if negative {
+      self.advance();
+    }
+
+    match (self.peek().r#type, negative) {
+      (Number, _) | (String | True | False | Nil, false) => {}
+      (_, true) => return Err(self.error(self.peek().clone(), "Expect number after '-'.")),
+      _ => return Err(self.error(self.peek().clone(), "Expect literal.")),
+    }
+
+    let token = self.advance_token();
+    match (token.r#type, token.literal) {
+      (Number, LiteralType::F64(n)) if negative => Ok(LiteralType::F64(-n)),
+      (True, _) => Ok(LiteralType::Bool(true)),
+      (False, _) => Ok(LiteralType::Bool(false)),
+      (Nil, _) => Ok(LiteralType::None),
+      (_, literal) => Ok(literal),
+    }
+  }
+
   fn synchronize(&mut self) {
     self.advance();
     while !self.is_eof() {
@@ -177,7 +1168,7 @@ impl Parser {
       }
       use TokenType::*;
       match self.peek().r#type {
-        Class | Fun | Var | For | If | While | Print | Return => return,
+        Class | Trait | Enum | Fun | Var | Const | Let | Import | Export | For | If | While | Print | Return | Throw | Try => return,
         _ => self.advance(),
       };
     }
@@ -188,10 +1179,18 @@ impl Parser {
   }
 
   fn advance(&mut self) -> &Token {
-    self.current += 1;
+    if !self.is_eof() {
+      self.current += 1;
+    }
     self.peek()
   }
 
+  // Consumes the current token and returns it
+  fn advance_token(&mut self) -> Token {
+    self.advance();
+    self.previous().clone()
+  }
+
   fn peek(&self) -> &Token {
     &self.tokens[self.current]
   }
@@ -200,6 +1199,70 @@ impl Parser {
     &self.tokens[self.current - 1]
   }
 
+  // Parses one level of recursion deeper. Whatever `f` chains on top is
+  // given back when it returns.
+  fn nested<T>(&mut self, f: impl FnOnce(&mut Parser) -> Result<T, RakiError>) -> Result<T, RakiError> {
+    if self.nesting == MAX_NESTING {
+      return Err(self.error(self.peek().clone(), "Too deeply nested."));
+    }
+    let depth = self.depth;
+    self.nesting += 1;
+    let result = self.deeper().and_then(|_| f(self));
+    self.nesting -= 1;
+    self.depth = depth;
+    result
+  }
+
+  // Each operator in a chain nests the left operand one level deeper
+  fn deeper(&mut self) -> Result<(), RakiError> {
+    self.depth += 1;
+    match self.depth > MAX_DEPTH {
+      true => Err(self.error(self.peek().clone(), "Too deeply nested.")),
+      false => Ok(()),
+    }
+  }
+
+  // `print a, b, sep: ", ", end: "";`. Options come after the values.
+  fn print_statement(&mut self, keyword: Token) -> Result<Stmt, RakiError> {
+    let (mut values, mut sep, mut end) = (Vec::new(), None, None);
+    while self.peek().r#type != TokenType::Semicolon {
+      let token = self.peek().clone();
+      let is_option = token.r#type == TokenType::Identifier
+        && matches!(token.lexeme.as_str(), "sep" | "end")
+        && self.tokens.get(self.current + 1).is_some_and(|next| next.r#type == TokenType::DoubleDot);
+      if is_option {
+        self.advance();
+        self.advance();
+        let option = if token.lexeme == "sep" { &mut sep } else { &mut end };
+        if option.replace(self.assignment()?).is_some() {
+          return Err(self.error(token.clone(), &format!("Duplicate '{}' in print.", token.lexeme)));
+        }
+      } else if sep.is_some() || end.is_some() {
+        return Err(self.error(token, "Expect values before 'sep' and 'end'."));
+      } else {
+        values.push(self.assignment()?);
+      }
+
+      if self.peek().r#type != TokenType::Comma {
+        break;
+      }
+      self.advance();
+    }
+    self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+    Ok(Stmt::Print { values, sep, end, keyword })
+  }
+
+  // Keywords are allowed after '.', like `pattern.match(text)`
+  fn property_name(&mut self) -> Result<Token, RakiError> {
+    let token = self.peek().clone();
+    if token.r#type.get_identifier(&token.lexeme).is_some() {
+      self.advance();
+      return Ok(Token { r#type: TokenType::Identifier, ..token });
+    }
+    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+    Ok(self.previous().clone())
+  }
+
   fn consume(&mut self, tty: TokenType, msg: &str) -> Result<&Token, RakiError> {
     if self.peek().r#type == tty {
       return Ok(self.advance());
@@ -209,23 +1272,22 @@ impl Parser {
   }
 
   fn error(&mut self, token: Token, msg: &str) -> RakiError {
-    let err: RakiError;
-    match token.r#type {
+    let err = match token.r#type {
       TokenType::Eof => {
-        err = RakiError::Syntax {
+        RakiError::Syntax {
           line: token.line,
           at: " at end".to_string(),
           message: msg.to_string(),
         }
       }
       _ => {
-        err = RakiError::Syntax {
+        RakiError::Syntax {
           line: token.line,
           at: format!(" at {}", token.lexeme),
           message: msg.to_string(),
         }
       }
-    }
+    };
 
     raki_log(&err);
     self.errors.push(err.clone());
@@ -279,6 +1341,205 @@ mod test {
     assert_eq!(ast_printer.visit_expr(&exprs[0]), "( ternary ( > 1 2 ) 3 4 )");
   }
 
+  #[test]
+  fn handles_map_and_index_expressions() {
+    let mut scanner = Scanner::new("{\"a\": [1, 2]}[\"a\"][0] = 3".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    let exprs = parser.parse();
+    assert_eq!(ast_printer.visit_expr(&exprs[0]), "( index= ( index ( map a ( list 1 2 ) ) a ) 0 3 )");
+  }
+
+  #[test]
+  fn handles_tuples_and_grouping() {
+    let mut scanner = Scanner::new("(a, b) = (1, (2), (3,))".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    let exprs = parser.parse();
+    assert_eq!(ast_printer.visit_expr(&exprs[0]), "( = ( tuple a b ) ( tuple 1 ( group 2 ) ( tuple 3 ) ) )");
+  }
+
+  #[test]
+  fn rejects_invalid_destructuring_targets() {
+    let mut scanner = Scanner::new("(a, 1) = (1, 2);".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at =".to_string(), message: "Invalid assignment target.".to_string()});
+  }
+
+  #[test]
+  fn handles_range_precedence() {
+    let mut scanner = Scanner::new("0..n + 1 < 2".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    let exprs = parser.parse();
+    assert_eq!(ast_printer.visit_expr(&exprs[0]), "( < ( .. 0 ( + n 1 ) ) 2 )");
+  }
+
+  #[test]
+  fn rejects_break_outside_of_loops() {
+    let mut scanner = Scanner::new("while (true) { fun f() { break; } }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at break".to_string(), message: "Can't use 'break' outside of a loop.".to_string()});
+  }
+
+  #[test]
+  fn rejects_try_without_handlers() {
+    let mut scanner = Scanner::new("try { print 1; } print 2;".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at try".to_string(), message: "Expect 'catch' or 'finally' after try block.".to_string()});
+  }
+
+  #[test]
+  fn parses_match_patterns() {
+    let mut scanner = Scanner::new("match x { [a, ...rest] => a, Shape.Circle(r) if r > 0 => r, (_, 1..2) => 0 }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    assert_eq!(ast_printer.visit_expr(&parser.parse()[0]), "( match x a r 0 )");
+
+    let mut scanner = Scanner::new("match x { [...rest, a] => a }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at a".to_string(), message: "Rest pattern must come last in a list pattern.".to_string()});
+  }
+
+  #[test]
+  fn parses_type_annotations() {
+    let mut scanner = Scanner::new("fun f(a: String, b: Num?) -> Bool { return true; } var x: Int = 1; var g = (a: Num) => a;".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let Stmt::Function { types, .. } = &statements[0] else { panic!("expected a function") };
+    let params: Vec<(&str, bool)> = types.params.iter().flatten().map(|t| (t.name.lexeme.as_str(), t.nullable)).collect();
+    assert_eq!(params, vec![("String", false), ("Num", true)]);
+    assert_eq!(types.result.as_ref().map(|t| t.name.lexeme.as_str()), Some("Bool"));
+    assert!(matches!(&statements[1], Stmt::Var { annotation: Some(_), .. }));
+  }
+
+  #[test]
+  fn parses_traits_and_inheritance() {
+    let mut scanner = Scanner::new("trait T { f(a); g() { return 1; } } class A < B impl T, U { f(a) {} }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let Stmt::Trait { required, defaults, .. } = &statements[0] else { panic!("expected a trait") };
+    assert_eq!(required.iter().map(|(name, params)| (name.lexeme.as_str(), params.len())).collect::<Vec<_>>(), vec![("f", 1)]);
+    assert_eq!(defaults.len(), 1);
+    let Stmt::Class { superclass: Some(Expr::Variable { name }), traits, .. } = &statements[1] else { panic!("expected a subclass") };
+    assert_eq!(name.lexeme, "B");
+    assert_eq!(traits.len(), 2);
+  }
+
+  #[test]
+  fn parses_class_members() {
+    let mut scanner = Scanner::new("class A { f() {} area -> Num { return 1; } set(x) {} set size(v) {} class count = 0; class make() {} }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let Stmt::Class { methods, getters, setters, statics, .. } = &statements[0] else { panic!("expected a class") };
+    assert_eq!((methods.len(), getters.len(), setters.len(), statics.len()), (2, 1, 1, 2));
+    assert!(matches!(&statics[0], Stmt::Var { .. }));
+
+    let mut scanner = Scanner::new("class A { set size(a, b) {} }".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at size".to_string(), message: "A setter takes exactly one parameter.".to_string()});
+  }
+
+  #[test]
+  fn parses_imports_and_exports() {
+    let mut scanner = Scanner::new("import \"a/b.raki\" as b; from \"c.raki\" import x, y; export fun f() {}".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors().is_empty());
+
+    let Stmt::Import { path, alias: Some(alias), names, .. } = &statements[0] else { panic!("expected an import") };
+    assert_eq!((path.as_str(), alias.lexeme.as_str(), names.len()), ("a/b.raki", "b", 0));
+    let Stmt::Import { path, alias: None, names, .. } = &statements[1] else { panic!("expected a from import") };
+    assert_eq!((path.as_str(), names.iter().map(|name| name.lexeme.as_str()).collect::<Vec<_>>()), ("c.raki", vec!["x", "y"]));
+    assert!(matches!(&statements[2], Stmt::Export { .. }));
+
+    let mut scanner = Scanner::new("export print 1;".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at print".to_string(), message: "Expect declaration after 'export'.".to_string()});
+  }
+
+  #[test]
+  fn handles_lambda_expressions() {
+    let mut scanner = Scanner::new("f(fun (a, b) { return a; }, (x) => x, () => 1, (y))".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    let exprs = parser.parse();
+    assert_eq!(ast_printer.visit_expr(&exprs[0]), "( call f ( fun (a b) ) ( fun (x) ) ( fun () ) ( group y ) )");
+  }
+
+  #[test]
+  fn handles_keywords_as_property_names() {
+    let mut scanner = Scanner::new("pattern.match(text).print".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let ast_printer = AstPrinter {};
+    let exprs = parser.parse();
+    assert_eq!(ast_printer.visit_expr(&exprs[0]), "( . print ( call ( . match pattern ) text ) )");
+
+    let mut scanner = Scanner::new("a.1".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse();
+    assert_eq!(parser.errors[0].to_string(), "Syntax error on line 1 at 1: Expect property name after '.'.");
+  }
+
+  #[test]
+  fn handles_print_options() {
+    let mut scanner = Scanner::new("print 1, sep; print a, b, sep: \"-\", end: \"\"; print;".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    let statements = parser.parse_program();
+    assert!(parser.errors.is_empty());
+    assert!(matches!(&statements[0], Stmt::Print { values, sep: None, end: None, .. } if values.len() == 2));
+    assert!(matches!(&statements[1], Stmt::Print { values, sep: Some(_), end: Some(_), .. } if values.len() == 2));
+    assert!(matches!(&statements[2], Stmt::Print { values, .. } if values.is_empty()));
+
+    let mut scanner = Scanner::new("print sep: \"-\", 1;".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at 1".to_string(), message: "Expect values before 'sep' and 'end'.".to_string()});
+
+    let mut scanner = Scanner::new("print 1, end: \"\", end: \"\";".to_string());
+    let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+    parser.parse_program();
+    assert_eq!(parser.errors[0], RakiError::Syntax{line: 1, at: " at end".to_string(), message: "Duplicate 'end' in print.".to_string()});
+  }
+
+  // Messages of the errors in `source`, parsed on a thread with room for the
+  // deepest source the parser accepts
+  fn nesting_errors(source: String) -> Vec<String> {
+    let parse = move || {
+      let mut parser = Parser::new(Scanner::new(source).scan_tokens().unwrap());
+      parser.parse_program();
+      parser.errors().iter().map(|err| err.to_string()).collect()
+    };
+    std::thread::Builder::new().stack_size(64 << 20).spawn(parse).unwrap().join().unwrap()
+  }
+
+  #[test]
+  fn rejects_deeply_nested_source() {
+    let nested = |open: &str, close: &str, depth: usize| format!("var x = {}1{};", open.repeat(depth), close.repeat(depth));
+    assert!(nesting_errors(nested("(", ")", 60)).is_empty());
+    assert_eq!(nesting_errors(nested("(", ")", 100_000)), vec!["Syntax error on line 1 at (: Too deeply nested."]);
+    assert_eq!(nesting_errors(nested("-", "", 100_000)), vec!["Syntax error on line 1 at -: Too deeply nested."]);
+    assert!(nesting_errors(format!("{}{}", "if (true) {".repeat(1000), "}".repeat(1000)))[0].ends_with("Too deeply nested."));
+    assert!(nesting_errors(format!("{}{}", "fun f() {".repeat(1000), "}".repeat(1000)))[0].ends_with("Too deeply nested."));
+
+    // Chained operators only get deep once the tree is walked, so many more fit
+    assert!(nesting_errors(format!("var x = 1{};", " + 1".repeat(900))).is_empty());
+    assert_eq!(nesting_errors(format!("var x = 1{};", " + 1".repeat(100_000))), vec!["Syntax error on line 1 at +: Too deeply nested."]);
+    assert_eq!(nesting_errors(format!("var x = f{};", "()".repeat(100_000))), vec!["Syntax error on line 1 at (: Too deeply nested."]);
+  }
+
   #[test]
   fn handles_invalid_binary_operator() {
     let mut scanner = Scanner::new("> 2".to_string());
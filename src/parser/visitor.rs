@@ -1,5 +1,9 @@
-use super::Expr;
+use super::{Expr, Stmt};
 
 pub trait Visitor<R> {
   fn visit_expr(&self, expr: &Expr) -> R;
+}
+
+pub trait StmtVisitor<R> {
+  fn visit_stmt(&self, stmt: &Stmt) -> R;
 }
\ No newline at end of file
@@ -1,6 +1,99 @@
+use std::fmt;
+
+use crate::interpreter::Object;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum ErrorKind {
+  TypeError,
+  NameError,
+  IndexError,
+  KeyError,
+  ValueError,
+  MatchError,
+  ImportError,
+  PermissionError,
+  IOError,
+  RuntimeError,
+}
+
+impl ErrorKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ErrorKind::TypeError => "TypeError",
+      ErrorKind::NameError => "NameError",
+      ErrorKind::IndexError => "IndexError",
+      ErrorKind::KeyError => "KeyError",
+      ErrorKind::ValueError => "ValueError",
+      ErrorKind::MatchError => "MatchError",
+      ErrorKind::ImportError => "ImportError",
+      ErrorKind::PermissionError => "PermissionError",
+      ErrorKind::IOError => "IOError",
+      ErrorKind::RuntimeError => "RuntimeError",
+    }
+  }
+}
+
+// A sandbox limit the host set, see `Limits`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LimitKind {
+  Steps,
+  Time,
+  CallDepth,
+  Stack,
+  Allocation,
+  Cancelled,
+}
+
+impl LimitKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LimitKind::Steps => "StepLimit",
+      LimitKind::Time => "TimeLimit",
+      LimitKind::CallDepth => "CallDepthLimit",
+      LimitKind::Stack => "StackLimit",
+      LimitKind::Allocation => "AllocationLimit",
+      LimitKind::Cancelled => "Cancelled",
+    }
+  }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum RakiError {
   Scanner(String),
   Syntax{line: u32, at: String, message: String},
-  Runtime{}
-}
\ No newline at end of file
+  Runtime{kind: ErrorKind, line: u32, message: String},
+  // Found by the type checker before the program runs
+  Type{line: u32, message: String},
+  // A value raised by `throw` that no `catch` handled
+  Thrown{line: u32, value: Object},
+  // A script that couldn't be read
+  Io{path: String, message: String},
+  // The script ran out of budget or was cancelled. Scripts can't catch these.
+  Limit{kind: LimitKind, message: String},
+  // Everything found before a script ran, in the order it was found
+  Analysis(Vec<RakiError>),
+}
+
+impl fmt::Display for RakiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RakiError::Scanner(message) => write!(f, "Parser error => {}", message),
+      RakiError::Syntax { line, at, message } => write!(f, "Syntax error on line {}{}: {}", line, at, message),
+      RakiError::Runtime { kind, line, message } => write!(f, "{} on line {}: {}", kind.as_str(), line, message),
+      RakiError::Type { line, message } => write!(f, "Type error on line {}: {}", line, message),
+      RakiError::Thrown { line, value } => write!(f, "Uncaught exception on line {}: {}", line, value),
+      RakiError::Io { path, message } => write!(f, "Could not read {}: {}", path, message),
+      RakiError::Limit { kind, message } => write!(f, "{}: {}", kind.as_str(), message),
+      RakiError::Analysis(errors) => write!(f, "{}", errors.iter().map(|err| err.to_string()).collect::<Vec<_>>().join("\n")),
+    }
+  }
+}
+
+impl std::error::Error for RakiError {}
+// Diagnostics that don't stop the program from running
+#[derive(Clone, PartialEq, Debug)]
+pub struct RakiWarning {
+  pub line: u32,
+  pub message: String,
+}
@@ -1,10 +1,35 @@
-use crate::raki_log::RakiError;
-use log::error;
+use crate::raki_log::{RakiError, RakiWarning};
+use log::{error, warn, Level, LevelFilter, Log, Metadata, Record};
 
-pub fn raki_log(err: &RakiError) {
-  match err {
-    RakiError::Scanner(msg) => error!("Parser error => {}", msg),
-    RakiError::Syntax { line, at, message } => error!("Syntax error on line {}, {}: {}", line, at, message),
-    RakiError::Runtime {} => {  }
+struct StderrLogger;
+
+impl Log for StderrLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Warn
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      eprintln!("{}", record.args());
+    }
   }
-}
\ No newline at end of file
+
+  fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+// Without a logger the `log` macros drop every diagnostic
+pub fn init() {
+  if log::set_logger(&LOGGER).is_ok() {
+    log::set_max_level(LevelFilter::Warn);
+  }
+}
+
+pub fn raki_log(err: &RakiError) {
+  error!("{}", err);
+}
+
+pub fn raki_warn(warning: &RakiWarning) {
+  warn!("Warning on line {}: {}", warning.line, warning.message);
+}
@@ -2,4 +2,4 @@ mod error;
 mod logger;
 
 pub use error::*;
-pub use logger::raki_log;
\ No newline at end of file
+pub use logger::{init, raki_log, raki_warn};
\ No newline at end of file
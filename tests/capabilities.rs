@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use raki_lang::{Capabilities, Engine, Error, ErrorKind, Value};
+
+fn temp_dir(test: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("raki_capabilities_{}_{}", test, std::process::id()));
+  fs::create_dir_all(dir.join("data")).unwrap();
+  fs::create_dir_all(dir.join("out")).unwrap();
+  fs::write(dir.join("data/notes.txt"), "hello").unwrap();
+  fs::write(dir.join("secret.txt"), "hunter2").unwrap();
+  dir
+}
+
+fn engine(capabilities: Capabilities) -> Engine {
+  let engine = Engine::new();
+  engine.grant(capabilities);
+  engine
+}
+
+fn path(dir: &Path, file: &str) -> String {
+  format!("\"{}\"", dir.join(file).display())
+}
+
+fn error_kind(result: Result<Value, Error>) -> ErrorKind {
+  match result {
+    Err(Error::Runtime { kind, .. }) => kind,
+    result => panic!("expected a runtime error, got {:?}", result),
+  }
+}
+
+#[test]
+fn reads_inside_granted_directories() {
+  let dir = temp_dir("read");
+  let engine = engine(Capabilities { read: vec![dir.join("data")], ..Capabilities::default() });
+
+  assert_eq!(engine.eval(&format!("fs.read_file({});", path(&dir, "data/notes.txt"))), Ok(Value::String("hello".to_string())));
+  assert_eq!(engine.eval(&format!("fs.list_dir({}).len();", path(&dir, "data"))), Ok(Value::Double(1.0)));
+  assert_eq!(engine.eval(&format!("fs.exists({});", path(&dir, "data/missing.txt"))), Ok(Value::Boolean(false)));
+  assert_eq!(error_kind(engine.eval(&format!("fs.read_file({});", path(&dir, "data/missing.txt")))), ErrorKind::IOError);
+
+  assert_eq!(error_kind(engine.eval(&format!("fs.read_file({});", path(&dir, "secret.txt")))), ErrorKind::PermissionError);
+  assert_eq!(error_kind(engine.eval(&format!("fs.read_file({});", path(&dir, "data/../secret.txt")))), ErrorKind::PermissionError);
+  assert_eq!(error_kind(engine.eval(&format!("fs.write_file({}, \"x\");", path(&dir, "data/notes.txt")))), ErrorKind::PermissionError);
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_inside_granted_directories() {
+  let dir = temp_dir("write");
+  let engine = engine(Capabilities { write: vec![dir.join("out")], ..Capabilities::default() });
+
+  engine.eval(&format!("fs.write_file({0}, \"a\"); fs.append({0}, \"b\");", path(&dir, "out/log.txt"))).unwrap();
+  assert_eq!(fs::read_to_string(dir.join("out/log.txt")).unwrap(), "ab");
+  assert_eq!(error_kind(engine.eval(&format!("fs.write_file({}, \"x\");", path(&dir, "data/log.txt")))), ErrorKind::PermissionError);
+  assert_eq!(error_kind(engine.eval(&format!("fs.read_file({});", path(&dir, "out/log.txt")))), ErrorKind::PermissionError);
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_cannot_escape() {
+  let dir = temp_dir("symlink");
+  std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("data/link.txt")).unwrap();
+  let engine = engine(Capabilities { read: vec![dir.join("data")], ..Capabilities::default() });
+
+  assert_eq!(error_kind(engine.eval(&format!("fs.read_file({});", path(&dir, "data/link.txt")))), ErrorKind::PermissionError);
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn denies_everything_by_default() {
+  let engine = Engine::new();
+  let result = engine.eval("var kinds = []; try { fs.exists(\".\"); } catch (e) { kinds.push(e.kind); } try { io.env.get(\"PATH\"); } catch (e) { kinds.push(e.kind); } try { io.stdin.read_line(); } catch (e) { kinds.push(e.kind); } kinds;");
+  assert_eq!(result.map(|kinds| kinds.to_string()), Ok("[\"PermissionError\", \"PermissionError\", \"PermissionError\"]".to_string()));
+  assert_eq!(engine.eval("io.args();").map(|args| args.to_string()), Ok("[]".to_string()));
+}
+
+#[test]
+fn reads_the_environment_and_arguments() {
+  let engine = engine(Capabilities { env: true, args: vec!["--verbose".to_string()], ..Capabilities::default() });
+  assert_eq!(engine.eval("io.env.get(\"RAKI_SURELY_UNSET_VARIABLE\");"), Ok(Value::None));
+  assert!(matches!(engine.eval("io.env.get(\"PATH\");"), Ok(Value::String(_))));
+  assert_eq!(engine.eval("io.args()[0];"), Ok(Value::String("--verbose".to_string())));
+}
+
+#[test]
+fn imports_need_a_read_grant_outside_the_root() {
+  let dir = temp_dir("import");
+  fs::write(dir.join("data/lib.raki"), "export const answer = 42;").unwrap();
+  let import = |file: &str| format!("import {} as m; m.answer;", path(&dir, file));
+
+  let denied = Engine::new();
+  assert_eq!(error_kind(denied.eval(&import("secret.txt"))), ErrorKind::PermissionError);
+  assert_eq!(error_kind(denied.eval(&import("data/missing.raki"))), ErrorKind::PermissionError);
+  assert_eq!(error_kind(denied.eval(&import("data/lib.raki"))), ErrorKind::PermissionError);
+
+  let granted = engine(Capabilities { read: vec![dir.join("data")], ..Capabilities::default() });
+  assert_eq!(granted.eval(&import("data/lib.raki")), Ok(Value::Double(42.0)));
+  assert_eq!(error_kind(granted.eval(&import("data/../secret.txt"))), ErrorKind::PermissionError);
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn imports_relative_paths_only_from_a_script_directory() {
+  // Tests run in the crate root, so this would be Cargo.toml without a grant
+  match Engine::new().eval("import \"Cargo.toml\" as manifest;") {
+    Err(Error::Runtime { kind, message, .. }) => {
+      assert_eq!(kind, ErrorKind::PermissionError);
+      assert!(!message.contains("package"), "{}", message);
+    }
+    result => panic!("expected a runtime error, got {:?}", result),
+  }
+
+  let dir = temp_dir("relative_import");
+  fs::write(dir.join("lib.raki"), "export const answer = 42;").unwrap();
+  fs::write(dir.join("admin.raki"), "admin password").unwrap();
+  fs::write(dir.join("main.raki"), "import \"lib.raki\" as lib;\nlib.answer;").unwrap();
+  fs::write(dir.join("bad.raki"), "import \"admin.raki\" as admin;").unwrap();
+  assert_eq!(Engine::new().run_file(dir.join("main.raki")), Ok(Value::Double(42.0)));
+  match Engine::new().run_file(dir.join("bad.raki")) {
+    Err(Error::Runtime { kind, message, .. }) => {
+      assert_eq!(kind, ErrorKind::ImportError);
+      assert_eq!(message, "Module 'admin.raki' is not valid Raki on line 1.");
+    }
+    result => panic!("expected a runtime error, got {:?}", result),
+  }
+  fs::remove_dir_all(dir).unwrap();
+}
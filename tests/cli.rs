@@ -0,0 +1,19 @@
+use std::fs;
+use std::process::Command;
+
+// Runs `source` as a script file and returns what it printed
+fn run(test: &str, source: &str) -> String {
+  let path = std::env::temp_dir().join(format!("raki_cli_{}_{}.raki", test, std::process::id()));
+  fs::write(&path, source).unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_raki_lang")).arg(&path).output().unwrap();
+  fs::remove_file(path).unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_values_with_sep_and_end() {
+  assert_eq!(run("values", "print 1, \"a\", nil, 0.5;\nprint;"), "1 a nil 0.5\n\n");
+  assert_eq!(run("sep", "print 1, 2, 3, sep: \", \";"), "1, 2, 3\n");
+  assert_eq!(run("end", "print \"a\", end: \"\";\nprint \"b\", \"c\", end: \"!\", sep: \"-\";"), "ab-c!");
+}
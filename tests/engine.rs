@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use raki_lang::{Arity, Engine, Error, ErrorKind, FixedClock, FromRaki, Value, number_argument};
+
+fn temp_dir(test: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("raki_engine_{}_{}", test, std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+#[test]
+fn evaluates_to_the_last_expression() {
+  let engine = Engine::new();
+  assert_eq!(engine.eval("1 + 2;"), Ok(Value::Double(3.0)));
+  assert_eq!(engine.eval("var x = 1;"), Ok(Value::None));
+  assert_eq!(engine.eval("\"a\" + \"b\";"), Ok(Value::String("ab".to_string())));
+}
+
+#[test]
+fn keeps_globals_between_calls() {
+  let engine = Engine::new();
+  engine.eval("var count = 41;").unwrap();
+  engine.eval("count = count + 1;").unwrap();
+  assert_eq!(engine.get_global("count"), Some(Value::Double(42.0)));
+  assert_eq!(engine.get_global("missing"), None);
+
+  engine.set_global("limit", Value::Double(10.0));
+  assert_eq!(engine.eval("limit * 2;"), Ok(Value::Double(20.0)));
+}
+
+#[test]
+fn calls_script_functions() {
+  let engine = Engine::new();
+  engine.eval("fun greet(name) { return \"hello \" + name; }").unwrap();
+  let greet = engine.get_global("greet").unwrap();
+  assert_eq!(engine.call(&greet, vec![Value::String("raki".to_string())]), Ok(Value::String("hello raki".to_string())));
+
+  let Err(Error::Runtime { kind, .. }) = engine.call(&greet, vec![]) else { panic!("expected an arity error") };
+  assert_eq!(kind, ErrorKind::TypeError);
+}
+
+#[test]
+fn registers_native_functions() {
+  let engine = Engine::new();
+  engine.register_native("double", 1, |_, arguments| Ok(Value::Double(number_argument("double", arguments, 0)? * 2.0)));
+  engine.register_native("count", Arity::Variadic(0), |_, arguments| Ok(Value::Double(arguments.len() as f64)));
+
+  assert_eq!(engine.eval("double(21);"), Ok(Value::Double(42.0)));
+  assert_eq!(engine.eval("count(1, 2, 3);"), Ok(Value::Double(3.0)));
+  let err = engine.eval("\ndouble(\"x\");").unwrap_err();
+  assert_eq!(err, Error::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Argument 1 of double() must be a number, not string.".to_string() });
+}
+
+#[test]
+fn reports_errors_by_kind() {
+  let engine = Engine::new();
+  assert!(matches!(engine.eval("var = 1;").unwrap_err(), Error::Analysis(errors) if matches!(errors[..], [Error::Syntax { line: 1, .. }])));
+  assert!(matches!(engine.eval("[1][3];"), Err(Error::Runtime { kind: ErrorKind::IndexError, .. })));
+  assert!(engine.check("fun f() { return 1; }").is_ok());
+  assert!(engine.check("print (;").is_err());
+}
+
+#[test]
+fn reports_every_analysis_error() {
+  let err = Engine::new().eval("var = 1;\nvar = 2;").unwrap_err();
+  let Error::Analysis(errors) = &err else {
+    panic!("expected analysis errors, got {:?}", err);
+  };
+  assert_eq!(errors.iter().map(|err| err.to_string()).collect::<Vec<_>>(), ["Syntax error on line 1 at =: Expect variable name.", "Syntax error on line 2 at =: Expect variable name."]);
+  assert_eq!(err.to_string(), "Syntax error on line 1 at =: Expect variable name.\nSyntax error on line 2 at =: Expect variable name.");
+}
+
+#[test]
+fn runs_files_with_imports() {
+  let dir = temp_dir("imports");
+  fs::write(dir.join("util.raki"), "export fun square(x) { return x * x; }").unwrap();
+  fs::write(dir.join("main.raki"), "import \"util.raki\" as util;\nvar result = util.square(7);").unwrap();
+
+  let engine = Engine::new();
+  engine.run_file(dir.join("main.raki")).unwrap();
+  assert_eq!(engine.get_global("result"), Some(Value::Double(49.0)));
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reports_missing_files() {
+  let engine = Engine::new();
+  let err = engine.run_file("does/not/exist.raki").unwrap_err();
+  assert!(matches!(err, Error::Io { ref path, .. } if path == "does/not/exist.raki"));
+  assert!(err.to_string().starts_with("Could not read does/not/exist.raki"));
+}
+
+#[test]
+fn registers_typed_closures() {
+  let engine = Engine::new();
+  engine.register("add", |a: f64, b: f64| a + b);
+  engine.register("greet", |name: Option<String>| format!("hello {}", name.unwrap_or("world".to_string())));
+  engine.register("total", |items: HashMap<String, Vec<i64>>| items.values().flatten().sum::<i64>());
+  engine.register("pair", |s: String| (s.len() as i64, s.to_uppercase()));
+
+  assert_eq!(engine.eval("add(1, 2);"), Ok(Value::Double(3.0)));
+  assert_eq!(engine.eval("greet(nil);"), Ok(Value::String("hello world".to_string())));
+  assert_eq!(engine.eval("total({\"a\": [1, 2], \"b\": [3]});"), Ok(Value::Double(6.0)));
+  assert_eq!(<(i64, String)>::from_raki(&engine.eval("pair(\"ab\");").unwrap()), Ok((2, "AB".to_string())));
+
+  let err = engine.eval("\nadd(1, \"2\");").unwrap_err();
+  assert_eq!(err, Error::Runtime { kind: ErrorKind::TypeError, line: 2, message: "Argument 2 of add() must be a number, not string.".to_string() });
+  let err = engine.eval("total({\"a\": [1.5]});").unwrap_err();
+  assert_eq!(err, Error::Runtime { kind: ErrorKind::ValueError, line: 1, message: "Argument 1[\"a\"][0] of total() must be an integer, not 1.5.".to_string() });
+  assert!(matches!(engine.eval("add(1);"), Err(Error::Runtime { kind: ErrorKind::TypeError, .. })));
+}
+
+#[test]
+fn replays_runs_with_a_fixed_clock_and_seed() {
+  let run = || {
+    let engine = Engine::new();
+    engine.set_clock(FixedClock::new(0.0));
+    engine.seed(2024);
+    engine.eval("time.sleep(random.int(1, 100)); [time.format(time.timestamp()), random.float()];").unwrap().to_string()
+  };
+  let first = run();
+  assert!(first.starts_with("[\"1970-01-01T00:"));
+  assert_eq!(first, run());
+}
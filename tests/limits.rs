@@ -0,0 +1,137 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use raki_lang::{Engine, Error, LimitKind, Limits, Value};
+
+fn limited(limits: Limits) -> Engine {
+  let engine = Engine::new();
+  engine.set_limits(limits);
+  engine
+}
+
+fn limit_kind(result: Result<Value, Error>) -> LimitKind {
+  match result {
+    Err(Error::Limit { kind, .. }) => kind,
+    result => panic!("expected a limit error, got {:?}", result),
+  }
+}
+
+#[test]
+fn stops_after_too_many_steps() {
+  let engine = limited(Limits { max_steps: Some(10_000), ..Limits::default() });
+  assert_eq!(limit_kind(engine.eval("while (true) {}")), LimitKind::Steps);
+
+  // Every run gets a fresh budget
+  for _ in 0..5 {
+    assert!(engine.eval("var total = 0; for (i in 0..100) { total = total + i; }").is_ok());
+  }
+}
+
+#[test]
+fn stops_at_the_deadline() {
+  let engine = limited(Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() });
+  let start = Instant::now();
+  let err = engine.eval("while (true) {}").unwrap_err();
+  assert!(start.elapsed() < Duration::from_secs(5));
+  assert_eq!(err.to_string(), "TimeLimit: Exceeded the time limit of 50ms.");
+
+  // Sleeping doesn't outlast the deadline either
+  let start = Instant::now();
+  assert_eq!(limit_kind(engine.eval("time.sleep(60);")), LimitKind::Time);
+  assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn limits_recursion() {
+  let engine = limited(Limits { max_call_depth: 20, ..Limits::default() });
+  assert_eq!(limit_kind(engine.eval("fun forever(n) { return forever(n + 1); } forever(0);")), LimitKind::CallDepth);
+  assert_eq!(engine.eval("fun depth(n) { return n == 0 ? 0 : 1 + depth(n - 1); } depth(19);"), Ok(Value::Double(19.0)));
+}
+
+#[test]
+fn limits_allocations() {
+  let engine = limited(Limits { max_allocation: Some(100_000), ..Limits::default() });
+  assert_eq!(limit_kind(engine.eval("var s = \"\"; while (true) { s = s + \"0123456789\"; }")), LimitKind::Allocation);
+  assert_eq!(limit_kind(engine.eval("var list = []; while (true) { list.push(list.len()); }")), LimitKind::Allocation);
+  assert_eq!(limit_kind(engine.eval("var map = {}; var i = 0; while (true) { map[i] = i; i = i + 1; }")), LimitKind::Allocation);
+  assert!(engine.eval("var small = [1, 2, 3].map((x) => x * 2);").is_ok());
+  // Charged before the string is built
+  assert_eq!(limit_kind(engine.eval("\"ab\".repeat(1000000000000);")), LimitKind::Allocation);
+  assert_eq!(limit_kind(engine.eval("\"ab\".pad_left(1000000000000);")), LimitKind::Allocation);
+  // The states a regex search remembers are charged too
+  let engine = limited(Limits { max_allocation: Some(1_000_000), ..Limits::default() });
+  assert_eq!(limit_kind(engine.eval("re.compile(\"x{100}\").search(\"b\".repeat(200000));")), LimitKind::Allocation);
+  assert_eq!(engine.eval("re.compile(\"x{100}\").search(\"b\".repeat(1000));"), Ok(Value::None));
+  // Values that were dropped still count
+  assert_eq!(limit_kind(engine.eval("for (i in 0..100000) { var s = \"ab\" + \"cd\"; }")), LimitKind::Allocation);
+}
+
+#[test]
+fn scripts_cannot_catch_limits() {
+  let engine = limited(Limits { max_steps: Some(1_000), ..Limits::default() });
+  let result = engine.eval("var caught = false; try { while (true) {} } catch (e) { caught = true; } finally { caught = true; }");
+  assert_eq!(limit_kind(result), LimitKind::Steps);
+  assert_eq!(engine.get_global("caught"), Some(Value::Boolean(false)));
+}
+
+#[test]
+fn cancels_from_another_thread() {
+  let engine = Engine::new();
+  let handle = engine.cancel_handle();
+  let canceller = thread::spawn(move || {
+    thread::sleep(Duration::from_millis(50));
+    handle.cancel();
+  });
+
+  let err = engine.eval("while (true) {}").unwrap_err();
+  canceller.join().unwrap();
+  assert_eq!(err.to_string(), "Cancelled: Execution was cancelled.");
+  assert_eq!(limit_kind(engine.eval("1 + 1;")), LimitKind::Cancelled);
+  engine.cancel_handle().reset();
+  assert_eq!(engine.eval("1 + 1;"), Ok(Value::Double(2.0)));
+}
+
+#[test]
+fn cancels_before_the_script_starts() {
+  let engine = Engine::new();
+  engine.cancel_handle().cancel();
+  assert_eq!(limit_kind(engine.eval("var ran = true;")), LimitKind::Cancelled);
+  assert_eq!(engine.get_global("ran"), None);
+}
+
+#[test]
+fn cancels_a_sleeping_script() {
+  let engine = Engine::new();
+  let handle = engine.cancel_handle();
+  let canceller = thread::spawn(move || {
+    thread::sleep(Duration::from_millis(50));
+    handle.cancel();
+  });
+
+  let start = Instant::now();
+  assert_eq!(limit_kind(engine.eval("time.sleep(60);")), LimitKind::Cancelled);
+  canceller.join().unwrap();
+  assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn default_limits_fit_a_spawned_thread() {
+  // Threads get 2 MiB of stack unless asked for more
+  let kind = thread::spawn(|| limit_kind(Engine::new().eval("fun f(n) { return n == 0 ? 0 : 1 + f(n - 1); } f(100000);"))).join().unwrap();
+  assert!(matches!(kind, LimitKind::Stack | LimitKind::CallDepth));
+}
+
+#[test]
+fn limits_deeply_nested_expressions_and_values() {
+  let engine = Engine::new();
+  engine.eval("var deep = []; for (i in 0..2000) { deep = [deep]; }").unwrap();
+
+  // Small enough to run out before a test thread's stack does
+  engine.set_limits(Limits { max_stack: 32 * 1024, ..Limits::default() });
+  let chain = format!("var x = 1{};", " + 1".repeat(200));
+  assert_eq!(limit_kind(engine.eval(&chain)), LimitKind::Stack);
+  assert_eq!(limit_kind(engine.eval("print deep;")), LimitKind::Stack);
+  assert_eq!(limit_kind(engine.eval("format(\"{}\", deep);")), LimitKind::Stack);
+  assert_eq!(limit_kind(engine.eval("json.stringify(deep);")), LimitKind::Stack);
+  assert_eq!(engine.eval("var shallow = [[1]]; json.stringify(shallow);"), Ok(Value::String("[[1]]".to_string())));
+}
@@ -0,0 +1,77 @@
+use raki_lang::{Engine, Error, ErrorKind, Handle, Value};
+
+struct Connection {
+  url: String,
+  timeout: f64,
+  queries: Vec<String>,
+}
+
+struct Config;
+
+fn engine() -> Engine {
+  let engine = Engine::new();
+  engine.register_method("query", |conn: &mut Connection, sql: String| {
+    conn.queries.push(sql.clone());
+    vec![format!("{}: {}", conn.url, sql)]
+  });
+  engine.register_method("count", |conn: &mut Connection| conn.queries.len() as i64);
+  engine.register_getter("url", |conn: &Connection| conn.url.clone());
+  engine.register_getter("timeout", |conn: &Connection| conn.timeout);
+  engine.register_setter("timeout", |conn: &mut Connection, timeout: f64| conn.timeout = timeout);
+  engine.register("close", |conn: Handle<Connection>| conn.borrow_mut().map(|mut conn| conn.queries.clear()));
+  engine.register_method("merge", |conn: &mut Connection, other: Handle<Connection>| -> Result<i64, Error> {
+    conn.queries.extend(other.borrow()?.queries.iter().cloned());
+    Ok(conn.queries.len() as i64)
+  });
+
+  engine.set_global("conn", Value::new_userdata(Connection { url: "db://local".to_string(), timeout: 1.0, queries: Vec::new() }));
+  engine.set_global("config", Value::new_userdata(Config));
+  engine
+}
+
+fn runtime_error(engine: &Engine, source: &str) -> (ErrorKind, String) {
+  match engine.eval(source) {
+    Err(Error::Runtime { kind, message, .. }) => (kind, message),
+    result => panic!("expected a runtime error, got {:?}", result),
+  }
+}
+
+#[test]
+fn calls_methods_on_host_objects() {
+  let engine = engine();
+  assert_eq!(engine.eval("conn.query(\"select 1\")[0];"), Ok(Value::String("db://local: select 1".to_string())));
+  assert_eq!(engine.eval("var q = conn.query; q(\"select 2\"); conn.count();"), Ok(Value::Double(2.0)));
+  assert_eq!(engine.eval("close(conn); conn.count();"), Ok(Value::Double(0.0)));
+  assert_eq!(engine.eval("[conn == conn, conn == config];").map(|value| value.to_string()), Ok("[true, false]".to_string()));
+  assert_eq!(engine.eval("conn;").map(|value| value.to_string()), Ok("<Connection>".to_string()));
+}
+
+#[test]
+fn reads_and_writes_properties() {
+  let engine = engine();
+  assert_eq!(engine.eval("conn.url;"), Ok(Value::String("db://local".to_string())));
+  engine.eval("conn.timeout = conn.timeout + 4;").unwrap();
+  assert_eq!(engine.eval("conn.timeout;"), Ok(Value::Double(5.0)));
+
+  assert_eq!(runtime_error(&engine, "conn.timeout = \"soon\";"), (ErrorKind::TypeError, "Property 'timeout' of Connection must be a number, not string.".to_string()));
+  assert_eq!(runtime_error(&engine, "conn.url = \"x\";"), (ErrorKind::TypeError, "Property 'url' of Connection can't be set.".to_string()));
+  assert_eq!(runtime_error(&engine, "conn.missing;"), (ErrorKind::NameError, "Undefined property 'missing' on Connection.".to_string()));
+}
+
+#[test]
+fn rejects_the_wrong_userdata_type() {
+  let engine = engine();
+  assert_eq!(runtime_error(&engine, "close(config);"), (ErrorKind::TypeError, "Argument 1 of close() must be Connection, not Config.".to_string()));
+  assert_eq!(runtime_error(&engine, "conn.query(1);"), (ErrorKind::TypeError, "Argument 1 of query() must be a string, not number.".to_string()));
+  assert_eq!(runtime_error(&engine, "config.query(\"x\");"), (ErrorKind::NameError, "Undefined property 'query' on Config.".to_string()));
+  assert!(matches!(engine.eval("conn.count(1);"), Err(Error::Runtime { kind: ErrorKind::TypeError, .. })));
+}
+
+#[test]
+fn reports_a_receiver_passed_back_in() {
+  let engine = engine();
+  engine.set_global("other", Value::new_userdata(Connection { url: "db://other".to_string(), timeout: 1.0, queries: vec!["select 1".to_string()] }));
+  assert_eq!(engine.eval("conn.merge(other);"), Ok(Value::Double(1.0)));
+  assert_eq!(runtime_error(&engine, "conn.merge(conn);"), (ErrorKind::RuntimeError, "Connection is already in use.".to_string()));
+  assert_eq!(engine.eval("var kind; try { conn.merge(conn); } catch (e) { kind = e.kind; } kind;"), Ok(Value::String("RuntimeError".to_string())));
+}